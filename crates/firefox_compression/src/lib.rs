@@ -41,13 +41,14 @@
 //! [https://github.com/pierrec/node-lz4/blob/master/lib/binding.js][https://github.com/pierrec/node-lz4/blob/master/lib/binding.js]
 //!
 
-#[cfg(feature = "compression")]
-use byteorder::{ByteOrder, LittleEndian};
 use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
 use std::io;
+use std::num::ParseIntError;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
 
 pub mod node_lz4_port;
 #[cfg(test)]
@@ -58,7 +59,15 @@ pub const MAGIC_HEADER_LENGTH: usize = 8;
 pub const HEADER_LENGTH: usize = 8 + 4;
 
 /// Represents the compression mode to be used.
+///
+/// Can be parsed from and formatted as `default`, `fast:<acceleration>` or
+/// `high:<level>`. This string form is also used when serializing.
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
 pub enum CompressionMode {
     /// High compression with compression parameter
     HIGHCOMPRESSION(i32),
@@ -79,6 +88,143 @@ impl From<CompressionMode> for lz4::block::CompressionMode {
         }
     }
 }
+impl CompressionMode {
+    /// The compression levels that are accepted for [`CompressionMode::HIGHCOMPRESSION`].
+    pub const HIGH_COMPRESSION_LEVELS: RangeInclusive<i32> = 1..=12;
+    /// The compression level that is used if `high` is specified without a level.
+    pub const DEFAULT_HIGH_COMPRESSION_LEVEL: i32 = 9;
+    /// The acceleration values that are accepted for [`CompressionMode::FAST`].
+    pub const FAST_ACCELERATIONS: RangeInclusive<i32> = 1..=65537;
+    /// The acceleration that is used if `fast` is specified without an acceleration.
+    pub const DEFAULT_FAST_ACCELERATION: i32 = 1;
+
+    /// `true` if this is the mode that Firefox uses when compressing data.
+    pub const fn is_default(&self) -> bool {
+        matches!(self, CompressionMode::DEFAULT)
+    }
+
+    /// Create a high compression mode. Higher levels give smaller output but
+    /// are slower to compress.
+    pub fn high_compression(level: i32) -> Result<Self, CompressionModeParseError> {
+        if Self::HIGH_COMPRESSION_LEVELS.contains(&level) {
+            Ok(CompressionMode::HIGHCOMPRESSION(level))
+        } else {
+            Err(CompressionModeParseError::OutOfRange {
+                mode: "high",
+                value: level,
+                range: Self::HIGH_COMPRESSION_LEVELS,
+            })
+        }
+    }
+    /// Create a fast compression mode. Higher acceleration values give faster
+    /// compression but larger output.
+    pub fn fast(acceleration: i32) -> Result<Self, CompressionModeParseError> {
+        if Self::FAST_ACCELERATIONS.contains(&acceleration) {
+            Ok(CompressionMode::FAST(acceleration))
+        } else {
+            Err(CompressionModeParseError::OutOfRange {
+                mode: "fast",
+                value: acceleration,
+                range: Self::FAST_ACCELERATIONS,
+            })
+        }
+    }
+}
+impl fmt::Display for CompressionMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompressionMode::HIGHCOMPRESSION(level) => write!(f, "high:{}", level),
+            CompressionMode::FAST(acceleration) => write!(f, "fast:{}", acceleration),
+            CompressionMode::DEFAULT => write!(f, "default"),
+        }
+    }
+}
+/// Parses `default`, `fast`, `fast:<acceleration>`, `high` or `high:<level>`.
+impl FromStr for CompressionMode {
+    type Err = CompressionModeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, parameter) = match s.trim().split_once(':') {
+            Some((name, parameter)) => (name.trim(), Some(parameter.trim())),
+            None => (s.trim(), None),
+        };
+        let parse_parameter = |mode: &'static str, default: i32| match parameter {
+            None => Ok(default),
+            Some(parameter) => parameter.parse::<i32>().map_err(|error| {
+                CompressionModeParseError::InvalidParameter {
+                    mode,
+                    parameter: parameter.to_owned(),
+                    error,
+                }
+            }),
+        };
+        if name.eq_ignore_ascii_case("default") {
+            if let Some(parameter) = parameter {
+                return Err(CompressionModeParseError::UnexpectedParameter(
+                    parameter.to_owned(),
+                ));
+            }
+            Ok(CompressionMode::DEFAULT)
+        } else if name.eq_ignore_ascii_case("fast") {
+            Self::fast(parse_parameter("fast", Self::DEFAULT_FAST_ACCELERATION)?)
+        } else if name.eq_ignore_ascii_case("high") {
+            Self::high_compression(parse_parameter(
+                "high",
+                Self::DEFAULT_HIGH_COMPRESSION_LEVEL,
+            )?)
+        } else {
+            Err(CompressionModeParseError::UnknownMode(name.to_owned()))
+        }
+    }
+}
+impl TryFrom<String> for CompressionMode {
+    type Error = CompressionModeParseError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+impl From<CompressionMode> for String {
+    fn from(value: CompressionMode) -> Self {
+        value.to_string()
+    }
+}
+
+/// Failed to parse a [`CompressionMode`] from a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompressionModeParseError {
+    UnknownMode(String),
+    UnexpectedParameter(String),
+    InvalidParameter {
+        mode: &'static str,
+        parameter: String,
+        error: ParseIntError,
+    },
+    OutOfRange {
+        mode: &'static str,
+        value: i32,
+        range: RangeInclusive<i32>,
+    },
+}
+impl fmt::Display for CompressionModeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CompressionModeParseError::*;
+        match self {
+            UnknownMode(name) => write!(f, "unknown compression mode \"{}\", expected \"default\", \"fast:<acceleration>\" or \"high:<level>\"", name),
+            UnexpectedParameter(parameter) => write!(f, "the \"default\" compression mode doesn't take a parameter but \"{}\" was specified", parameter),
+            InvalidParameter { mode, parameter, .. } => write!(f, "the parameter \"{}\" for the \"{}\" compression mode isn't a valid integer", parameter, mode),
+            OutOfRange { mode, value, range } => write!(f, "the parameter {} for the \"{}\" compression mode must be between {} and {}", value, mode, range.start(), range.end()),
+        }
+    }
+}
+impl Error for CompressionModeParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use CompressionModeParseError::*;
+        match self {
+            InvalidParameter { error, .. } => Some(error),
+            UnknownMode(_) | UnexpectedParameter(_) | OutOfRange { .. } => None,
+        }
+    }
+}
 
 /// Indicate what library to use for compression.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -118,6 +264,9 @@ impl CompressionLibrary {
 
     /// `true` if the library produces byte perfect compressed files that would
     /// match what Firefox would produce when compressing some data.
+    ///
+    /// This is only true when using the default compression mode, see
+    /// [`CompressionLibrary::same_as_firefox_compression_with_mode`].
     pub const fn same_as_firefox_compression(self) -> bool {
         match self {
             CompressionLibrary::Lz4 => true,
@@ -129,6 +278,17 @@ impl CompressionLibrary {
         }
     }
 
+    /// `true` if the library produces byte perfect compressed files that would
+    /// match what Firefox would produce when compressing some data using the
+    /// specified compression mode. `None` is the same as
+    /// [`CompressionMode::DEFAULT`].
+    pub const fn same_as_firefox_compression_with_mode(
+        self,
+        mode: Option<&CompressionMode>,
+    ) -> bool {
+        self.same_as_firefox_compression() && matches!(mode, None | Some(CompressionMode::DEFAULT))
+    }
+
     pub const fn get_all() -> &'static [Self] {
         macro_rules! all {
             ($($variant:ident),* $(,)?) => {{
@@ -218,6 +378,12 @@ impl SupportedCompressionLibrary {
             SupportedCompressionLibrary::PortedNodeLz4 => CompressionLibrary::PortedNodeLz4,
         }
     }
+
+    /// `true` if [`decompress_to_writer`] can write decompressed data as it is
+    /// decoded instead of buffering all of it in memory first.
+    pub const fn can_decompress_to_writer(self) -> bool {
+        matches!(self, SupportedCompressionLibrary::PortedNodeLz4)
+    }
 }
 impl TryFrom<CompressionLibrary> for SupportedCompressionLibrary {
     type Error = ();
@@ -232,10 +398,15 @@ impl From<SupportedCompressionLibrary> for CompressionLibrary {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum EncoderError {
     UncompressedDataBufferIsTooLong(io::Error),
     InternalCLibraryError(io::Error),
     UnknownError(io::Error),
+    /// The library panicked while compressing. Only libraries where
+    /// [`CompressionLibrary::panic_on_compress`] is `true` are guarded against
+    /// panics. Contains the panic message if it was a string.
+    BackendPanicked(SupportedCompressionLibrary, Option<String>),
 }
 impl fmt::Display for EncoderError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -244,6 +415,8 @@ impl fmt::Display for EncoderError {
             UncompressedDataBufferIsTooLong(_) => write!(f, "Failed to compress data because the uncompressed data buffer was too long."),
             InternalCLibraryError(_) => write!(f, "Failed to compress data because of an internal compression error in the C Library."),
             UnknownError(_) => write!(f, "Failed to compress data."),
+            BackendPanicked(library, None) => write!(f, "Failed to compress data because the {:?} library panicked.", library),
+            BackendPanicked(library, Some(msg)) => write!(f, "Failed to compress data because the {:?} library panicked: {}", library, msg),
         }
     }
 }
@@ -254,6 +427,7 @@ impl Error for EncoderError {
             UncompressedDataBufferIsTooLong(e) => Some(e),
             InternalCLibraryError(e) => Some(e),
             UnknownError(e) => Some(e),
+            BackendPanicked(..) => None,
         }
     }
 }
@@ -267,49 +441,175 @@ impl From<io::Error> for EncoderError {
     }
 }
 
+/// Options for [`Encoder::compress_with_options`]. The default options write
+/// data the same way Firefox does, other options are mostly useful when the
+/// data should be read by something other than Firefox.
+///
+/// Note that `mozLz4` data is a single lz4 block and not an lz4 frame, so frame
+/// settings such as block independence or checksums don't apply to it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EncoderOptions {
+    /// The compression mode, only used by the `Lz4` library.
+    pub mode: Option<CompressionMode>,
+    /// Write the uncompressed size as 4 little endian bytes right before the
+    /// compressed block. This is the same as the `prepend_size` argument of
+    /// `lz4::block::compress`. Firefox doesn't do this since the size is
+    /// already stored in the `mozLz4` header, so it can't read such data.
+    pub prepend_size: bool,
+    /// Don't write the `mozLz4` header (see [`Encoder::get_header`]). Firefox
+    /// can't read data without the header.
+    pub omit_header: bool,
+}
+impl EncoderOptions {
+    /// `true` if these options produce data that Firefox can read.
+    pub fn readable_by_firefox(&self) -> bool {
+        !self.prepend_size && !self.omit_header
+    }
+
+    /// `true` if these options together with `library` produce byte perfect
+    /// compressed files that would match what Firefox would produce, see
+    /// [`CompressionLibrary::same_as_firefox_compression_with_mode`].
+    pub fn same_as_firefox_compression(&self, library: CompressionLibrary) -> bool {
+        self.readable_by_firefox()
+            && library.same_as_firefox_compression_with_mode(self.mode.as_ref())
+    }
+}
+
 pub struct Encoder {
     compressed_data: Vec<u8>,
     uncompressed_size: usize,
     index: usize,
+    write_header: bool,
+    prepend_size: bool,
 }
 impl Encoder {
-    #[allow(unreachable_code, unused_variables)] // <- when all features are disabled
+    /// Compress some data. The `mode` is only used by the `Lz4` library.
+    ///
+    /// Only the default mode produces the same bytes as Firefox would, see
+    /// [`CompressionLibrary::same_as_firefox_compression_with_mode`]. Files
+    /// compressed using other modes can still be read by Firefox.
+    ///
+    /// Libraries that might panic (see [`CompressionLibrary::panic_on_compress`])
+    /// are run inside [`std::panic::catch_unwind`] and a panic is returned as
+    /// [`EncoderError::BackendPanicked`]. The panic hook will still be invoked.
     pub fn compress(
         uncompressed_data: &[u8],
         mode: Option<CompressionMode>,
         library: SupportedCompressionLibrary,
     ) -> Result<Self, EncoderError> {
+        Self::compress_with_options(
+            uncompressed_data,
+            &EncoderOptions {
+                mode,
+                ..EncoderOptions::default()
+            },
+            library,
+        )
+    }
+    /// Compress some data like [`Encoder::compress`] but with more control of
+    /// how the data is written. Check
+    /// [`EncoderOptions::same_as_firefox_compression`] to see if the options
+    /// produce the same data as Firefox would.
+    pub fn compress_with_options(
+        uncompressed_data: &[u8],
+        options: &EncoderOptions,
+        library: SupportedCompressionLibrary,
+    ) -> Result<Self, EncoderError> {
+        let mode = options.mode.clone();
+        // Same as what `lz4::block::compress` writes when it prepends the size,
+        // but that works for every library:
+        let prefix_len = if options.prepend_size { 4 } else { 0 };
+        let mut compressed_data = if library.to_compression_lib().panic_on_compress() {
+            let result = std::panic::catch_unwind(|| {
+                Self::compress_block(uncompressed_data, mode, library, prefix_len)
+            });
+            match result {
+                Ok(result) => result?,
+                Err(payload) => {
+                    let msg = if let Some(msg) = payload.downcast_ref::<String>() {
+                        Some(msg.clone())
+                    } else {
+                        payload
+                            .downcast_ref::<&'static str>()
+                            .map(|msg| msg.to_string())
+                    };
+                    return Err(EncoderError::BackendPanicked(library, msg));
+                }
+            }
+        } else {
+            Self::compress_block(uncompressed_data, mode, library, prefix_len)?
+        };
+        if options.prepend_size {
+            let size = (uncompressed_data.len() as u32).to_le_bytes();
+            compressed_data[..prefix_len].copy_from_slice(&size);
+        }
+
+        Ok(Self {
+            compressed_data,
+            uncompressed_size: uncompressed_data.len(),
+            index: 0,
+            write_header: !options.omit_header,
+            prepend_size: options.prepend_size,
+        })
+    }
+    /// Compress data using `library` without writing any header. The returned
+    /// data starts with `prefix_len` zero bytes that the caller can fill in.
+    #[allow(unreachable_code, unused_variables)] // <- when all features are disabled
+    fn compress_block(
+        uncompressed_data: &[u8],
+        mode: Option<CompressionMode>,
+        library: SupportedCompressionLibrary,
+        prefix_len: usize,
+    ) -> Result<Vec<u8>, EncoderError> {
+        // Libraries that only return a new buffer are copied after the prefix:
+        let after_prefix = |block: Vec<u8>| {
+            if prefix_len == 0 {
+                return block;
+            }
+            let mut data = Vec::with_capacity(prefix_len + block.len());
+            data.resize(prefix_len, 0);
+            data.extend_from_slice(&block);
+            data
+        };
         // TODO: Figure out which compression crates include size as header info before compressed data.
-        let compressed_data = match library {
+        Ok(match library {
             #[cfg(all(feature = "compression_lz4", not(target_family = "wasm")))]
             SupportedCompressionLibrary::Lz4 => {
-                lz4::block::compress(uncompressed_data, mode.map(Into::into), false)?
+                let upper_bound = lz4::block::compress_bound(uncompressed_data.len())?;
+                let mut data = vec![0; prefix_len + upper_bound];
+                let written = lz4::block::compress_to_buffer(
+                    uncompressed_data,
+                    mode.map(Into::into),
+                    false,
+                    &mut data[prefix_len..],
+                )?;
+                data.truncate(prefix_len + written);
+                data
             }
             #[cfg(feature = "compression_compress")]
             SupportedCompressionLibrary::Compress => {
                 let mut data = match compress::lz4::compression_bound(uncompressed_data.len() as u32)
                 {
-                    Some(upper_bound) => Vec::with_capacity(upper_bound as usize),
+                    Some(upper_bound) => Vec::with_capacity(prefix_len + upper_bound as usize),
                     None => Vec::new(),
                 };
+                data.resize(prefix_len, 0);
                 compress::lz4::encode_block(uncompressed_data, &mut data);
                 data
             }
             #[cfg(feature = "compression_lz4_compression")]
             SupportedCompressionLibrary::Lz4Compression => {
-                lz4_compression::compress::compress(uncompressed_data)
+                after_prefix(lz4_compression::compress::compress(uncompressed_data))
             }
             #[cfg(feature = "compression_lz4_compress")]
-            SupportedCompressionLibrary::Lz4Compress => lz4_compress::compress(uncompressed_data),
+            SupportedCompressionLibrary::Lz4Compress => {
+                after_prefix(lz4_compress::compress(uncompressed_data))
+            }
             #[cfg(feature = "compression_lz4_flex")]
-            SupportedCompressionLibrary::Lz4Flex => lz4_flex::compress(uncompressed_data),
+            SupportedCompressionLibrary::Lz4Flex => {
+                after_prefix(lz4_flex::compress(uncompressed_data))
+            }
             SupportedCompressionLibrary::PortedNodeLz4 => unimplemented!(),
-        };
-
-        Ok(Self {
-            compressed_data,
-            uncompressed_size: uncompressed_data.len(),
-            index: 0,
         })
     }
 
@@ -318,19 +618,52 @@ impl Encoder {
         let mut buf = [0; HEADER_LENGTH];
 
         buf[0..MAGIC_HEADER_LENGTH].copy_from_slice(MAGIC_HEADER);
-        #[cfg(feature = "compression")]
-        LittleEndian::write_u32(
-            &mut buf[MAGIC_HEADER_LENGTH..],
-            self.uncompressed_size as u32,
-        );
-        #[cfg(not(feature = "compression"))]
-        unreachable!("No compression feature enabled.");
+        buf[MAGIC_HEADER_LENGTH..].copy_from_slice(&(self.uncompressed_size as u32).to_le_bytes());
         buf
     }
+    /// The number of header bytes this encoder writes, either
+    /// [`HEADER_LENGTH`] or `0` if [`EncoderOptions::omit_header`] was set.
+    fn header_len(&self) -> usize {
+        if self.write_header {
+            HEADER_LENGTH
+        } else {
+            0
+        }
+    }
+    /// The total number of bytes this encoder writes, including the
+    /// [`HEADER_LENGTH`] bytes of header unless the header was omitted. This
+    /// doesn't change as the encoder is read from.
+    pub fn compressed_len(&self) -> usize {
+        self.header_len() + self.compressed_data.len()
+    }
+    /// The number of bytes of data that was compressed.
+    pub fn uncompressed_len(&self) -> usize {
+        self.uncompressed_size
+    }
     /// This will contain the compressed data without the header that should be written before it.
+    ///
+    /// The data starts with the uncompressed size if
+    /// [`EncoderOptions::prepend_size`] was set.
     pub fn get_vec_without_header(self) -> Vec<u8> {
         self.compressed_data
     }
+    /// Decompress the encoder's output using `library` and check that it
+    /// matches the data that was compressed.
+    pub fn verify(
+        &self,
+        uncompressed_data: &[u8],
+        library: SupportedCompressionLibrary,
+    ) -> Result<bool, DecoderError> {
+        let block = if self.prepend_size {
+            &self.compressed_data[4..]
+        } else {
+            &self.compressed_data[..]
+        };
+        let mut data = Vec::with_capacity(HEADER_LENGTH + block.len());
+        data.extend_from_slice(&self.get_header());
+        data.extend_from_slice(block);
+        Ok(decompress(&data, library)? == uncompressed_data)
+    }
 }
 impl io::Read for Encoder {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
@@ -346,12 +679,13 @@ impl io::Read for Encoder {
             n += n_to_write;
             n_to_write
         };
-        if self.index < HEADER_LENGTH {
+        let header_len = self.header_len();
+        if self.index < header_len {
             // Need to write header.
-            self.index += write(&self.get_header());
+            self.index += write(&self.get_header()[self.index..]);
         }
-        if self.index >= HEADER_LENGTH {
-            let data_start = self.index - HEADER_LENGTH;
+        if self.index >= header_len {
+            let data_start = self.index - header_len;
             if data_start < self.compressed_data.len() {
                 self.index += write(&self.compressed_data[data_start..]);
             }
@@ -362,6 +696,7 @@ impl io::Read for Encoder {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DecoderError {
     UncompressedDataBufferIsTooShort(Option<io::Error>, Option<u32>),
     BadHeader([u8; MAGIC_HEADER_LENGTH]),
@@ -370,6 +705,11 @@ pub enum DecoderError {
     TextError(String),
     InvalidDeduplicationOffset,
     PortedNodeLz4Error,
+    /// Failed to write decompressed data, see [`decompress_to_writer`].
+    WriteError(io::Error),
+    /// The header declared a non-zero uncompressed size but no compressed data
+    /// followed it. Contains the declared uncompressed size.
+    EmptyInput(u32),
 }
 impl fmt::Display for DecoderError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -386,7 +726,9 @@ impl fmt::Display for DecoderError {
             BadHeader(d) => write!(f, "Failed to decompress data because of a Bad header: expected \"{:?}\" followed by 4 bytes of uncompressed size but found \"{:?}\"", MAGIC_HEADER, d),
             InvalidDeduplicationOffset => write!(f, "Failed to decompress data because the offset for a de-duplication was out of bounds. The offset to copy was not contained in the decompressed buffer"),
             TextError(s) => write!(f, "Failed to decompress data: {}", s),
-            PortedNodeLz4Error => write!(f, "Failed to decompress data using code ported from the \"node-lz4\" library")
+            PortedNodeLz4Error => write!(f, "Failed to decompress data using code ported from the \"node-lz4\" library"),
+            WriteError(_) => write!(f, "Failed to write decompressed data"),
+            EmptyInput(uncompressed_size) => write!(f, "Failed to decompress data because the header declared {} bytes of uncompressed data but no compressed data followed it", uncompressed_size),
         }
     }
 }
@@ -401,88 +743,300 @@ impl Error for DecoderError {
             InvalidDeduplicationOffset => None,
             TextError(_) => None,
             PortedNodeLz4Error => None,
+            WriteError(e) => Some(e),
+            EmptyInput(_) => None,
         }
     }
 }
 
-pub fn decompress(
-    mut data: &[u8],
-    library: SupportedCompressionLibrary,
-) -> Result<Vec<u8>, DecoderError> {
+/// Check the `mozLz4` header at the start of `data` and return the
+/// uncompressed size that it declares. Only the first [`HEADER_LENGTH`] bytes
+/// are read so this can be used to inspect a file without decompressing it.
+pub fn parse_header(data: &[u8]) -> Result<u32, DecoderError> {
     if data.len() < HEADER_LENGTH {
         return Err(DecoderError::UncompressedDataBufferIsTooShort(None, None));
     }
-    if data.len() < MAGIC_HEADER_LENGTH || &data[..MAGIC_HEADER_LENGTH] != MAGIC_HEADER {
+    if &data[..MAGIC_HEADER_LENGTH] != MAGIC_HEADER {
         let mut header_data = [0; MAGIC_HEADER_LENGTH];
         header_data.copy_from_slice(&data[..MAGIC_HEADER_LENGTH]);
         return Err(DecoderError::BadHeader(header_data));
     }
-    data = &data[MAGIC_HEADER_LENGTH..];
+    let mut uncompressed_size = [0; 4];
+    uncompressed_size.copy_from_slice(&data[MAGIC_HEADER_LENGTH..HEADER_LENGTH]);
+    Ok(u32::from_le_bytes(uncompressed_size))
+}
 
-    #[cfg(not(feature = "compression"))]
-    unreachable!("No compression feature enabled.");
+pub fn decompress(
+    data: &[u8],
+    library: SupportedCompressionLibrary,
+) -> Result<Vec<u8>, DecoderError> {
+    let uncompressed_size = parse_header(data)?;
+    let _data_with_size = &data[MAGIC_HEADER_LENGTH..];
+    let data = &data[HEADER_LENGTH..];
 
-    #[cfg(feature = "compression")]
-    {
-        let _data_with_size = data;
-        let uncompressed_size = LittleEndian::read_u32(data);
-        data = &data[4..];
+    // Libraries don't agree on how to handle an empty block:
+    if data.is_empty() {
+        return if uncompressed_size == 0 {
+            Ok(Vec::new())
+        } else {
+            Err(DecoderError::EmptyInput(uncompressed_size))
+        };
+    }
 
-        match library {
-            #[cfg(all(feature = "compression_lz4", not(target_family = "wasm")))]
-            SupportedCompressionLibrary::Lz4 => {
-                lz4::block::decompress(data, Some(uncompressed_size as i32)).map_err(|e| {
-                    match e.kind() {
-                        io::ErrorKind::InvalidData => DecoderError::InternalCLibraryError(e),
-                        io::ErrorKind::InvalidInput => {
-                            DecoderError::UncompressedDataBufferIsTooShort(
-                                Some(e),
-                                Some(uncompressed_size),
-                            )
-                        }
-                        _ => DecoderError::UnknownIoError(e),
-                    }
-                })
-            }
-            #[cfg(feature = "compression_compress")]
-            SupportedCompressionLibrary::Compress => {
-                let mut uncompressed_data = Vec::with_capacity(uncompressed_size as usize);
-                let _processed_bytes = compress::lz4::decode_block(data, &mut uncompressed_data);
-                Ok(uncompressed_data)
-            }
-            #[cfg(feature = "compression_lz4_compression")]
-            SupportedCompressionLibrary::Lz4Compression => {
-                lz4_compression::decompress::decompress(data).map_err(|e| {
-                    use lz4_compression::decompress::Error::*;
-                    match e {
-                        UnexpectedEnd => DecoderError::UncompressedDataBufferIsTooShort(
-                            None,
-                            Some(uncompressed_size),
-                        ),
-                        InvalidDeduplicationOffset => DecoderError::InvalidDeduplicationOffset,
-                    }
-                })
-            }
-            #[cfg(feature = "compression_lz4_compress")]
-            SupportedCompressionLibrary::Lz4Compress => {
-                lz4_compress::decompress(data).map_err(|e| DecoderError::TextError(e.to_string()))
-            }
-            #[cfg(feature = "compression_lz4_flex")]
-            SupportedCompressionLibrary::Lz4Flex => {
-                lz4_flex::decompress_size_prepended(_data_with_size).map_err(|e| {
-                    use lz4_flex::block::DecompressError::*;
-                    match e {
-                        OffsetOutOfBounds => DecoderError::InvalidDeduplicationOffset,
-                        _ => DecoderError::TextError(e.to_string()),
-                    }
-                })
-            }
-            SupportedCompressionLibrary::PortedNodeLz4 => {
-                let mut output = Vec::with_capacity(uncompressed_size as usize);
-                node_lz4_port::decompress(data, &mut output)
-                    .map_err(|_| DecoderError::PortedNodeLz4Error)?;
-                Ok(output)
-            }
+    match library {
+        #[cfg(all(feature = "compression_lz4", not(target_family = "wasm")))]
+        SupportedCompressionLibrary::Lz4 => {
+            lz4::block::decompress(data, Some(uncompressed_size as i32)).map_err(|e| {
+                match e.kind() {
+                    io::ErrorKind::InvalidData => DecoderError::InternalCLibraryError(e),
+                    io::ErrorKind::InvalidInput => DecoderError::UncompressedDataBufferIsTooShort(
+                        Some(e),
+                        Some(uncompressed_size),
+                    ),
+                    _ => DecoderError::UnknownIoError(e),
+                }
+            })
+        }
+        #[cfg(feature = "compression_compress")]
+        SupportedCompressionLibrary::Compress => {
+            let mut uncompressed_data = Vec::with_capacity(uncompressed_size as usize);
+            let _processed_bytes = compress::lz4::decode_block(data, &mut uncompressed_data);
+            Ok(uncompressed_data)
+        }
+        #[cfg(feature = "compression_lz4_compression")]
+        SupportedCompressionLibrary::Lz4Compression => {
+            lz4_compression::decompress::decompress(data).map_err(|e| {
+                use lz4_compression::decompress::Error::*;
+                match e {
+                    UnexpectedEnd => DecoderError::UncompressedDataBufferIsTooShort(
+                        None,
+                        Some(uncompressed_size),
+                    ),
+                    InvalidDeduplicationOffset => DecoderError::InvalidDeduplicationOffset,
+                }
+            })
         }
+        #[cfg(feature = "compression_lz4_compress")]
+        SupportedCompressionLibrary::Lz4Compress => {
+            lz4_compress::decompress(data).map_err(|e| DecoderError::TextError(e.to_string()))
+        }
+        #[cfg(feature = "compression_lz4_flex")]
+        SupportedCompressionLibrary::Lz4Flex => {
+            lz4_flex::decompress_size_prepended(_data_with_size).map_err(|e| {
+                use lz4_flex::block::DecompressError::*;
+                match e {
+                    OffsetOutOfBounds => DecoderError::InvalidDeduplicationOffset,
+                    _ => DecoderError::TextError(e.to_string()),
+                }
+            })
+        }
+        SupportedCompressionLibrary::PortedNodeLz4 => {
+            let mut output = Vec::with_capacity(uncompressed_size as usize);
+            node_lz4_port::decompress(data, &mut output)
+                .map_err(|_| DecoderError::PortedNodeLz4Error)?;
+            Ok(output)
+        }
+    }
+}
+
+/// Decompress data and write the result to `writer`. Returns the number of
+/// decompressed bytes.
+///
+/// Libraries that support it decode directly into the writer without keeping
+/// the whole decompressed data in memory, see
+/// [`SupportedCompressionLibrary::can_decompress_to_writer`]. Other libraries
+/// decompress into a buffer first, so nothing is written if they fail.
+pub fn decompress_to_writer<W: io::Write>(
+    data: &[u8],
+    library: SupportedCompressionLibrary,
+    mut writer: W,
+) -> Result<usize, DecoderError> {
+    if !library.can_decompress_to_writer() {
+        let decompressed = decompress(data, library)?;
+        writer
+            .write_all(&decompressed)
+            .map_err(DecoderError::WriteError)?;
+        return Ok(decompressed.len());
+    }
+    let uncompressed_size = parse_header(data)?;
+    if data.len() == HEADER_LENGTH {
+        return if uncompressed_size == 0 {
+            Ok(0)
+        } else {
+            Err(DecoderError::EmptyInput(uncompressed_size))
+        };
+    }
+    node_lz4_port::decompress_to_writer(&data[HEADER_LENGTH..], writer).map_err(|e| match e {
+        node_lz4_port::DecodeToWriterError::InvalidInput(_) => DecoderError::PortedNodeLz4Error,
+        node_lz4_port::DecodeToWriterError::Write(e) => DecoderError::WriteError(e),
+    })
+}
+
+/// Decoders that [`decompress_with_fallback`] tries if the preferred library
+/// fails.
+const FALLBACK_DECOMPRESSION_LIBRARIES: &[SupportedCompressionLibrary] = &[
+    #[cfg(feature = "compression_lz4_flex")]
+    SupportedCompressionLibrary::Lz4Flex,
+    SupportedCompressionLibrary::PortedNodeLz4,
+];
+
+/// Decompress data using `library` and if that fails then retry with other
+/// block decoders before giving up. Some third-party tools produce `mozLz4`
+/// files that the preferred library can't handle.
+///
+/// A fallback decoder's output is only accepted if its length matches the
+/// uncompressed size that was stored in the header.
+///
+/// Returns the decompressed data and the library that succeeded. If every
+/// decoder fails then the error from `library` is returned.
+pub fn decompress_with_fallback(
+    data: &[u8],
+    library: SupportedCompressionLibrary,
+) -> Result<(Vec<u8>, SupportedCompressionLibrary), DecoderError> {
+    let error = match decompress(data, library) {
+        Ok(decompressed) => return Ok((decompressed, library)),
+        // The data isn't in the mozLz4 format so no decoder will help:
+        Err(e @ DecoderError::BadHeader(_)) => return Err(e),
+        Err(e @ DecoderError::UncompressedDataBufferIsTooShort(_, None)) => return Err(e),
+        Err(e @ DecoderError::EmptyInput(_)) => return Err(e),
+        Err(e) => e,
+    };
+    let mut uncompressed_size = [0; 4];
+    uncompressed_size.copy_from_slice(&data[MAGIC_HEADER_LENGTH..HEADER_LENGTH]);
+    let uncompressed_size = u32::from_le_bytes(uncompressed_size) as usize;
+
+    FALLBACK_DECOMPRESSION_LIBRARIES
+        .iter()
+        .filter(|&&fallback| fallback != library)
+        .find_map(|&fallback| {
+            let decompressed = decompress(data, fallback).ok()?;
+            (decompressed.len() == uncompressed_size).then_some((decompressed, fallback))
+        })
+        .ok_or(error)
+}
+
+/// Collects data that is provided in multiple chunks and compresses it when
+/// [`CompressorSink::finish`] is called.
+///
+/// The `mozLz4` format stores all data in a single lz4 block that starts with
+/// the uncompressed size, so none of the libraries can start compressing before
+/// all data is known. This sink therefore buffers the data until it is
+/// finished, it only provides a convenient API for data that arrives
+/// incrementally.
+#[derive(Debug, Clone)]
+pub struct CompressorSink {
+    data: Vec<u8>,
+    mode: Option<CompressionMode>,
+    library: SupportedCompressionLibrary,
+}
+impl CompressorSink {
+    /// See [`Encoder::compress`] for info about the arguments.
+    pub fn new(mode: Option<CompressionMode>, library: SupportedCompressionLibrary) -> Self {
+        Self {
+            data: Vec::new(),
+            mode,
+            library,
+        }
+    }
+    /// Add data after any data that was previously pushed.
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.data.extend_from_slice(chunk);
+    }
+    /// The number of bytes that has been pushed.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    /// Compress all data that was pushed.
+    pub fn finish(self) -> Result<Encoder, EncoderError> {
+        Encoder::compress(&self.data, self.mode, self.library)
+    }
+}
+impl io::Write for CompressorSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.push(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Collects compressed data that is provided in multiple chunks and
+/// decompresses it when [`DecompressorSink::finish`] is called.
+///
+/// Like [`CompressorSink`] this buffers all data before decompressing it. The
+/// header is checked as soon as enough data has been pushed so that data in
+/// the wrong format can be rejected early.
+#[derive(Debug, Clone)]
+pub struct DecompressorSink {
+    data: Vec<u8>,
+    library: SupportedCompressionLibrary,
+    /// The start of the data if it didn't have the [`MAGIC_HEADER`].
+    bad_header: Option<[u8; MAGIC_HEADER_LENGTH]>,
+}
+impl DecompressorSink {
+    pub fn new(library: SupportedCompressionLibrary) -> Self {
+        Self {
+            data: Vec::new(),
+            library,
+            bad_header: None,
+        }
+    }
+    /// Add data after any data that was previously pushed. Fails with
+    /// [`DecoderError::BadHeader`] if the data doesn't start with
+    /// [`MAGIC_HEADER`].
+    ///
+    /// After that error the buffered data is dropped and every later call to
+    /// this method or [`DecompressorSink::finish`] fails with the same error.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<(), DecoderError> {
+        if let Some(header_data) = self.bad_header {
+            return Err(DecoderError::BadHeader(header_data));
+        }
+        let had_magic_header = self.data.len() >= MAGIC_HEADER_LENGTH;
+        self.data.extend_from_slice(chunk);
+        if !had_magic_header
+            && self.data.len() >= MAGIC_HEADER_LENGTH
+            && &self.data[..MAGIC_HEADER_LENGTH] != MAGIC_HEADER
+        {
+            let mut header_data = [0; MAGIC_HEADER_LENGTH];
+            header_data.copy_from_slice(&self.data[..MAGIC_HEADER_LENGTH]);
+            self.data = Vec::new();
+            self.bad_header = Some(header_data);
+            return Err(DecoderError::BadHeader(header_data));
+        }
+        Ok(())
+    }
+    /// The number of bytes that has been pushed.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    /// The uncompressed size that is stored in the header, or `None` if not
+    /// enough data has been pushed yet.
+    pub fn uncompressed_size(&self) -> Option<u32> {
+        let size = self.data.get(MAGIC_HEADER_LENGTH..HEADER_LENGTH)?;
+        Some(u32::from_le_bytes(size.try_into().ok()?))
+    }
+    /// Decompress all data that was pushed.
+    pub fn finish(self) -> Result<Vec<u8>, DecoderError> {
+        if let Some(header_data) = self.bad_header {
+            return Err(DecoderError::BadHeader(header_data));
+        }
+        decompress(&self.data, self.library)
+    }
+}
+impl io::Write for DecompressorSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.push(buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
 }
@@ -2,6 +2,7 @@
 
 use std::error::Error;
 use std::fmt;
+use std::io::{self, Write};
 
 pub const MAX_COMPRESSION_INPUT_SIZE: u32 = 0x7E00_0000;
 
@@ -12,26 +13,131 @@ pub const MAX_COMPRESSION_INPUT_SIZE: u32 = 0x7E00_0000;
 ///
 /// This method's code was taken from node-lz4 by Pierre Curto. MIT license.
 pub fn decompress(input: &[u8], output: &mut Vec<u8>) -> Result<usize, usize> {
-    struct Output<'a> {
+    struct VecOutput<'a> {
         data: &'a mut Vec<u8>,
         start_index: usize,
     }
-    impl<'a> Output<'a> {
-        pub fn new(data: &'a mut Vec<u8>) -> Self {
-            let start_index = data.len();
-            Self { data, start_index }
+    impl DecodeOutput for VecOutput<'_> {
+        fn push(&mut self, value: u8) -> io::Result<()> {
+            self.data.push(value);
+            Ok(())
         }
-        pub fn push(&mut self, value: u8) {
-            self.data.push(value)
-        }
-        pub fn decoded_count(&self) -> usize {
+        fn decoded_count(&self) -> usize {
             self.data.len() - self.start_index
         }
-        pub fn get(&self, index: usize) -> Option<&u8> {
-            self.data.get(index)
+        fn get(&self, index: usize) -> Option<u8> {
+            self.data.get(self.start_index + index).copied()
+        }
+    }
+    let start_index = output.len();
+    decode(
+        input,
+        &mut VecOutput {
+            data: output,
+            start_index,
+        },
+    )
+    .map_err(|e| match e {
+        DecodeToWriterError::InvalidInput(offset) => offset,
+        DecodeToWriterError::Write(_) => unreachable!("writing to a Vec can't fail"),
+    })
+}
+
+/// The largest offset that a match copy can refer back to.
+const WINDOW_SIZE: usize = 1 << 16;
+
+/// Decode a block and write the decoded bytes to `writer` as they are
+/// produced. Only the last 64KB of output is kept in memory since match
+/// copies can't refer further back than that. If the return value is `Ok`
+/// then it is the number of decoded bytes.
+pub fn decompress_to_writer<W: Write>(
+    input: &[u8],
+    writer: W,
+) -> Result<usize, DecodeToWriterError> {
+    struct WriterOutput<W> {
+        writer: W,
+        /// The most recently decoded bytes that haven't been written yet.
+        buffer: Vec<u8>,
+        /// Number of bytes that have already been written to `writer`.
+        written: usize,
+    }
+    impl<W: Write> DecodeOutput for WriterOutput<W> {
+        fn push(&mut self, value: u8) -> io::Result<()> {
+            if self.buffer.len() >= 2 * WINDOW_SIZE {
+                let flush_len = self.buffer.len() - WINDOW_SIZE;
+                self.writer.write_all(&self.buffer[..flush_len])?;
+                self.buffer.drain(..flush_len);
+                self.written += flush_len;
+            }
+            self.buffer.push(value);
+            Ok(())
+        }
+        fn decoded_count(&self) -> usize {
+            self.written + self.buffer.len()
+        }
+        fn get(&self, index: usize) -> Option<u8> {
+            let index = index.checked_sub(self.written)?;
+            self.buffer.get(index).copied()
+        }
+    }
+    let mut output = WriterOutput {
+        writer,
+        buffer: Vec::with_capacity(2 * WINDOW_SIZE),
+        written: 0,
+    };
+    let decoded_count = decode(input, &mut output)?;
+    output
+        .writer
+        .write_all(&output.buffer)
+        .and_then(|()| output.writer.flush())
+        .map_err(DecodeToWriterError::Write)?;
+    Ok(decoded_count)
+}
+
+/// An error from [`decompress_to_writer`].
+#[derive(Debug)]
+pub enum DecodeToWriterError {
+    /// The input was malformed at the specified offset.
+    InvalidInput(usize),
+    /// Failed to write decoded data.
+    Write(io::Error),
+}
+impl fmt::Display for DecodeToWriterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeToWriterError::InvalidInput(offset) => {
+                write!(f, "Malformed lz4 block at offset {}.", offset)
+            }
+            DecodeToWriterError::Write(_) => write!(f, "Failed to write decoded data."),
+        }
+    }
+}
+impl Error for DecodeToWriterError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DecodeToWriterError::InvalidInput(_) => None,
+            DecodeToWriterError::Write(e) => Some(e),
         }
     }
-    let mut output = Output::new(output);
+}
+
+/// Somewhere to put decoded bytes.
+trait DecodeOutput {
+    fn push(&mut self, value: u8) -> io::Result<()>;
+    fn decoded_count(&self) -> usize;
+    /// Get a decoded byte, `index` is relative to the first decoded byte.
+    fn get(&self, index: usize) -> Option<u8>;
+}
+
+fn decode(input: &[u8], output: &mut impl DecodeOutput) -> Result<usize, DecodeToWriterError> {
+    // Malformed input should be reported as an error instead of panicking:
+    let byte_at = |i: usize| {
+        input
+            .get(i)
+            .copied()
+            .map(usize::from)
+            .ok_or(DecodeToWriterError::InvalidInput(i))
+    };
 
     // Process each sequence in the incoming data
     let mut i = 0;
@@ -45,15 +151,18 @@ pub fn decompress(input: &[u8], output: &mut Vec<u8>) -> Result<usize, usize> {
             // length of literals
             let mut l = literals_length + 240;
             while l == 255 {
-                l = input[i] as usize;
+                l = byte_at(i)?;
                 i += 1;
                 literals_length += l;
             }
 
             // Copy the literals
             let end = i + literals_length;
+            if end > input.len() {
+                return Err(DecodeToWriterError::InvalidInput(i));
+            }
             while i < end {
-                output.push(input[i]);
+                output.push(input[i]).map_err(DecodeToWriterError::Write)?;
                 i += 1;
             }
 
@@ -65,21 +174,21 @@ pub fn decompress(input: &[u8], output: &mut Vec<u8>) -> Result<usize, usize> {
 
         // Match copy
         // 2 bytes offset (little endian)
-        let mut offset = input[i] as usize;
+        let mut offset = byte_at(i)?;
         i += 1;
-        offset |= (input[i] as usize) << 8;
+        offset |= byte_at(i)? << 8;
         i += 1;
 
         // 0 is an invalid offset value
         if offset == 0 || offset > output.decoded_count() {
-            return Err((i as usize) - 2);
+            return Err(DecodeToWriterError::InvalidInput(i - 2));
         }
 
         // length of match copy
         let mut match_length = (token & 0xf) as usize;
         let mut l = match_length + 240;
         while l == 255 {
-            l = input[i] as usize;
+            l = byte_at(i)?;
             i += 1;
             match_length += l;
         }
@@ -89,10 +198,9 @@ pub fn decompress(input: &[u8], output: &mut Vec<u8>) -> Result<usize, usize> {
         let end = output.decoded_count() + match_length + 4; // minmatch = 4
         while output.decoded_count() < end {
             if let Some(value) = output.get(pos) {
-                let value = *value;
-                output.push(value);
+                output.push(value).map_err(DecodeToWriterError::Write)?;
             } else {
-                return Err(i);
+                return Err(DecodeToWriterError::InvalidInput(i));
             }
             pos += 1;
         }
@@ -1,4 +1,3 @@
-use std::borrow::Cow;
 use std::error::Error;
 use std::fmt;
 use std::io;
@@ -256,6 +255,14 @@ impl Error for CompressValidationError {
 fn test_compression(
     library: super::CompressionLibrary,
     expect_same_compression_as_firefox: bool,
+) -> Result<(), CompressValidationError> {
+    test_compression_with_mode(library, None, expect_same_compression_as_firefox)
+}
+
+fn test_compression_with_mode(
+    library: super::CompressionLibrary,
+    mode: Option<super::CompressionMode>,
+    expect_same_compression_as_firefox: bool,
 ) -> Result<(), CompressValidationError> {
     use super::*;
     use std::convert::TryFrom;
@@ -265,7 +272,7 @@ fn test_compression(
     let library = SupportedCompressionLibrary::try_from(library)
         .map_err(|_| CompressValidationError::NotSupported(library))?;
 
-    let mut encoder = Encoder::compress(test_decompressed_data, None, library)
+    let mut encoder = Encoder::compress(test_decompressed_data, mode, library)
         .map_err(|e| CompressValidationError::ReturnedError(library, e))?;
 
     let mut buf = Vec::new();
@@ -378,6 +385,9 @@ fn test_compression_and_decompression(
 #[test]
 fn decompress_all() {
     for &library in super::CompressionLibrary::get_all() {
+        if library.try_into_supported().is_none() {
+            continue;
+        }
         test_decompress(library).unwrap_pretty();
     }
 }
@@ -399,10 +409,15 @@ macro_rules! individual_decompress {
     };
 }
 individual_decompress![
+    #[cfg(all(feature = "compression_lz4", not(target_family = "wasm")))]
     Lz4 as decompress_lz4,
+    #[cfg(feature = "compression_lz4_compress")]
     Lz4Compress as decompress_lz4_compress,
+    #[cfg(feature = "compression_lz4_compression")]
     Lz4Compression as decompress_lz4_compression,
+    #[cfg(feature = "compression_compress")]
     Compress as decompress_compress,
+    #[cfg(feature = "compression_lz4_flex")]
     Lz4Flex as decompress_lz4_flex,
     PortedNodeLz4 as decompress_ported_node_lz4,
 ];
@@ -414,7 +429,7 @@ individual_decompress![
 #[test]
 fn compress_all() {
     for &library in super::CompressionLibrary::get_all() {
-        if library.panic_on_compress() {
+        if library.panic_on_compress() || library.try_into_supported().is_none() {
             continue;
         }
         test_compression(library, library.same_as_firefox_compression()).unwrap_pretty();
@@ -439,11 +454,16 @@ macro_rules! individual_compress {
     };
 }
 individual_compress![
+    #[cfg(all(feature = "compression_lz4", not(target_family = "wasm")))]
     Lz4 as compress_lz4,
+    #[cfg(feature = "compression_lz4_compress")]
     Lz4Compress as compress_lz4_compress,
+    #[cfg(feature = "compression_lz4_compression")]
     Lz4Compression as compress_lz4_compression,
+    #[cfg(feature = "compression_compress")]
     #[ignore = "this library panics when compressing"]
     Compress as compress_compress,
+    #[cfg(feature = "compression_lz4_flex")]
     Lz4Flex as compress_lz4_flex,
     #[ignore = "haven't ported code for compression yet"]
     PortedNodeLz4 as compress_ported_node_lz4,
@@ -456,7 +476,7 @@ individual_compress![
 #[test]
 fn compress_and_decompress_all() {
     for &library in super::CompressionLibrary::get_all() {
-        if library.panic_on_compress() {
+        if library.panic_on_compress() || library.try_into_supported().is_none() {
             continue;
         }
         test_compression_and_decompression(library).unwrap_pretty();
@@ -480,16 +500,95 @@ macro_rules! individual_compress_and_decompress {
     };
 }
 individual_compress_and_decompress![
+    #[cfg(all(feature = "compression_lz4", not(target_family = "wasm")))]
     Lz4 as compress_and_decompress_lz4,
+    #[cfg(feature = "compression_lz4_compress")]
     Lz4Compress as compress_and_decompress_lz4_compress,
+    #[cfg(feature = "compression_lz4_compression")]
     Lz4Compression as compress_and_decompress_lz4_compression,
+    #[cfg(feature = "compression_compress")]
     #[ignore = "this library panics when compressing"]
     Compress as compress_and_decompress_compress,
+    #[cfg(feature = "compression_lz4_flex")]
     Lz4Flex as compress_and_decompress_lz4_flex,
     #[ignore = "haven't ported code for compression yet"]
     PortedNodeLz4 as compress_and_decompress_ported_node_lz4,
 ];
 
+////////////////////////////////////////////////////////////////////////////////
+// Feature matrix (each backend should work on its own)
+////////////////////////////////////////////////////////////////////////////////
+
+/// Run these with a single backend enabled (e.g. `cargo test
+/// --no-default-features --features compression_lz4_flex`) to check that its
+/// code path compiles and works without the other backends' features.
+mod feature_matrix {
+    use super::{test_compression_and_decompression, test_decompress, PrettyPanic};
+    use crate::{CompressionLibrary, SupportedCompressionLibrary};
+
+    #[test]
+    fn supported_libraries_match_enabled_features() {
+        for &library in CompressionLibrary::get_all() {
+            let enabled = match library {
+                CompressionLibrary::Lz4 => cfg!(all(
+                    feature = "compression_lz4",
+                    not(target_family = "wasm")
+                )),
+                CompressionLibrary::Compress => cfg!(feature = "compression_compress"),
+                CompressionLibrary::Lz4Compression => cfg!(feature = "compression_lz4_compression"),
+                CompressionLibrary::Lz4Compress => cfg!(feature = "compression_lz4_compress"),
+                CompressionLibrary::Lz4Flex => cfg!(feature = "compression_lz4_flex"),
+                CompressionLibrary::PortedNodeLz4 => true,
+            };
+            assert_eq!(
+                SupportedCompressionLibrary::try_from_compression_lib(library).is_some(),
+                enabled,
+                "{:?} should only be supported when its Cargo feature is enabled",
+                library
+            );
+        }
+    }
+
+    macro_rules! backend_in_isolation {
+        ($(  $(#[$attr:meta])*  $variant:ident as $fn_name:ident),* $(,)?) => {
+            const _: fn(CompressionLibrary) = |this| {
+                match this {
+                    $(CompressionLibrary::$variant => {},)*
+                }
+            };
+            $(
+                $(#[$attr])*
+                #[test]
+                fn $fn_name() {
+                    let library = CompressionLibrary::$variant;
+                    assert!(
+                        SupportedCompressionLibrary::try_from_compression_lib(library).is_some(),
+                        "{:?} should be supported when its Cargo feature is enabled",
+                        library
+                    );
+                    test_decompress(library).unwrap_pretty();
+                    if !library.panic_on_compress() {
+                        test_compression_and_decompression(library).unwrap_pretty();
+                    }
+                }
+            )*
+        };
+    }
+    backend_in_isolation![
+        #[cfg(all(feature = "compression_lz4", not(target_family = "wasm")))]
+        Lz4 as lz4,
+        #[cfg(feature = "compression_compress")]
+        Compress as compress,
+        #[cfg(feature = "compression_lz4_compression")]
+        Lz4Compression as lz4_compression,
+        #[cfg(feature = "compression_lz4_compress")]
+        Lz4Compress as lz4_compress,
+        #[cfg(feature = "compression_lz4_flex")]
+        Lz4Flex as lz4_flex,
+        PortedNodeLz4 as ported_node_lz4,
+    ];
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Check that library guarantees are correct
 ////////////////////////////////////////////////////////////////////////////////
@@ -497,11 +596,32 @@ individual_compress_and_decompress![
 #[test]
 fn panic_on_compress_info() {
     for &library in super::CompressionLibrary::get_all() {
-        let panic_err = std::panic::catch_unwind(|| {
-            test_compression(library, false).ok();
-        })
-        .err();
-        let panicked = panic_err.is_some();
+        if library.try_into_supported().is_none() {
+            continue;
+        }
+        let (panicked, panic_msg) =
+            match std::panic::catch_unwind(|| test_compression(library, false)) {
+                Err(panic_err) => {
+                    assert!(
+                        !library.panic_on_compress(),
+                        "a panic from {:?} should have been returned as an error",
+                        library
+                    );
+                    let panic_msg = if let Some(panic_msg) = panic_err.downcast_ref::<String>() {
+                        Some(panic_msg.clone())
+                    } else {
+                        panic_err
+                            .downcast_ref::<&'static str>()
+                            .map(|panic_msg| panic_msg.to_string())
+                    };
+                    (true, panic_msg)
+                }
+                Ok(Err(CompressValidationError::ReturnedError(
+                    _,
+                    super::EncoderError::BackendPanicked(_, panic_msg),
+                ))) => (true, panic_msg),
+                Ok(_) => (false, None),
+            };
         if panicked != library.panic_on_compress() {
             panic!(
                 "panic_on_compress info is incorrect for {:?}. It {} when info indicated that it should{} have.{}{}",
@@ -509,25 +629,40 @@ fn panic_on_compress_info() {
                 if panicked { "panicked" } else { "didn't panic" },
                 if panicked { "'t" } else { "" },
                 if panicked { "\nPanic message:\n" } else { "" },
-                if let Some(panic_err) = panic_err {
-                    if let Some(panic_msg) = panic_err.downcast_ref::<String>() {
-                        Cow::from(panic_msg.clone())
-                    } else if let Some(panic_msg) = panic_err.downcast_ref::<&'static str>() {
-                        Cow::from(*panic_msg)
-                    } else {
-                        Cow::from("")
-                    }
-                } else {
-                    Cow::from("")
-                }
+                panic_msg.unwrap_or_default()
             );
         }
     }
 }
 
+#[test]
+fn panicking_backend_returns_error() {
+    use super::*;
+
+    let result = Encoder::compress(
+        b"some data",
+        None,
+        SupportedCompressionLibrary::PortedNodeLz4,
+    );
+    assert!(
+        matches!(
+            result,
+            Err(EncoderError::BackendPanicked(
+                SupportedCompressionLibrary::PortedNodeLz4,
+                Some(_)
+            ))
+        ),
+        "expected a caught panic, got {:?}",
+        result.err()
+    );
+}
+
 #[test]
 fn same_as_firefox_info() {
     for &library in super::CompressionLibrary::get_all() {
+        if library.try_into_supported().is_none() {
+            continue;
+        }
         let expected = library.same_as_firefox_compression();
         let result = std::panic::catch_unwind(|| test_compression(library, true));
         if matches!(result, Ok(Ok(()))) != expected {
@@ -550,3 +685,528 @@ fn same_as_firefox_info() {
         }
     }
 }
+
+#[test]
+fn same_as_firefox_only_for_default_mode() {
+    use super::{CompressionLibrary, CompressionMode};
+
+    let modes = [
+        None,
+        Some(CompressionMode::DEFAULT),
+        Some(CompressionMode::FAST(4)),
+        Some(CompressionMode::HIGHCOMPRESSION(9)),
+    ];
+    for &library in CompressionLibrary::get_all() {
+        for mode in &modes {
+            let expected = library == CompressionLibrary::Lz4
+                && matches!(mode, None | Some(CompressionMode::DEFAULT));
+            assert_eq!(
+                library.same_as_firefox_compression_with_mode(mode.as_ref()),
+                expected,
+                "`same_as_firefox_compression_with_mode` info is incorrect for {:?} with mode {:?}",
+                library,
+                mode
+            );
+        }
+    }
+}
+
+#[cfg(all(feature = "compression_lz4", not(target_family = "wasm")))]
+#[test]
+fn lz4_default_mode_matches_firefox() {
+    use super::{CompressionLibrary, CompressionMode};
+
+    test_compression_with_mode(CompressionLibrary::Lz4, None, true).unwrap_pretty();
+    test_compression_with_mode(
+        CompressionLibrary::Lz4,
+        Some(CompressionMode::DEFAULT),
+        true,
+    )
+    .unwrap_pretty();
+    assert!(
+        test_compression_with_mode(
+            CompressionLibrary::Lz4,
+            Some(CompressionMode::HIGHCOMPRESSION(9)),
+            true
+        )
+        .is_err(),
+        "high compression mode should produce different data than Firefox"
+    );
+}
+
+#[test]
+fn encoder_verify() {
+    use super::*;
+
+    let test_decompressed_data = include_bytes!("./expected/sessionstore.json");
+    for library in CompressionLibrary::get_all()
+        .iter()
+        .filter(|library| !library.panic_on_compress())
+        .filter_map(|library| library.try_into_supported())
+    {
+        let encoder = Encoder::compress(test_decompressed_data, None, library).unwrap();
+        assert!(
+            encoder.verify(test_decompressed_data, library).unwrap(),
+            "compressed data from {:?} should verify",
+            library
+        );
+        assert!(
+            !encoder.verify(b"other data", library).unwrap(),
+            "compressed data from {:?} should not match other data",
+            library
+        );
+    }
+}
+
+#[test]
+fn encoder_lengths() {
+    use super::*;
+    use std::io::Read;
+
+    let test_compressed_data = include_bytes!("./expected/sessionstore.jsonlz4");
+    let test_decompressed_data = include_bytes!("./expected/sessionstore.json");
+    for library in CompressionLibrary::get_all()
+        .iter()
+        .filter(|library| !library.panic_on_compress())
+        .filter_map(|library| library.try_into_supported())
+    {
+        let mut encoder = Encoder::compress(test_decompressed_data, None, library).unwrap();
+        assert_eq!(encoder.uncompressed_len(), test_decompressed_data.len());
+
+        let compressed_len = encoder.compressed_len();
+        if library.to_compression_lib().same_as_firefox_compression() {
+            assert_eq!(compressed_len, test_compressed_data.len());
+        }
+
+        let mut output = Vec::new();
+        encoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output.len(), compressed_len, "{:?}", library);
+        assert_eq!(encoder.compressed_len(), compressed_len);
+    }
+}
+
+#[test]
+fn encoder_options_across_settings() {
+    use super::*;
+    use std::io::Read;
+
+    let test_decompressed_data = include_bytes!("./expected/sessionstore.json");
+    let size = (test_decompressed_data.len() as u32).to_le_bytes();
+    for library in CompressionLibrary::get_all()
+        .iter()
+        .filter(|library| !library.panic_on_compress())
+        .filter_map(|library| library.try_into_supported())
+    {
+        let block = Encoder::compress(test_decompressed_data, None, library)
+            .unwrap()
+            .get_vec_without_header();
+        for (prepend_size, omit_header) in
+            [(false, false), (true, false), (false, true), (true, true)]
+        {
+            let options = EncoderOptions {
+                mode: None,
+                prepend_size,
+                omit_header,
+            };
+            let mut encoder =
+                Encoder::compress_with_options(test_decompressed_data, &options, library).unwrap();
+            assert!(
+                encoder.verify(test_decompressed_data, library).unwrap(),
+                "{:?} with {:?}",
+                library,
+                options
+            );
+
+            let mut expected = Vec::new();
+            if !omit_header {
+                expected.extend_from_slice(&encoder.get_header());
+            }
+            if prepend_size {
+                expected.extend_from_slice(&size);
+            }
+            expected.extend_from_slice(&block);
+            assert_eq!(encoder.compressed_len(), expected.len());
+
+            let mut output = Vec::new();
+            encoder.read_to_end(&mut output).unwrap();
+            assert!(output == expected, "{:?} with {:?}", library, options);
+
+            assert_eq!(
+                options.same_as_firefox_compression(library.to_compression_lib()),
+                library.to_compression_lib().same_as_firefox_compression()
+                    && !prepend_size
+                    && !omit_header
+            );
+        }
+    }
+}
+
+#[cfg(all(feature = "compression_lz4", not(target_family = "wasm")))]
+#[test]
+fn encoder_prepend_size_matches_lz4() {
+    use super::*;
+
+    let test_decompressed_data = include_bytes!("./expected/sessionstore.json");
+    let options = EncoderOptions {
+        mode: None,
+        prepend_size: true,
+        omit_header: true,
+    };
+    let encoder = Encoder::compress_with_options(
+        test_decompressed_data,
+        &options,
+        SupportedCompressionLibrary::Lz4,
+    )
+    .unwrap();
+    let expected = lz4::block::compress(test_decompressed_data, None, true).unwrap();
+    assert!(encoder.get_vec_without_header() == expected);
+}
+
+#[test]
+fn sinks_accept_chunks() {
+    use super::*;
+
+    let test_compressed_data = include_bytes!("./expected/sessionstore.jsonlz4");
+    let test_decompressed_data = include_bytes!("./expected/sessionstore.json");
+
+    let mut decompressor = DecompressorSink::new(SupportedCompressionLibrary::PortedNodeLz4);
+    for chunk in test_compressed_data.chunks(5) {
+        decompressor.push(chunk).unwrap_pretty();
+    }
+    assert_eq!(
+        decompressor.uncompressed_size(),
+        Some(test_decompressed_data.len() as u32)
+    );
+    let decompressed = decompressor.finish().unwrap_pretty();
+    assert!(decompressed == test_decompressed_data[..]);
+
+    for library in CompressionLibrary::get_all()
+        .iter()
+        .filter(|library| !library.panic_on_compress())
+        .filter_map(|library| library.try_into_supported())
+    {
+        let mut compressor = CompressorSink::new(None, library);
+        for chunk in test_decompressed_data.chunks(1000) {
+            compressor.push(chunk);
+        }
+        assert_eq!(compressor.len(), test_decompressed_data.len());
+        let encoder = compressor.finish().unwrap();
+        assert!(
+            encoder.verify(test_decompressed_data, library).unwrap(),
+            "{:?}",
+            library
+        );
+    }
+}
+
+#[test]
+fn decompressor_sink_rejects_bad_header_early() {
+    use super::*;
+
+    let mut decompressor = DecompressorSink::new(SupportedCompressionLibrary::PortedNodeLz4);
+    decompressor.push(b"{\"ver").unwrap_pretty();
+    assert_eq!(decompressor.uncompressed_size(), None);
+    assert!(matches!(
+        decompressor.push(b"sion\":1}"),
+        Err(DecoderError::BadHeader(_))
+    ));
+    assert!(decompressor.is_empty());
+
+    // The sink stays unusable even if valid data is pushed afterwards:
+    assert!(matches!(
+        decompressor.push(MAGIC_HEADER),
+        Err(DecoderError::BadHeader(_))
+    ));
+    assert!(decompressor.is_empty());
+    assert!(matches!(
+        decompressor.finish(),
+        Err(DecoderError::BadHeader(header)) if &header == b"{\"versio"
+    ));
+}
+
+#[test]
+fn parse_header_reads_uncompressed_size() {
+    use super::*;
+
+    let test_compressed_data = include_bytes!("./expected/sessionstore.jsonlz4");
+    let test_decompressed_data = include_bytes!("./expected/sessionstore.json");
+
+    assert_eq!(
+        parse_header(&test_compressed_data[..HEADER_LENGTH]).unwrap_pretty(),
+        test_decompressed_data.len() as u32
+    );
+    assert!(matches!(
+        parse_header(&test_compressed_data[..HEADER_LENGTH - 1]),
+        Err(DecoderError::UncompressedDataBufferIsTooShort(None, None))
+    ));
+    assert!(matches!(
+        parse_header(&test_decompressed_data[..HEADER_LENGTH]),
+        Err(DecoderError::BadHeader(_))
+    ));
+}
+
+#[test]
+fn decompress_empty_payload() {
+    use super::*;
+
+    let mut empty = MAGIC_HEADER.to_vec();
+    empty.extend_from_slice(&0u32.to_le_bytes());
+    for library in CompressionLibrary::get_all()
+        .iter()
+        .filter_map(|library| library.try_into_supported())
+    {
+        assert_eq!(
+            decompress(&empty, library).unwrap_pretty(),
+            Vec::<u8>::new(),
+            "{:?}",
+            library
+        );
+        let mut output = Vec::new();
+        assert_eq!(
+            decompress_to_writer(&empty, library, &mut output).unwrap_pretty(),
+            0
+        );
+        assert!(output.is_empty());
+    }
+}
+
+#[test]
+fn decompress_missing_payload() {
+    use super::*;
+
+    let mut missing = MAGIC_HEADER.to_vec();
+    missing.extend_from_slice(&10u32.to_le_bytes());
+    for library in CompressionLibrary::get_all()
+        .iter()
+        .filter_map(|library| library.try_into_supported())
+    {
+        assert!(
+            matches!(
+                decompress(&missing, library),
+                Err(DecoderError::EmptyInput(10))
+            ),
+            "{:?}",
+            library
+        );
+        assert!(matches!(
+            decompress_to_writer(&missing, library, io::sink()),
+            Err(DecoderError::EmptyInput(10))
+        ));
+    }
+    assert!(matches!(
+        decompress_with_fallback(&missing, SupportedCompressionLibrary::PortedNodeLz4),
+        Err(DecoderError::EmptyInput(10))
+    ));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Fallback decompression
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn decompress_with_fallback_prefers_library() {
+    use super::*;
+
+    let test_compressed_data = include_bytes!("./expected/sessionstore.jsonlz4");
+    let test_decompressed_data = include_bytes!("./expected/sessionstore.json");
+
+    let (decompressed, library) = decompress_with_fallback(
+        test_compressed_data,
+        SupportedCompressionLibrary::PortedNodeLz4,
+    )
+    .unwrap_pretty();
+    assert_eq!(library, SupportedCompressionLibrary::PortedNodeLz4);
+    assert!(decompressed == test_decompressed_data[..]);
+}
+
+#[test]
+fn decompress_with_fallback_fails_on_truncated_data() {
+    use super::*;
+
+    let test_compressed_data = include_bytes!("./expected/sessionstore.jsonlz4");
+    let truncated = &test_compressed_data[..test_compressed_data.len() / 2];
+
+    // Should return an error instead of panicking or returning partial data:
+    assert!(
+        decompress_with_fallback(truncated, SupportedCompressionLibrary::PortedNodeLz4).is_err()
+    );
+    assert!(matches!(
+        decompress_with_fallback(
+            b"not mozLz4 data",
+            SupportedCompressionLibrary::PortedNodeLz4
+        ),
+        Err(DecoderError::BadHeader(_))
+    ));
+}
+
+#[test]
+#[cfg(all(feature = "compression_lz4", not(target_family = "wasm")))]
+fn decompress_with_fallback_recovers_rejected_data() {
+    use super::*;
+
+    // A block that ends with a match instead of literals. The lz4 C library
+    // enforces the end of block rules and rejects it while the other decoders
+    // don't care:
+    let block = [0x10, b'a', 0x01, 0x00];
+    let mut data = MAGIC_HEADER.to_vec();
+    data.extend_from_slice(&5u32.to_le_bytes());
+    data.extend_from_slice(&block);
+
+    assert!(decompress(&data, SupportedCompressionLibrary::Lz4).is_err());
+    let (decompressed, library) =
+        decompress_with_fallback(&data, SupportedCompressionLibrary::Lz4).unwrap_pretty();
+    assert_ne!(library, SupportedCompressionLibrary::Lz4);
+    assert_eq!(decompressed, b"aaaaa");
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Decompress to writer
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn decompress_to_writer_matches_decompress() {
+    use super::*;
+
+    let test_compressed_data = include_bytes!("./expected/sessionstore.jsonlz4");
+    let test_decompressed_data = include_bytes!("./expected/sessionstore.json");
+
+    let mut output = Vec::new();
+    let len = decompress_to_writer(
+        test_compressed_data,
+        SupportedCompressionLibrary::PortedNodeLz4,
+        &mut output,
+    )
+    .unwrap_pretty();
+    assert_eq!(len, output.len());
+    assert!(output == test_decompressed_data[..]);
+}
+
+#[test]
+fn decompress_to_writer_copies_matches_across_flushes() {
+    use super::*;
+
+    /// Encode a length that doesn't fit in a token's nibble.
+    fn push_length(block: &mut Vec<u8>, mut length: usize) {
+        while length >= 255 {
+            block.push(255);
+            length -= 255;
+        }
+        block.push(length as u8);
+    }
+
+    // Pseudo-random literals followed by a long match at the max offset so
+    // that the streaming decoder must flush while still copying the match.
+    let literals: Vec<u8> = (0..70_000u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect();
+    let offset = 0xFFFF;
+    let match_length = 300_000;
+
+    let mut block = vec![0xFF];
+    push_length(&mut block, literals.len() - 15);
+    block.extend_from_slice(&literals);
+    block.extend_from_slice(&(offset as u16).to_le_bytes());
+    push_length(&mut block, match_length - 15 - 4);
+    // Last sequence contains only literals:
+    block.extend_from_slice(&[0x10, b'!']);
+
+    let mut expected = literals.clone();
+    for _ in 0..match_length {
+        expected.push(expected[expected.len() - offset]);
+    }
+    expected.push(b'!');
+
+    let mut data = MAGIC_HEADER.to_vec();
+    data.extend_from_slice(&(expected.len() as u32).to_le_bytes());
+    data.extend_from_slice(&block);
+
+    let mut output = Vec::new();
+    decompress_to_writer(
+        &data,
+        SupportedCompressionLibrary::PortedNodeLz4,
+        &mut output,
+    )
+    .unwrap_pretty();
+    assert!(output == expected);
+    assert!(
+        decompress(&data, SupportedCompressionLibrary::PortedNodeLz4).unwrap_pretty() == expected
+    );
+}
+
+#[test]
+fn decompress_to_writer_reports_write_errors() {
+    use super::*;
+
+    struct FailingWriter;
+    impl io::Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("write failed"))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let test_compressed_data = include_bytes!("./expected/sessionstore.jsonlz4");
+    assert!(matches!(
+        decompress_to_writer(
+            test_compressed_data,
+            SupportedCompressionLibrary::PortedNodeLz4,
+            FailingWriter
+        ),
+        Err(DecoderError::WriteError(_))
+    ));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Parse compression modes
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn parse_compression_mode() {
+    use super::CompressionMode;
+
+    for (text, expected) in [
+        ("default", CompressionMode::DEFAULT),
+        ("DEFAULT", CompressionMode::DEFAULT),
+        ("fast", CompressionMode::FAST(1)),
+        ("fast:4", CompressionMode::FAST(4)),
+        ("high", CompressionMode::HIGHCOMPRESSION(9)),
+        (" high : 12 ", CompressionMode::HIGHCOMPRESSION(12)),
+    ] {
+        assert_eq!(text.parse::<CompressionMode>(), Ok(expected.clone()));
+        assert_eq!(expected.to_string().parse(), Ok(expected));
+    }
+}
+
+#[test]
+fn parse_invalid_compression_mode() {
+    use super::{CompressionMode, CompressionModeParseError};
+
+    for text in [
+        "",
+        "slow",
+        "default:1",
+        "fast:",
+        "fast:x",
+        "fast:0",
+        "fast:65538",
+        "high:0",
+        "high:13",
+    ] {
+        let error = text.parse::<CompressionMode>().unwrap_err();
+        assert!(
+            !error.to_string().is_empty(),
+            "error for {:?} should have a message",
+            text
+        );
+    }
+    assert!(matches!(
+        "high:13".parse::<CompressionMode>(),
+        Err(CompressionModeParseError::OutOfRange {
+            mode: "high",
+            value: 13,
+            ..
+        })
+    ));
+}
@@ -6,6 +6,7 @@ use either::*;
 use eyre::{bail, ContextCompat, WrapErr};
 
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
@@ -147,43 +148,236 @@ impl FirefoxProfileFinder {
         };
 
         if profile_paths.peek().is_some() {
-            // List possible profiles (with a max count if there are too many):
-
-            let possible_profiles = iter::once(first)
-                .chain(&mut profile_paths)
-                .take(5)
-                .map(|(path, _)| path)
-                // Make string that can be displayed:
-                .map(path_to_file_name)
-                .collect::<Vec<_>>()
-                .join("\n");
-
-            let more_count = if profile_paths.peek().is_some() {
-                Cow::from(format!("\n...and {} more", profile_paths.count()))
-            } else {
-                Cow::from("")
-            };
-
-            let mut error: Result<_> = Err(eyre::eyre!(
-                "More than one Firefox profile was found with the specified name.\n\nPossible profile directories:\n{}{}\n\n",
-                possible_profiles,
-                more_count
-            ));
-            if let Some((path, _)) = profiles
+            return Self::multiple_profiles_error(
+                profiles,
+                iter::once(first).chain(profile_paths),
+                "More than one Firefox profile was found with the specified name.",
+            );
+        }
+
+        Ok(Some(first.0.clone()))
+    }
+    /// Find a Firefox profile whose directory name matches a wildcard pattern
+    /// where `*` matches any number of characters and `?` matches a single
+    /// character, for example `*.dev-edition-default`. If several profiles
+    /// match then the latest modified one is used.
+    ///
+    /// Returns `None` if no profile matched. Returns an error if multiple
+    /// profiles match and it isn't clear which one was modified last.
+    pub fn find_profile_by_glob(&self, pattern: &str) -> Result<Option<PathBuf>> {
+        let profiles = self.all_profiles()?;
+
+        let matches = profiles
+            .iter()
+            .filter(|(entry, _)| {
+                entry
+                    .file_name()
+                    .is_some_and(|name| wildcard_match(pattern, &name.to_string_lossy()))
+            })
+            .collect::<Vec<_>>();
+
+        match matches.as_slice() {
+            [] => {
+                log::debug!(
+                    "No profile folders matched {pattern:?} (possible_profiles: {})",
+                    profiles.len()
+                );
+                return Ok(None);
+            }
+            [(path, _)] => return Ok(Some(path.clone())),
+            _ => {}
+        }
+
+        // Only pick the latest modified profile if all modification times are
+        // known and no other profile was modified at the same time:
+        let times = matches
+            .iter()
+            .map(|(_, time)| time.as_ref().ok())
+            .collect::<Option<Vec<_>>>();
+        if let Some(times) = times {
+            let latest = times.iter().max().copied();
+            let mut latest_matches = matches
                 .iter()
-                // Ignore profile directories with unknown modification time:
-                .filter_map(|(p, time)| Some((p, time.as_ref().ok()?)))
-                // Then find the latest modified one:
-                .max_by_key(|(_, &time)| time)
-            {
-                let path = path_to_file_name(path);
-                error = error.suggestion(format!(r#"of the found Firefox profiles the "{path}" profile is the latest modified, maybe that is the one you want?"#));
+                .zip(&times)
+                .filter(|(_, &time)| Some(time) == latest);
+            if let (Some(((path, _), _)), None) = (latest_matches.next(), latest_matches.next()) {
+                info!(
+                    r#"{} Firefox profiles matched {pattern:?}, using the latest modified one: "{}""#,
+                    matches.len(),
+                    path_to_file_name(path)
+                );
+                return Ok(Some(path.clone()));
             }
-            return error;
         }
 
-        Ok(Some(first.0.clone()))
+        Self::multiple_profiles_error(
+            profiles,
+            matches.into_iter(),
+            "More than one Firefox profile matched the specified pattern and none of them was clearly the latest modified.",
+        )
+    }
+    /// Create an error that lists some of the profiles that matched a query.
+    fn multiple_profiles_error<'a, T>(
+        profiles: &[(PathBuf, io::Result<SystemTime>)],
+        matches: impl Iterator<Item = &'a (PathBuf, io::Result<SystemTime>)>,
+        message: &str,
+    ) -> Result<T> {
+        let mut matches = matches.peekable();
+
+        // List possible profiles (with a max count if there are too many):
+
+        let possible_profiles = (&mut matches)
+            .take(5)
+            .map(|(path, _)| path)
+            // Make string that can be displayed:
+            .map(path_to_file_name)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let more_count = if matches.peek().is_some() {
+            Cow::from(format!("\n...and {} more", matches.count()))
+        } else {
+            Cow::from("")
+        };
+
+        let mut error: Result<_> = Err(eyre::eyre!(
+            "{}\n\nPossible profile directories:\n{}{}\n\n",
+            message,
+            possible_profiles,
+            more_count
+        ));
+        if let Some((path, _)) = profiles
+            .iter()
+            // Ignore profile directories with unknown modification time:
+            .filter_map(|(p, time)| Some((p, time.as_ref().ok()?)))
+            // Then find the latest modified one:
+            .max_by_key(|(_, &time)| time)
+        {
+            let path = path_to_file_name(path);
+            error = error.suggestion(format!(r#"of the found Firefox profiles the "{path}" profile is the latest modified, maybe that is the one you want?"#));
+        }
+        error
+    }
+}
+
+/// Check if some text matches a wildcard pattern where `*` matches any number
+/// of characters and `?` matches exactly one character. Letters are compared
+/// case-insensitively since Windows file names are case-insensitive.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` in the pattern and the text position it was
+    // tried at, used to backtrack when a later part fails to match:
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c.eq_ignore_ascii_case(&text[t]) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    // Let the last `*` consume one more character:
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
     }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Name of the sub directory inside a Firefox profile that contains backups of
+/// the sessionstore file.
+pub const SESSION_BACKUP_DIR: &str = "sessionstore-backups";
+
+/// Name of the file inside a Firefox profile where Firefox records how far it
+/// got during its last startup and shutdown.
+pub const SESSION_CHECKPOINTS_FILE: &str = "sessionCheckpoints.json";
+
+/// The last checkpoint that Firefox records when it shuts down cleanly, after
+/// the final sessionstore file has been written.
+const FINAL_SESSION_CHECKPOINT: &str = "sessionstore-final-state-write-complete";
+
+/// Check if the content of a "sessionCheckpoints.json" file indicates that
+/// Firefox shut down cleanly, meaning it wasn't running anymore and didn't
+/// crash.
+pub fn is_clean_shutdown(checkpoints: &BTreeMap<String, bool>) -> bool {
+    checkpoints
+        .get(FINAL_SESSION_CHECKPOINT)
+        .copied()
+        .unwrap_or(false)
+}
+
+/// Read the "sessionCheckpoints.json" file of a Firefox profile and check if
+/// Firefox shut down cleanly. Returns `None` if the profile has no such file.
+pub fn read_clean_shutdown(profile_dir: &Path) -> Result<Option<bool>> {
+    let path = profile_dir.join(SESSION_CHECKPOINTS_FILE);
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read \"{}\"", path.display())),
+    };
+    let checkpoints = serde_json::from_slice(&data)
+        .with_context(|| format!("Failed to parse \"{}\"", path.display()))?;
+    Ok(Some(is_clean_shutdown(&checkpoints)))
+}
+
+/// Find the sessionstore file of a Firefox profile. Checks for the
+/// "sessionstore" file and then the "sessionstore-backups/recovery" file with
+/// each of the file extensions in turn.
+pub fn default_session_file(
+    profile_dir: &Path,
+    file_extensions: &[Cow<'static, str>],
+) -> Option<PathBuf> {
+    let backup_dir = profile_dir.join(SESSION_BACKUP_DIR);
+
+    for extension in file_extensions.iter() {
+        // Check if `sessionstore.` exists.
+
+        let mut path = profile_dir.join("sessionstore");
+        path.set_extension(&**extension);
+        info!(r#"Checking for input file at: "{}""#, path.display());
+        if path.is_file() {
+            return Some(path);
+        }
+
+        let mut path = backup_dir.join("recovery");
+        path.set_extension(&**extension);
+        info!(r#"Checking for input file at: "{}""#, path.display());
+        if path.is_file() {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Replace characters that aren't allowed in file names on Windows and limit
+/// the name's length so that text like a window's name can be used as part of
+/// a file name.
+pub fn sanitize_file_name(name: &str) -> String {
+    const MAX_CHARS: usize = 100;
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .take(MAX_CHARS)
+        .collect::<String>();
+    // Windows doesn't allow names that end with a dot or space:
+    name.trim_end_matches(['.', ' ']).trim_start().to_owned()
 }
 
 /// Convert a path to a filename. Useful for logging.
@@ -306,11 +500,16 @@ pub fn create_file(overwrite: bool, path: impl AsRef<Path>) -> io::Result<File>
 /// * If path is a folder (ends with `/` or `\`) then append `default_name`.
 /// * If path's file extension is empty then append `default_extension`.
 /// * If `overwrite` is `false` then attempt to find an unused path.
+/// * If `replace_latest` is `true` (and `overwrite` is `false`) then return the
+///   existing file with the highest number instead of an unused path, so the
+///   caller should overwrite the file at the returned path. An unused path is
+///   only returned if no such file exists yet.
 ///
 /// The `default_extension` should be without a leading dot.
 pub fn resolve_to_unused_path(
     path: Option<String>,
     overwrite: bool,
+    replace_latest: bool,
     default_name: Cow<str>,
     default_extension: Cow<str>,
 ) -> Result<String> {
@@ -358,6 +557,27 @@ pub fn resolve_to_unused_path(
             .context("Couldn't get the current working directory.")?
             .join(path);
 
+        if replace_latest {
+            let latest = fs::read_dir(&dir)
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    if !entry.file_type().ok()?.is_file() {
+                        return None;
+                    }
+                    let name = entry.file_name().into_string().ok()?;
+                    let index = numbered_file_index(&name, &file_name, &extension)?;
+                    Some((index, name))
+                })
+                .max_by_key(|(index, _)| *index);
+            if let Some((_, latest)) = latest {
+                let mut dir = dir_nav;
+                dir.push_str(&latest);
+                return Ok(dir);
+            }
+        }
+
         Ok(generate_file_names(dir, move |index| {
             format!(
                 "{}{}.{}",
@@ -388,6 +608,24 @@ pub fn resolve_to_unused_path(
     }
 }
 
+/// Get the number of a file name that [`resolve_to_unused_path`] could have
+/// generated, `"{file_name}.{extension}"` has the number `0` and
+/// `"{file_name} (3).{extension}"` has the number `3`.
+pub fn numbered_file_index(candidate: &str, file_name: &str, extension: &str) -> Option<u32> {
+    let rest = candidate
+        .strip_prefix(file_name)?
+        .strip_suffix(extension)?
+        .strip_suffix('.')?;
+    if rest.is_empty() {
+        return Some(0);
+    }
+    let index = rest.strip_prefix(" (")?.strip_suffix(')')?;
+    if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    index.parse().ok()
+}
+
 /// Create an iterator that generates file names.
 pub fn generate_file_names<R>(
     dir: impl Into<PathBuf>,
@@ -404,3 +642,51 @@ where
         target
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbered_output_file_names() {
+        assert_eq!(numbered_file_index("tabs.txt", "tabs", "txt"), Some(0));
+        assert_eq!(
+            numbered_file_index("tabs (12).txt", "tabs", "txt"),
+            Some(12)
+        );
+        assert_eq!(numbered_file_index("tabs (1).html", "tabs", "txt"), None);
+        assert_eq!(numbered_file_index("tabs (+1).txt", "tabs", "txt"), None);
+        assert_eq!(numbered_file_index("tabs ().txt", "tabs", "txt"), None);
+        assert_eq!(numbered_file_index("tabs-old.txt", "tabs", "txt"), None);
+        assert_eq!(numbered_file_index("tabstxt", "tabs", "txt"), None);
+    }
+
+    #[test]
+    fn profile_globs_match_directory_names() {
+        assert!(wildcard_match(
+            "*.dev-edition-default",
+            "wscs2ifj.dev-edition-default"
+        ));
+        assert!(wildcard_match("*.DEFAULT*", "wscs2ifj.default-release"));
+        assert!(wildcard_match("????????.default", "wscs2ifj.default"));
+        assert!(wildcard_match("*a*b*", "xaxxbx"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("*.default", "wscs2ifj.default-release"));
+        assert!(!wildcard_match("???.default", "wscs2ifj.default"));
+        assert!(!wildcard_match("*a*b", "xaxxbx"));
+    }
+
+    #[test]
+    fn session_checkpoints_show_clean_shutdowns() {
+        let checkpoints = |json: &str| serde_json::from_str(json).unwrap();
+
+        assert!(is_clean_shutdown(&checkpoints(
+            r#"{"profile-after-change":true,"final-ui-startup":true,"sessionstore-windows-restored":true,"quit-application-granted":true,"quit-application":true,"sessionstore-final-state-write-complete":true,"profile-change-net-teardown":true,"profile-change-teardown":true,"profile-before-change":true}"#
+        )));
+        // Firefox is still running or crashed:
+        assert!(!is_clean_shutdown(&checkpoints(
+            r#"{"profile-after-change":true,"final-ui-startup":true,"sessionstore-windows-restored":true}"#
+        )));
+        assert!(!is_clean_shutdown(&checkpoints("{}")));
+    }
+}
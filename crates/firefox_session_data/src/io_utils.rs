@@ -4,11 +4,13 @@ use std::{
     error::Error as StdError,
     fmt,
     fs::File,
-    io::{self, BufReader, BufWriter, Read, StdoutLock},
+    io::{self, BufRead, BufReader, BufWriter, Read, StdoutLock, Write},
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
+use color_eyre::Section;
 use either::*;
 use eyre::WrapErr;
 use html_to_pdf::{WriteBuilder, WriteBuilderLifetime};
@@ -24,8 +26,12 @@ use crate::{compression, find, Result};
 pub enum JSONCompression {
     Lz4Compression,
     NoCompression,
+    /// Decompress the data only if it starts with the `mozLz4` magic header.
+    DetectFromContent,
 }
 impl JSONCompression {
+    /// Files with extensions that end with "lz4" are compressed, for other
+    /// files the compression is detected from their content.
     pub fn auto_detect_from_path(path: impl AsRef<Path>) -> Self {
         fn inner(path: &Path) -> JSONCompression {
             path.extension()
@@ -34,10 +40,10 @@ impl JSONCompression {
                     if is_compressed {
                         JSONCompression::Lz4Compression
                     } else {
-                        JSONCompression::NoCompression
+                        JSONCompression::DetectFromContent
                     }
                 })
-                .unwrap_or(JSONCompression::NoCompression)
+                .unwrap_or(JSONCompression::DetectFromContent)
         }
         inner(path.as_ref())
     }
@@ -113,6 +119,56 @@ impl From<compression::DecoderError> for ReadFirefoxJsonError {
     }
 }
 
+/// Decompress `mozLz4` data. Falls back to other decoders if the preferred
+/// compression library fails.
+pub fn decompress_data(data: &[u8]) -> Result<Vec<u8>, compression::DecoderError> {
+    let (decompressed, library) =
+        compression::decompress_with_fallback(data, crate::COMPRESSION_LIBRARY)?;
+    if library != crate::COMPRESSION_LIBRARY {
+        warn!(
+            "Failed to decompress data using the {:?} library but succeeded using the {:?} library as a fallback",
+            crate::COMPRESSION_LIBRARY,
+            library
+        );
+    }
+    Ok(decompressed)
+}
+
+/// Decompress `mozLz4` data and write it to `writer`. The decompressed data is
+/// streamed to the writer if the preferred compression library supports that,
+/// otherwise it is buffered in memory so that other decoders can be tried as a
+/// fallback, see [`decompress_data`].
+pub fn decompress_data_to_writer(
+    data: &[u8],
+    mut writer: impl Write,
+) -> Result<(), compression::DecoderError> {
+    if crate::COMPRESSION_LIBRARY.can_decompress_to_writer() {
+        compression::decompress_to_writer(data, crate::COMPRESSION_LIBRARY, writer)?;
+    } else {
+        writer
+            .write_all(&decompress_data(data)?)
+            .map_err(compression::DecoderError::WriteError)?;
+    }
+    Ok(())
+}
+
+/// A reader that first yields the bytes that were read by
+/// [`starts_with_magic_header`] and then the rest of the data.
+pub type PeekedReader<R> = io::Chain<io::Cursor<Vec<u8>>, R>;
+
+/// Check if a reader's data starts with the `mozLz4` magic header. Reading
+/// continues until the whole header has been read or the data ends since a
+/// single read from a pipe can return fewer bytes. The returned reader yields
+/// all the data, including the bytes that were checked.
+pub fn starts_with_magic_header<R: Read>(mut reader: R) -> io::Result<(bool, PeekedReader<R>)> {
+    let mut start = Vec::with_capacity(compression::MAGIC_HEADER_LENGTH);
+    (&mut reader)
+        .take(compression::MAGIC_HEADER_LENGTH as u64)
+        .read_to_end(&mut start)?;
+    let is_compressed = start == compression::MAGIC_HEADER;
+    Ok((is_compressed, io::Cursor::new(start).chain(reader)))
+}
+
 /// Decompress lz4 data.
 pub fn decompress_lz4_data(mut reader: Either<SliceReader, impl Read>) -> Result<SliceReader> {
     let (buf, index) = if let Left(slice_reader) = reader {
@@ -125,7 +181,7 @@ pub fn decompress_lz4_data(mut reader: Either<SliceReader, impl Read>) -> Result
         (buf, 0)
     };
     let buf_ref = &buf[index..];
-    let decompressed = crate::compression::decompress(buf_ref, crate::COMPRESSION_LIBRARY)?;
+    let decompressed = decompress_data(buf_ref)?;
 
     Ok(SliceReader::new(decompressed))
 }
@@ -142,29 +198,55 @@ pub fn read_json_file<'a>(
     compression: JSONCompression,
 ) -> Result<Either<SliceReader, impl Read>> {
     let path = path.as_ref();
-    let reader = {
-        let file = File::open(path).map_err(ReadFirefoxJsonError::OpenFile)?;
-        let mut buffer = BufReader::new(file);
-        if cache_file {
-            let mut data = Vec::new();
-            buffer
-                .read_to_end(&mut data)
-                .map_err(ReadFirefoxJsonError::ReadFile)?;
-            Left(SliceReader::new(data))
-        } else {
-            Right(buffer)
+    let file = File::open(path).map_err(ReadFirefoxJsonError::OpenFile)?;
+    let mut buffer = BufReader::new(file);
+    let reader = if cache_file {
+        let mut data = Vec::new();
+        buffer
+            .read_to_end(&mut data)
+            .map_err(ReadFirefoxJsonError::ReadFile)?;
+        Left(SliceReader::new(data))
+    } else {
+        Right(buffer)
+    };
+
+    let (is_compressed, reader) = match compression {
+        JSONCompression::Lz4Compression => (true, reader.map_right(unpeeked)),
+        JSONCompression::NoCompression => (false, reader.map_right(unpeeked)),
+        JSONCompression::DetectFromContent => {
+            let (is_compressed, reader) = match reader {
+                Left(slice_reader) => (
+                    slice_reader.data[slice_reader.index..].starts_with(compression::MAGIC_HEADER),
+                    Left(slice_reader),
+                ),
+                Right(buffer) => {
+                    let (is_compressed, reader) =
+                        starts_with_magic_header(buffer).map_err(ReadFirefoxJsonError::ReadFile)?;
+                    (is_compressed, Right(reader))
+                }
+            };
+            if is_compressed {
+                debug!(
+                    "Found compression header in file without \"lz4\" extension at: {:?}",
+                    path
+                );
+            }
+            (is_compressed, reader)
         }
     };
 
-    Ok(if JSONCompression::Lz4Compression == compression {
+    Ok(if is_compressed {
         Left(decompress_lz4_data(reader)?)
-    } else if let Left(slice_reader) = reader {
-        Left(slice_reader)
     } else {
-        Right(reader)
+        reader
     })
 }
 
+/// A [`PeekedReader`] for a reader that wasn't checked for a header.
+fn unpeeked<R: Read>(reader: R) -> PeekedReader<R> {
+    io::Cursor::new(Vec::new()).chain(reader)
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // CLI input helper
 ////////////////////////////////////////////////////////////////////////////////
@@ -190,21 +272,83 @@ where
     T: serde::de::DeserializeOwned,
     R: Read,
 {
-    #[cfg(feature = "serde_path_to_error")]
+    let mut reader = BufReader::new(reader);
+    // Peek at the start of the data since it can't be inspected after an error:
+    let is_html = looks_like_html(reader.fill_buf()?);
+    {
+        #[cfg(feature = "serde_path_to_error")]
+        {
+            serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_reader(reader))
+        }
+        #[cfg(not(feature = "serde_path_to_error"))]
+        {
+            serde_json::from_reader(reader)
+        }
+    }
+    .map_err(|e| {
+        if is_html {
+            html_input_error(e)
+        } else {
+            e.into()
+        }
+    })
+}
+
+/// Download the data at a `http` or `https` URL into memory.
+pub fn download(url: &str) -> Result<Vec<u8>> {
+    #[cfg(feature = "ureq")]
     {
-        Ok(serde_path_to_error::deserialize(
-            &mut serde_json::Deserializer::from_reader(reader),
-        )?)
+        let response = ureq::get(url)
+            .call()
+            .with_context(|| format!("Failed to request data from \"{url}\""))?;
+        let mut data = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut data)
+            .with_context(|| format!("Failed to download data from \"{url}\""))?;
+        Ok(data)
     }
-    #[cfg(not(feature = "serde_path_to_error"))]
+    #[cfg(not(feature = "ureq"))]
     {
-        Ok(serde_json::from_reader(reader)?)
+        eyre::bail!(
+            "Reading input from the URL \"{url}\" isn't supported since this program was built without the \"ureq\" feature"
+        )
+    }
+}
+
+/// Download at most `max_len` bytes from a `http` or `https` URL. Fails if the
+/// whole request takes longer than `timeout`.
+pub fn download_prefix(url: &str, max_len: u64, timeout: Duration) -> Result<Vec<u8>> {
+    #[cfg(feature = "ureq")]
+    {
+        let response = ureq::AgentBuilder::new()
+            .timeout(timeout)
+            .build()
+            .get(url)
+            .call()
+            .with_context(|| format!("Failed to request data from \"{url}\""))?;
+        let mut data = Vec::new();
+        response
+            .into_reader()
+            .take(max_len)
+            .read_to_end(&mut data)
+            .with_context(|| format!("Failed to download data from \"{url}\""))?;
+        Ok(data)
+    }
+    #[cfg(not(feature = "ureq"))]
+    {
+        let _ = (max_len, timeout);
+        eyre::bail!(
+            "Downloading data from the URL \"{url}\" isn't supported since this program was built without the \"ureq\" feature"
+        )
     }
 }
 
 pub enum InputReaderState {
     InputPath(PathBuf),
     Stdin(io::Stdin),
+    /// A `http` or `https` URL that the input should be downloaded from.
+    Remote(String),
 }
 /// Represents the input of a CLI command.
 pub struct InputReader {
@@ -235,7 +379,12 @@ impl InputReader {
             InputReaderState::Stdin(stdin) => {
                 let reader = BufReader::new(stdin.lock());
 
-                if self.is_compressed.unwrap_or(false) {
+                let (is_compressed, reader) = match self.is_compressed {
+                    Some(is_compressed) => (is_compressed, unpeeked(reader)),
+                    None => starts_with_magic_header(reader)
+                        .context("Failed to read data from stdin")?,
+                };
+                if is_compressed {
                     Either::Left(
                         decompress_lz4_data(Either::Right(reader))
                             .context("Failed to decompress data from stdin")?,
@@ -244,6 +393,22 @@ impl InputReader {
                     Either::Right(Either::Right(reader))
                 }
             }
+            InputReaderState::Remote(url) => {
+                info!("Downloading input from \"{url}\"");
+                let reader = SliceReader::new(download(url)?);
+
+                let is_compressed = self
+                    .is_compressed
+                    .unwrap_or_else(|| reader.data.starts_with(compression::MAGIC_HEADER));
+                if is_compressed {
+                    Either::Left(
+                        decompress_lz4_data(Either::<_, io::Empty>::Left(reader))
+                            .with_context(|| format!("Failed to decompress data from \"{url}\""))?,
+                    )
+                } else {
+                    Either::Left(reader)
+                }
+            }
         })
     }
 
@@ -262,6 +427,7 @@ impl InputReader {
                                 Cow::from(format!("file at: \"{}\"", path.display()))
                             }
                             InputReaderState::Stdin(_) => Cow::from("stdin"),
+                            InputReaderState::Remote(url) => Cow::from(format!("\"{url}\"")),
                         }
                     )
                 })?;
@@ -287,12 +453,17 @@ impl InputReader {
                     Some(false) => JSONCompression::NoCompression,
                     None => JSONCompression::auto_detect_from_path(path),
                 };
-                let uncompressed = if matches!(compression, JSONCompression::Lz4Compression) {
-                    let mut uncompressed =
-                        crate::compression::decompress(&original, crate::COMPRESSION_LIBRARY)
-                            .with_context(|| {
-                                format!("Failed to decompress data from file at: {:?}.", &path)
-                            })?;
+                let is_compressed = match compression {
+                    JSONCompression::Lz4Compression => true,
+                    JSONCompression::NoCompression => false,
+                    JSONCompression::DetectFromContent => {
+                        original.starts_with(compression::MAGIC_HEADER)
+                    }
+                };
+                let uncompressed = if is_compressed {
+                    let mut uncompressed = decompress_data(&original).with_context(|| {
+                        format!("Failed to decompress data from file at: {:?}.", &path)
+                    })?;
                     uncompressed.shrink_to_fit();
                     Arc::new(uncompressed)
                 } else {
@@ -301,20 +472,33 @@ impl InputReader {
 
                 Ok((original, uncompressed))
             }
-            InputReaderState::Stdin(stdin) => {
+            InputReaderState::Stdin(_) | InputReaderState::Remote(_) => {
                 let data = Arc::new({
-                    let mut reader = BufReader::new(stdin.lock());
-                    let mut data = Vec::new();
-                    reader
-                        .read_to_end(&mut data)
-                        .context("Failed to read data from stdin")?;
+                    let mut data = match &self.state {
+                        InputReaderState::Stdin(stdin) => {
+                            let mut reader = BufReader::new(stdin.lock());
+                            let mut data = Vec::new();
+                            reader
+                                .read_to_end(&mut data)
+                                .context("Failed to read data from stdin")?;
+                            data
+                        }
+                        InputReaderState::Remote(url) => {
+                            info!("Downloading input from \"{url}\"");
+                            download(url)?
+                        }
+                        InputReaderState::InputPath(_) => unreachable!(),
+                    };
                     data.shrink_to_fit();
                     data
                 });
-                let uncompressed = if matches!(self.is_compressed, Some(true)) {
-                    let mut uncompressed =
-                        crate::compression::decompress(&data, crate::COMPRESSION_LIBRARY)
-                            .context("Failed to decompress data from stdin")?;
+                let is_compressed = self
+                    .is_compressed
+                    .unwrap_or_else(|| data.starts_with(compression::MAGIC_HEADER));
+                let uncompressed = if is_compressed {
+                    let mut uncompressed = decompress_data(&data).with_context(|| {
+                        format!("Failed to decompress data from {}", self.reader_info())
+                    })?;
                     uncompressed.shrink_to_fit();
                     Arc::new(uncompressed)
                 } else {
@@ -363,17 +547,42 @@ impl InputReader {
     }
 
     pub fn reader_info(&self) -> impl fmt::Display + '_ {
-        match self.path() {
-            Some(v) => Left(format!(r#""{}""#, v.display())),
-            None => Right("stdin"),
+        match &self.state {
+            InputReaderState::InputPath(v) => Left(format!(r#""{}""#, v.display())),
+            InputReaderState::Remote(url) => Left(format!(r#""{}""#, url)),
+            InputReaderState::Stdin(_) => Right("stdin"),
         }
     }
 }
 
+/// Check if some data starts with an HTML tag, for example `<!DOCTYPE html>`,
+/// ignoring any leading whitespace and byte order mark.
+pub fn looks_like_html(data: &[u8]) -> bool {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    data.iter()
+        .find(|byte| !byte.is_ascii_whitespace())
+        .is_some_and(|&byte| byte == b'<')
+}
+
+/// The error for input that [`looks_like_html`] and couldn't be parsed.
+fn html_input_error<E>(error: E) -> eyre::Report
+where
+    E: StdError + Send + Sync + 'static,
+{
+    // Likely the output of a previous tabs-to-links command or a web page
+    // that was saved instead of the file it linked to:
+    eyre::Report::new(error)
+        .wrap_err("The input looks like HTML, not a sessionstore JSON file.")
+        .suggestion("make sure the input is a sessionstore file like \"recovery.jsonlz4\"")
+}
 pub fn json_parse_error_context<E>(error: E, data: &[u8]) -> eyre::Report
 where
     E: StdError + Send + Sync + 'static,
 {
+    if looks_like_html(data) {
+        return html_input_error(error);
+    }
+
     let json_error: &serde_json::Error = {
         let mut e: &(dyn StdError + 'static) = &error;
         loop {
@@ -511,3 +720,177 @@ impl Clone for OutputWriter {
         }
     }
 }
+
+/// Byte Order Mark for UTF-16 in little endian byte order.
+pub const UTF_16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+
+/// Transcodes UTF-8 text written to it into UTF-16 text in little endian byte
+/// order. A Byte Order Mark is written before any other text.
+pub struct Utf16LeWriter<W: Write> {
+    inner: W,
+    /// The start of a UTF-8 encoded character that was split between
+    /// different writes.
+    incomplete: Vec<u8>,
+}
+impl<W: Write> Utf16LeWriter<W> {
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(UTF_16_LE_BOM)?;
+        Ok(Self {
+            inner,
+            incomplete: Vec::new(),
+        })
+    }
+
+    /// Flush the inner writer and return it. Fails if the written text ended
+    /// in the middle of a character.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.incomplete.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "written text ended with an incomplete UTF-8 character",
+            ));
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+impl<W: Write> Write for Utf16LeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = std::mem::take(&mut self.incomplete);
+        data.extend_from_slice(buf);
+        let valid_len = match std::str::from_utf8(&data) {
+            Ok(_) => data.len(),
+            // Wait for the rest of a character that was split between writes:
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let text = std::str::from_utf8(&data[..valid_len]).expect("text was validated");
+        let encoded = text
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        self.inner.write_all(&encoded)?;
+        self.incomplete = data.split_off(valid_len);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf16_writer_handles_split_characters() {
+        let mut writer = Utf16LeWriter::new(Vec::new()).unwrap();
+        let text = "Tab: 日本 😀";
+        for chunk in text.as_bytes().chunks(3) {
+            writer.write_all(chunk).unwrap();
+        }
+        let output = writer.finish().unwrap();
+        let mut expected = UTF_16_LE_BOM.to_vec();
+        expected.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(output, expected);
+
+        let mut writer = Utf16LeWriter::new(Vec::new()).unwrap();
+        writer.write_all(&"日".as_bytes()[..2]).unwrap();
+        assert!(writer.finish().is_err());
+    }
+
+    /// `{"a":1}` compressed as a single lz4 literal sequence.
+    fn compressed_json() -> Vec<u8> {
+        let json = br#"{"a":1}"#;
+        let mut data = compression::MAGIC_HEADER.to_vec();
+        data.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        data.push((json.len() as u8) << 4);
+        data.extend_from_slice(json);
+        data
+    }
+
+    fn read_to_string(path: &Path, cache_file: bool) -> String {
+        let mut text = String::new();
+        read_json_file(
+            path,
+            cache_file,
+            JSONCompression::auto_detect_from_path(path),
+        )
+        .unwrap()
+        .read_to_string(&mut text)
+        .unwrap();
+        text
+    }
+
+    #[test]
+    fn magic_header_is_found_across_short_reads() {
+        /// Returns one byte per read like a slow pipe might.
+        struct OneByteReader<'a>(&'a [u8]);
+        impl Read for OneByteReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let len = self.0.len().min(buf.len()).min(1);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let data = compressed_json();
+        let (is_compressed, mut reader) =
+            starts_with_magic_header(BufReader::new(OneByteReader(&data))).unwrap();
+        assert!(is_compressed);
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+
+        let (is_compressed, mut reader) = starts_with_magic_header(OneByteReader(b"{}")).unwrap();
+        assert!(!is_compressed);
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, b"{}");
+    }
+
+    #[test]
+    fn detect_compression_from_content() {
+        let path = std::env::temp_dir().join(format!(
+            "firefox_session_data_test_{}_compressed.js",
+            std::process::id()
+        ));
+        std::fs::write(&path, compressed_json()).unwrap();
+        let result = (read_to_string(&path, true), read_to_string(&path, false));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.0, r#"{"a":1}"#);
+        assert_eq!(result.1, r#"{"a":1}"#);
+    }
+
+    #[test]
+    fn html_input_has_clear_error() {
+        assert!(looks_like_html(b"<!DOCTYPE html><html></html>"));
+        assert!(looks_like_html(b"\xEF\xBB\xBF\r\n  <html>"));
+        assert!(!looks_like_html(br#"{"a":"<b>"}"#));
+        assert!(!looks_like_html(b""));
+
+        let error = deserialize_from_slice::<serde_json::Value>(b"<!DOCTYPE html>").unwrap_err();
+        assert!(error.to_string().contains("looks like HTML"));
+        let error =
+            deserialize_from_reader::<serde_json::Value, _>(&b"\n<!DOCTYPE html>"[..]).unwrap_err();
+        assert!(error.to_string().contains("looks like HTML"));
+        let error = deserialize_from_reader::<serde_json::Value, _>(&b"{"[..]).unwrap_err();
+        assert!(!error.to_string().contains("looks like HTML"));
+    }
+
+    #[test]
+    fn uncompressed_file_is_not_decompressed() {
+        let path = std::env::temp_dir().join(format!(
+            "firefox_session_data_test_{}_uncompressed.js",
+            std::process::id()
+        ));
+        std::fs::write(&path, br#"{"a":1}"#).unwrap();
+        let result = read_to_string(&path, true);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result, r#"{"a":1}"#);
+    }
+}
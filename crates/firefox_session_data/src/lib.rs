@@ -6,6 +6,7 @@ extern crate log;
 pub mod find;
 pub mod io_utils;
 pub mod pdf_converter;
+pub mod session;
 pub mod shared_opts;
 pub mod to_links;
 #[cfg(feature = "typst_pdf")]
@@ -20,12 +21,16 @@ pub type Error = eyre::Report;
 
 use std::{
     cmp::Reverse,
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     ffi::OsString,
     fs::OpenOptions,
     io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
     process::{Command, Stdio},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     thread,
     time::Instant,
 };
@@ -35,9 +40,18 @@ use color_eyre::Help;
 use either::*;
 use eyre::WrapErr;
 use html_to_pdf::{HtmlSink, HtmlToPdfConverter};
-use json_statistics::{collect_statistics, type_script::TypeScriptStatisticsFormatter};
+use json_statistics::{
+    collect_statistics_from_reader, collect_statistics_with_options,
+    print::{ExtraFMTInfo, FlatStatisticsFormatter, StandardStatisticsFormatter},
+    type_script::TypeScriptStatisticsFormatter,
+    CollectOptions, JSONValueStatistics,
+};
+use serde::de::IgnoredAny;
 
-use shared_opts::{CommonOpt, InOutOpt, OverwriteInputOpt, SessionstoreOpt};
+use shared_opts::{
+    CommonOpt, FreeSpaceOpt, HostFilterOpt, InOutOpt, JsonFormatOpt, OutputCompressionOpt,
+    OverwriteInputOpt, PreserveMtimeOpt, SessionstoreOpt,
+};
 
 /// The compression library that should be used.
 const COMPRESSION_LIBRARY: compression::SupportedCompressionLibrary = {
@@ -99,6 +113,7 @@ use crate::io_utils::{deserialize_from_slice, json_parse_error_context};
 #[derive(Debug, Parser, Clone)]
 #[clap(rename_all = "kebab-case")]
 #[clap(version, author, about)]
+#[allow(clippy::large_enum_variant)] // Only created once when parsing arguments.
 pub enum Opt {
     /// Prints information about the contents of a JSON file. The file can be
     /// compressed using firefox `mozLz4` format.
@@ -123,6 +138,59 @@ pub enum Opt {
         /// Max keys inside an object before no specific keys are shown.
         max_object_keys: u32,
 
+        #[clap(long, conflicts_with = "type_script")]
+        /// Count how many distinct values each string property has. This shows
+        /// how much redundancy there is in the data but uses more memory
+        /// since a hash of every distinct string is kept around.
+        distinct_strings: bool,
+
+        #[clap(long, conflicts_with = "type_script")]
+        /// Show a histogram of the sizes of strings and the lengths of arrays.
+        histogram: bool,
+
+        #[clap(long, conflicts_with = "histogram")]
+        /// Only keep the count and total size of values instead of the size of
+        /// every value. Uses much less memory for large files but can't be
+        /// combined with a histogram.
+        counts_only: bool,
+
+        #[clap(long, conflicts_with = "type_script")]
+        /// Show the largest string and number for each property. Useful to
+        /// find out what is bloating the data, like a huge favicon.
+        show_largest: bool,
+
+        #[clap(long, conflicts_with_all = &["type_script", "histogram", "show_largest"])]
+        /// List the total size of every path in the JSON data, largest first,
+        /// instead of showing the nested structure.
+        flat: bool,
+
+        #[clap(long, value_name = "CODE", conflicts_with = "type_script")]
+        /// Locale that decides how digits in large numbers are grouped, for
+        /// example "de" or "fr". Defaults to "en".
+        number_locale: Option<String>,
+
+        #[clap(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = &["type_script", "histogram", "show_largest", "flat", "distinct_strings"]
+        )]
+        /// Compare the input with the JSON data in this file and list how the
+        /// count and total size of every path changed, largest change first.
+        /// Useful to see how a session grew over time or what a command
+        /// changed. If the file extension ends with "lz4" then it is assumed
+        /// to be compressed.
+        compare_with: Option<PathBuf>,
+
+        #[clap(long, requires = "compare_with")]
+        /// Output the comparison as JSON.
+        json: bool,
+
+        #[clap(long)]
+        /// Ignore the data that extensions stored for windows and tabs, for
+        /// example tab trees from Tree Style Tab or Sidebery. Such data can be
+        /// large and clutter the results.
+        exclude_extension_data: bool,
+
         #[clap(flatten)]
         session: SessionstoreOpt,
     },
@@ -131,19 +199,60 @@ pub enum Opt {
     /// store files usually have the `.js` file extensions.
     #[clap(version, author)]
     #[clap(visible_alias = "c")]
-    Compress(InOutOpt),
+    Compress {
+        #[clap(flatten)]
+        output_compression: OutputCompressionOpt,
+
+        #[clap(flatten)]
+        free_space: FreeSpaceOpt,
+
+        #[clap(long, conflicts_with = "open", help_heading = "OUTPUT")]
+        /// Compress the data in memory and log the resulting size without
+        /// writing any output.
+        dry_run: bool,
+
+        #[clap(flatten)]
+        preserve_mtime: PreserveMtimeOpt,
+
+        #[clap(flatten)]
+        in_out: InOutOpt,
+    },
 
     /// Decompress a file that is using Firefox's `mozLz4` format. Compressed
     /// session store files usually have the `.jsonlz4` file extensions.
+    ///
+    /// To decompress the sessionstore of a Firefox profile use:
+    ///
+    /// firefox-session-data decompress --firefox-profile=default-release --output "sessionstore.js"
     #[clap(version, author)]
     #[clap(visible_alias = "d")]
-    Decompress(InOutOpt),
+    Decompress {
+        #[clap(long, conflicts_with = "open", help_heading = "OUTPUT")]
+        /// Decompress the data in memory and log the resulting size without
+        /// writing any output.
+        dry_run: bool,
+
+        #[clap(flatten)]
+        free_space: FreeSpaceOpt,
+
+        #[clap(flatten)]
+        preserve_mtime: PreserveMtimeOpt,
+
+        #[clap(flatten)]
+        in_out: InOutOpt,
+    },
 
     /// Copy a sessionstore file to an output location.
     ///
     /// Allows making use of this program's ability to find sessionstore files.
     #[clap(version, author)]
-    Copy(SessionstoreOpt),
+    Copy {
+        #[clap(flatten)]
+        preserve_mtime: PreserveMtimeOpt,
+
+        #[clap(flatten)]
+        session: SessionstoreOpt,
+    },
 
     /// Remove tabs that are marked via a special Firefox extension from a
     /// sessionstore file.
@@ -156,6 +265,15 @@ pub enum Opt {
         #[clap(flatten)]
         overwrite_input: OverwriteInputOpt,
 
+        #[clap(flatten)]
+        output_compression: OutputCompressionOpt,
+
+        #[clap(flatten)]
+        free_space: FreeSpaceOpt,
+
+        #[clap(flatten)]
+        json_format: JsonFormatOpt,
+
         #[clap(flatten)]
         session: SessionstoreOpt,
     },
@@ -182,6 +300,65 @@ pub enum Opt {
         #[clap(flatten)]
         overwrite_input: OverwriteInputOpt,
 
+        #[clap(flatten)]
+        output_compression: OutputCompressionOpt,
+
+        #[clap(flatten)]
+        free_space: FreeSpaceOpt,
+
+        #[clap(flatten)]
+        json_format: JsonFormatOpt,
+
+        #[clap(flatten)]
+        session: SessionstoreOpt,
+    },
+
+    /// Remove all cookies from a sessionstore file.
+    ///
+    /// Sessionstore files can contain login cookies, so removing them makes it
+    /// safer to share a session with others.
+    #[clap(version, author)]
+    #[clap(visible_alias = "sc")]
+    StripCookies {
+        #[clap(flatten)]
+        overwrite_input: OverwriteInputOpt,
+
+        #[clap(flatten)]
+        output_compression: OutputCompressionOpt,
+
+        #[clap(flatten)]
+        free_space: FreeSpaceOpt,
+
+        #[clap(flatten)]
+        json_format: JsonFormatOpt,
+
+        #[clap(flatten)]
+        session: SessionstoreOpt,
+    },
+
+    /// Remove private data from a sessionstore file so that it can be shared
+    /// with others.
+    ///
+    /// To keep only the currently open URLs use:
+    ///
+    /// firefox-session-data sanitize --firefox-profile=default-release --all
+    #[clap(version, author)]
+    Sanitize {
+        #[clap(flatten)]
+        sanitize_options: SanitizeOptions,
+
+        #[clap(flatten)]
+        overwrite_input: OverwriteInputOpt,
+
+        #[clap(flatten)]
+        output_compression: OutputCompressionOpt,
+
+        #[clap(flatten)]
+        free_space: FreeSpaceOpt,
+
+        #[clap(flatten)]
+        json_format: JsonFormatOpt,
+
         #[clap(flatten)]
         session: SessionstoreOpt,
     },
@@ -234,6 +411,10 @@ pub enum Opt {
         /// non-zero exit code then the new content won't be written anywhere.
         /// If the error code isn't listed using `--stop-exit-code` then this
         /// program will also exit with a non-zero exit code.
+        ///
+        /// The command's output is compressed as is, so it isn't reformatted
+        /// unless `--json-pretty` or `--json-compact` is specified. The whole
+        /// output is read into memory before it is compressed.
         command: Vec<OsString>,
 
         #[clap(
@@ -248,11 +429,24 @@ pub enum Opt {
         #[clap(flatten)]
         overwrite_input: OverwriteInputOpt,
 
+        #[clap(flatten)]
+        output_compression: OutputCompressionOpt,
+
+        #[clap(flatten)]
+        free_space: FreeSpaceOpt,
+
+        #[clap(flatten)]
+        json_format: JsonFormatOpt,
+
         #[clap(flatten)]
         session: SessionstoreOpt,
     },
 
     /// Get a list of tab groups from a sessionstore.
+    ///
+    /// To list the windows of a Firefox profile as JSON use:
+    ///
+    /// firefox-session-data get-groups --firefox-profile=default-release --json --include-window-geometry
     #[clap(version, author)]
     #[clap(visible_alias = "gg")]
     GetGroups {
@@ -265,16 +459,166 @@ pub enum Opt {
         /// Output the information as JSON.
         #[clap(long)]
         json: bool,
+
+        /// Include the size, position and size mode (for example "maximized")
+        /// of each window.
+        #[clap(long)]
+        include_window_geometry: bool,
+    },
+
+    /// Compare two sessionstore files and list the tabs and windows that were
+    /// added, removed or moved.
+    ///
+    /// The input file is treated as the new session and is compared to the
+    /// original session specified with `--original`.
+    ///
+    /// For example, to see what changed since a backup was made use:
+    ///
+    /// firefox-session-data diff --firefox-profile=default-release --original "backup.jsonlz4"
+    #[clap(version, author)]
+    Diff {
+        #[clap(long, value_name = "FILE", help_heading = "INPUT")]
+        /// The original sessionstore file to compare the input with. If the file
+        /// extension ends with "lz4" then it is assumed to be compressed.
+        original: PathBuf,
+
+        #[clap(flatten)]
+        session: SessionstoreOpt,
+
+        /// Output the information as JSON.
+        #[clap(long)]
+        json: bool,
     },
 
     /// Get URLs for tabs in a sessionstore file.
+    ///
+    /// To write the open tabs of a Firefox profile to a Markdown file use:
+    ///
+    /// firefox-session-data tabs-to-links --firefox-profile=default-release --format=markdown --output "tabs.md"
     #[clap(version, author)]
     #[clap(visible_alias = "ttl")]
     TabsToLinks(to_links::TabsToLinksOpt),
 
+    /// Build a sessionstore file from the JSON written by the "json" format
+    /// of the tabs-to-links command. The windows and tabs are ordered by their
+    /// indices so an export can be edited and then loaded by Firefox again.
+    ///
+    /// Only URLs, titles, selected and pinned tabs and containers are
+    /// restored, tab history and extension data are lost.
+    ///
+    /// For example:
+    ///
+    /// firefox-session-data import-links --input "tabs.json" --output "sessionstore.jsonlz4"
+    #[clap(version, author)]
+    ImportLinks {
+        #[clap(flatten)]
+        output_compression: OutputCompressionOpt,
+
+        #[clap(flatten)]
+        free_space: FreeSpaceOpt,
+
+        #[clap(flatten)]
+        in_out: InOutOpt,
+    },
+
     /// Analyze the domains of a session's open tabs.
+    ///
+    /// Domains are sorted by the number of tabs that use them. Domains with the
+    /// same number of tabs are sorted by name.
+    #[clap(version, author)]
+    Domains {
+        #[clap(flatten)]
+        session: SessionstoreOpt,
+
+        /// Count registrable domains, for example "example.co.uk" for
+        /// "www.example.co.uk", instead of full host names. Requires a copy of
+        /// the public suffix list, see "--public-suffix-list".
+        #[clap(long, requires = "public_suffix_list")]
+        registrable_domain: bool,
+
+        /// Path to a copy of the public suffix list, which can be downloaded
+        /// from https://publicsuffix.org/list/public_suffix_list.dat
+        #[clap(long, value_name = "FILE")]
+        public_suffix_list: Option<PathBuf>,
+
+        #[clap(flatten)]
+        host_filter: HostFilterOpt,
+    },
+
+    /// Count the windows and tabs in a sessionstore file. Useful as a quick
+    /// check of a file before running other commands on it.
+    #[clap(version, author)]
+    Stats {
+        #[clap(flatten)]
+        session: SessionstoreOpt,
+
+        /// Output the information as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// List the extensions that have stored data in a session's windows and
+    /// tabs.
+    ///
+    /// Extensions are identified by the prefix of the keys they use, for
+    /// example "extension:treestyletab@piro.sakura.ne.jp" for Tree Style Tab.
+    /// The number of windows and tabs that have data from each extension is
+    /// also shown. Useful to check what data can be removed with the
+    /// `remove-tree-data` command.
+    #[clap(version, author)]
+    Extensions {
+        #[clap(flatten)]
+        session: SessionstoreOpt,
+
+        /// Output the information as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Print the complete JSON data of a single tab in a sessionstore file.
+    ///
+    /// Useful when figuring out what data an extension stores for its tabs.
+    /// For example, to show the first tab of the second window use:
+    ///
+    /// firefox-session-data show-tab --firefox-profile=default-release --window 1 --tab 0
+    #[clap(version, author)]
+    ShowTab {
+        #[clap(flatten)]
+        session: SessionstoreOpt,
+
+        /// The zero-based index of the window that has the tab.
+        #[clap(long, value_name = "INDEX")]
+        window: usize,
+
+        /// The zero-based index of the tab in its window.
+        #[clap(long, value_name = "INDEX")]
+        tab: usize,
+
+        /// Look for the window among recently closed windows instead of open
+        /// windows.
+        #[clap(long)]
+        closed_window: bool,
+
+        /// Write the tab's JSON on a single line instead of pretty printing it.
+        #[clap(long)]
+        raw: bool,
+    },
+
+    /// Count the windows and tabs in the sessionstore file of every Firefox
+    /// profile. Useful to find the profile you care about when you have many
+    /// profiles.
+    ///
+    /// Profiles without a sessionstore file or with a file that couldn't be
+    /// read are listed as "n/a".
     #[clap(version, author)]
-    Domains(SessionstoreOpt),
+    CompareProfiles {
+        #[clap(flatten)]
+        common: CommonOpt,
+
+        /// Output the information as JSON.
+        #[clap(long)]
+        json: bool,
+    },
 
     /// Print info about the different output formats that are supported by the
     /// `tabs-to-links` command.
@@ -284,22 +628,55 @@ pub enum Opt {
         /// Output the information as JSON.
         #[clap(long)]
         json: bool,
+
+        /// Instead of listing formats, describe every option of the
+        /// `tabs-to-links` command as JSON. Each option's name, type, default
+        /// value and help text is included so that other programs, for
+        /// example a GUI, can present the options automatically.
+        #[clap(long, conflicts_with = "json")]
+        describe_options: bool,
+    },
+
+    /// Print a script that provides tab completion of this program's commands
+    /// and options for a shell.
+    ///
+    /// For example, to enable completions for bash you can use:
+    ///
+    /// firefox-session-data completions bash > ~/.local/share/bash-completion/completions/firefox-session-data
+    ///
+    /// And for PowerShell you can add the script to your profile:
+    ///
+    /// firefox-session-data completions powershell >> $PROFILE
+    #[clap(version, author)]
+    Completions {
+        /// The shell to generate completions for.
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
     },
 }
 impl Opt {
     pub fn common(&self) -> &CommonOpt {
         match self {
             Opt::AnalyzeJson { session, .. } => &session.in_out_info.common,
-            Opt::Copy(opt) => &opt.in_out_info.common,
-            Opt::Compress(opt) => &opt.common,
-            Opt::Decompress(opt) => &opt.common,
+            Opt::Copy { session, .. } => &session.in_out_info.common,
+            Opt::Compress { in_out, .. } => &in_out.common,
+            Opt::Decompress { in_out, .. } => &in_out.common,
             Opt::RemoveMarkedTabs { session, .. } => &session.in_out_info.common,
             Opt::RemoveTreeData { session, .. } => &session.in_out_info.common,
+            Opt::StripCookies { session, .. } => &session.in_out_info.common,
+            Opt::Sanitize { session, .. } => &session.in_out_info.common,
             Opt::Modify { session, .. } => &session.in_out_info.common,
             Opt::GetGroups { session, .. } => &session.in_out_info.common,
+            Opt::Diff { session, .. } => &session.in_out_info.common,
             Opt::TabsToLinks(opt) => &opt.session_store_opt.in_out_info.common,
-            Opt::Domains(opt) => &opt.in_out_info.common,
+            Opt::ImportLinks { in_out, .. } => &in_out.common,
+            Opt::Domains { session, .. } => &session.in_out_info.common,
+            Opt::Stats { session, .. } => &session.in_out_info.common,
+            Opt::Extensions { session, .. } => &session.in_out_info.common,
+            Opt::ShowTab { session, .. } => &session.in_out_info.common,
+            Opt::CompareProfiles { common, .. } => common,
             Opt::TabsToLinksFormats { .. } => panic!("this command doesn't have any arguments"),
+            Opt::Completions { .. } => panic!("this command doesn't have any arguments"),
         }
     }
 }
@@ -454,73 +831,454 @@ pub fn remove_tree_data(
     Ok(())
 }
 
-#[derive(Debug, Args, Clone, Default)]
-#[clap(rename_all = "kebab-case")]
-pub struct RemoveMarkedTabsOptions {
-    #[clap(
-        long,
-        action = clap::ArgAction::Append,
-        use_value_delimiter = true,
-        help_heading = "Remove Marked Tabs"
-    )]
-    /// Remove tabs that are marked with a specific color in the extension
-    /// Sidebery. For example: "red".
-    ///
-    /// Multiple values can be specified by separating them with commas (,)
-    /// in which case a tab will be removed if it is marked with any of the
-    /// colors.
-    sidebery_colors: Vec<String>,
+/// Modify Firefox session data so that all cookies are removed.
+///
+/// The `session_data` argument should be the complete JSON structure that is
+/// deserialized from the sessionstore file.
+///
+/// Returns the number of cookies that were removed.
+pub fn strip_cookies(session_data: &mut serde_json::Value) -> Result<usize> {
+    let mut total_remove_count = 0;
+
+    // Newer Firefox versions store cookies for the whole session at the top level:
+    if let Some(cookies) = session_data
+        .get_mut("cookies")
+        .and_then(serde_json::Value::as_array_mut)
+    {
+        total_remove_count += cookies.len();
+        cookies.clear();
+    }
+
+    let session = session_store::serde_unstructured::view(session_data)
+        .cast::<session_store::FirefoxSessionStore>();
+
+    let (windows, closed_windows) = session.project(|p| (p.windows(), p._closed_windows()));
+    let windows = windows?.try_array_iter()?;
+    // Older sessions might not have any closed windows:
+    let closed_windows = closed_windows
+        .ok()
+        .map(|closed_windows| closed_windows.try_array_iter())
+        .transpose()?
+        .into_iter()
+        .flatten();
+
+    for window in windows.chain(closed_windows) {
+        let Ok(cookies) = window.project(|p| p.cookies()) else {
+            // No cookies:
+            continue;
+        };
+        let Some(cookies_array) = cookies.data.as_array_mut() else {
+            warn!(
+                "A window's cookies were not an array (window was skipped): {}",
+                cookies.tracker
+            );
+            continue;
+        };
+        total_remove_count += cookies_array.len();
+        cookies_array.clear();
+    }
+
+    info!(
+        "Removed {} cookies from the sessionstore file",
+        total_remove_count
+    );
+
+    Ok(total_remove_count)
 }
 
-/// Modify Firefox session data so that marked tabs are removed.
+/// The number of windows and tabs that have stored data using a specific
+/// extension key prefix.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ExtensionDataCount {
+    pub windows: u64,
+    pub tabs: u64,
+}
+
+/// Get the part of an `ext_data` key that identifies the extension that
+/// stored it.
 ///
-/// The `session_data` argument should be the complete JSON structure that
-/// is deserialized from the sessionstore file.
-pub fn remove_marked_tabs(
+/// WebExtensions use keys like `extension:<id>:<name>` so for those the
+/// prefix is `extension:<id>`. Legacy addons used keys like
+/// `treestyletab_parent` or `tabview-group` so for those the prefix is the
+/// text before the first `_` or `-`.
+pub fn extension_key_prefix(key: &str) -> &str {
+    if let Some(rest) = key.strip_prefix("extension:") {
+        match rest.find(':') {
+            Some(end) => &key[.."extension:".len() + end],
+            None => key,
+        }
+    } else {
+        key.split(['_', '-']).next().unwrap_or(key)
+    }
+}
+
+/// A human readable name for an extension key prefix that was returned from
+/// [`extension_key_prefix`], if it is a prefix that this program knows about.
+pub fn known_extension_name(prefix: &str) -> Option<&'static str> {
+    Some(match prefix {
+        "extension:treestyletab@piro.sakura.ne.jp" => "Tree Style Tab",
+        "treestyletab" => "Tree Style Tab (legacy)",
+        "extension:{3c078156-979c-498b-8990-85f7987dd929}" => "Sidebery",
+        "extension:{c28e42b2-28b5-45f0-bdc8-6989ae7e6a7e}" => "Tab Count in Window Title",
+        "extension:{5df6e133-f35d-4c62-885a-56387df22f6b}" => "Other Window",
+        "tabview" => "Tab Groups (Panorama)",
+        _ => return None,
+    })
+}
+
+/// Find the extensions that have stored data in a session's windows and tabs.
+///
+/// The `session_data` argument should be the complete JSON structure that is
+/// deserialized from the sessionstore file. It is only mutable because of the
+/// API used to traverse it and won't be modified.
+///
+/// Returns the extension key prefixes (see [`extension_key_prefix`]) that
+/// were found together with the number of windows and tabs that used them.
+/// Both open and closed windows are included.
+pub fn extension_data_keys(
     session_data: &mut serde_json::Value,
-    options: &RemoveMarkedTabsOptions,
-) -> Result<()> {
-    let mut total_remove_count = 0;
+) -> Result<BTreeMap<String, ExtensionDataCount>> {
+    let mut found = BTreeMap::<String, ExtensionDataCount>::new();
+    let mut add_keys = |ext_data: &serde_json::Map<String, serde_json::Value>,
+                        count: fn(&mut ExtensionDataCount) -> &mut u64| {
+        let prefixes = ext_data
+            .keys()
+            .map(|key| extension_key_prefix(key))
+            .collect::<BTreeSet<_>>();
+        for prefix in prefixes {
+            *count(found.entry(prefix.to_owned()).or_default()) += 1;
+        }
+    };
+
     let session = session_store::serde_unstructured::view(session_data)
         .cast::<session_store::FirefoxSessionStore>();
 
-    let windows = session.project(|p| p.windows())?;
-    for window in windows.try_array_iter()? {
-        let window_result = (|| -> Result<_> {
-            let mut window_remove_count = 0;
+    let (windows, closed_windows) = session.project(|p| (p.windows(), p._closed_windows()));
+    let windows = windows?.try_array_iter()?;
+    // Older sessions might not have any closed windows:
+    let closed_windows = closed_windows
+        .ok()
+        .map(|closed_windows| closed_windows.try_array_iter())
+        .transpose()?
+        .into_iter()
+        .flatten();
+
+    for mut window in windows.chain(closed_windows) {
+        if let Ok(ext_data) = window.as_mut().project(|p| p.ext_data()) {
+            match ext_data.data.as_object() {
+                Some(ext_data) => add_keys(ext_data, |count| &mut count.windows),
+                None => warn!(
+                    "A window's ext_data was not an object (window was skipped): {}",
+                    ext_data.tracker
+                ),
+            }
+        }
 
-            let (tabs, selected) = window.project(|p| (p.tabs(), p.selected()));
-            let tabs = tabs?;
-            let mut selected_tab = try_!({
-                let selected = selected?;
-                let value = selected.as_ref().deserialize()?;
-                (selected, value)
-            })
-            .map_err(|e| {
-                error!(
-                    "could not get selected tab info for a window, \
-                    so can't update it if any tabs are removed: {e}"
+        let tabs = match window.as_mut().project(|p| p.tabs()) {
+            Ok(tabs) => tabs,
+            Err(e) => {
+                warn!(
+                    "failed to find tabs in a window: {e} (affected json data: {})",
+                    window.tracker
                 );
-            })
-            .ok();
-
-            // Remove unwanted tabs from the array:
-            let mut idx = 0;
-            tabs.try_retain(|tab| {
+                continue;
+            }
+        };
+        for tab in tabs.try_array_iter()? {
+            let Ok(ext_data) = tab.project(|p| p.ext_data()) else {
+                // No ext data:
+                continue;
+            };
+            match ext_data.data.as_object() {
+                Some(ext_data) => add_keys(ext_data, |count| &mut count.tabs),
+                None => warn!(
+                    "A tab's ext_data was not an object (tab was skipped): {}",
+                    ext_data.tracker
+                ),
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// Modify Firefox session data so that all extension data is removed from
+/// every window and tab. Unlike [`remove_tree_data`] this removes the data of
+/// all extensions, not just the ones that store tab trees.
+///
+/// The `session_data` argument should be the complete JSON structure that is
+/// deserialized from the sessionstore file.
+///
+/// Returns the number of windows and tabs that had extension data.
+pub fn strip_extension_data(session_data: &mut serde_json::Value) -> Result<usize> {
+    let mut total_remove_count = 0;
+    let mut clear = |ext_data: &mut serde_json::Value| {
+        if ext_data.as_object().is_some_and(|data| !data.is_empty()) {
+            total_remove_count += 1;
+        }
+        *ext_data = serde_json::Value::Object(Default::default());
+    };
+
+    let session = session_store::serde_unstructured::view(session_data)
+        .cast::<session_store::FirefoxSessionStore>();
+
+    let (windows, closed_windows) = session.project(|p| (p.windows(), p._closed_windows()));
+    let windows = windows?.try_array_iter()?;
+    // Older sessions might not have any closed windows:
+    let closed_windows = closed_windows
+        .ok()
+        .map(|closed_windows| closed_windows.try_array_iter())
+        .transpose()?
+        .into_iter()
+        .flatten();
+
+    for mut window in windows.chain(closed_windows) {
+        if let Ok(ext_data) = window.as_mut().project(|p| p.ext_data()) {
+            clear(ext_data.data);
+        }
+
+        let Ok(tabs) = window.as_mut().project(|p| p.tabs()) else {
+            warn!(
+                "failed to find tabs in a window (window was skipped): {}",
+                window.tracker
+            );
+            continue;
+        };
+        for tab in tabs.try_array_iter()? {
+            if let Ok(ext_data) = tab.project(|p| p.ext_data()) {
+                clear(ext_data.data);
+            }
+        }
+    }
+
+    info!(
+        "Removed extension data from {} windows and tabs in the sessionstore file",
+        total_remove_count
+    );
+
+    Ok(total_remove_count)
+}
+
+#[derive(Debug, Args, Clone, Default)]
+#[clap(rename_all = "kebab-case")]
+#[group(required = true, multiple = true)]
+pub struct SanitizeOptions {
+    #[clap(long, help_heading = "Sanitize")]
+    /// Remove all cookies.
+    pub cookies: bool,
+    #[clap(long, help_heading = "Sanitize")]
+    /// Only keep the current history entry for each tab.
+    pub history: bool,
+    #[clap(long, help_heading = "Sanitize")]
+    /// Remove text that was entered into forms.
+    pub form_data: bool,
+    #[clap(long, help_heading = "Sanitize")]
+    /// Remove text that was typed into the address bar.
+    pub typed_values: bool,
+    #[clap(long, help_heading = "Sanitize")]
+    /// Remove scroll positions.
+    pub scroll: bool,
+    #[clap(long, help_heading = "Sanitize")]
+    /// Remove recently closed windows and tabs.
+    pub closed: bool,
+    #[clap(long, help_heading = "Sanitize")]
+    /// Remove all of the above.
+    pub all: bool,
+}
+
+/// Modify Firefox session data so that private data is removed. Unknown
+/// fields are left as they are.
+///
+/// The `session_data` argument should be the complete JSON structure that is
+/// deserialized from the sessionstore file.
+pub fn sanitize(session_data: &mut serde_json::Value, options: &SanitizeOptions) -> Result<()> {
+    let all = options.all;
+    if all || options.cookies {
+        strip_cookies(session_data)?;
+    }
+    if all || options.closed {
+        if let Some(session) = session_data.as_object_mut() {
+            let closed_windows = session.insert(
+                "_closedWindows".to_owned(),
+                serde_json::Value::Array(Vec::new()),
+            );
+            info!(
+                "Removed {} closed windows from the sessionstore file",
+                closed_windows
+                    .as_ref()
+                    .and_then(serde_json::Value::as_array)
+                    .map_or(0, Vec::len)
+            );
+        }
+    }
+
+    let mut removed_entries = 0;
+    let mut sanitized_tabs = 0;
+
+    let session = session_store::serde_unstructured::view(session_data)
+        .cast::<session_store::FirefoxSessionStore>();
+
+    let (windows, closed_windows) = session.project(|p| (p.windows(), p._closed_windows()));
+    let windows = windows?.try_array_iter()?;
+    let closed_windows = closed_windows
+        .ok()
+        .map(|closed_windows| closed_windows.try_array_iter())
+        .transpose()?
+        .into_iter()
+        .flatten();
+
+    for mut window in windows.chain(closed_windows) {
+        if all || options.closed {
+            if let Some(window) = window.data.as_object_mut() {
+                window.insert(
+                    "_closedTabs".to_owned(),
+                    serde_json::Value::Array(Vec::new()),
+                );
+            }
+        }
+        let Ok(tabs) = window.as_mut().project(|p| p.tabs()) else {
+            warn!(
+                "A window had no tabs (window was skipped): {}",
+                window.tracker
+            );
+            continue;
+        };
+        for tab in tabs.try_array_iter()? {
+            let Some(tab_data) = tab.data.as_object_mut() else {
+                warn!("A tab was not an object (tab was skipped): {}", tab.tracker);
+                continue;
+            };
+            let mut was_affected = false;
+
+            if all || options.history {
+                let index = tab_data.get("index").and_then(serde_json::Value::as_i64);
+                if let Some(entries) = tab_data
+                    .get_mut("entries")
+                    .and_then(serde_json::Value::as_array_mut)
+                    .filter(|entries| entries.len() > 1)
+                {
+                    // Same as `TabInfo::current_entry_index` but fall back to
+                    // the last entry like Firefox does:
+                    let current = index
+                        .and_then(|index| usize::try_from(index - 1).ok())
+                        .filter(|&index| index < entries.len())
+                        .unwrap_or(entries.len() - 1);
+                    removed_entries += entries.len() - 1;
+                    let current_entry = entries.swap_remove(current);
+                    entries.clear();
+                    entries.push(current_entry);
+                    // The index is 1-based:
+                    tab_data.insert("index".to_owned(), 1.into());
+                    was_affected = true;
+                }
+            }
+            let mut remove_keys = |keys: &[&str]| {
+                for key in keys {
+                    was_affected |= tab_data.shift_remove(*key).is_some();
+                }
+            };
+            if all || options.form_data {
+                remove_keys(&["formdata"]);
+            }
+            if all || options.typed_values {
+                remove_keys(&["userTypedValue", "userTypedClear"]);
+            }
+            if all || options.scroll {
+                remove_keys(&["scroll"]);
+            }
+
+            if was_affected {
+                sanitized_tabs += 1;
+            }
+        }
+    }
+
+    info!(
+        "Sanitized {} tabs and removed {} history entries from the sessionstore file",
+        sanitized_tabs, removed_entries
+    );
+
+    Ok(())
+}
+
+#[derive(Debug, Args, Clone, Default)]
+#[clap(rename_all = "kebab-case")]
+pub struct RemoveMarkedTabsOptions {
+    #[clap(
+        long,
+        action = clap::ArgAction::Append,
+        use_value_delimiter = true,
+        help_heading = "Remove Marked Tabs"
+    )]
+    /// Remove tabs that are marked with a specific color in the extension
+    /// Sidebery. For example: "red".
+    ///
+    /// Multiple values can be specified by separating them with commas (,)
+    /// in which case a tab will be removed if it is marked with any of the
+    /// colors.
+    pub sidebery_colors: Vec<String>,
+
+    #[clap(long, help_heading = "Remove Marked Tabs")]
+    /// Keep windows that have no tabs left after the marked tabs were
+    /// removed. By default such windows are removed as well since Firefox
+    /// would otherwise restore them as blank windows.
+    pub keep_empty_windows: bool,
+}
+
+/// Modify Firefox session data so that marked tabs are removed.
+///
+/// The `session_data` argument should be the complete JSON structure that
+/// is deserialized from the sessionstore file.
+pub fn remove_marked_tabs(
+    session_data: &mut serde_json::Value,
+    options: &RemoveMarkedTabsOptions,
+) -> Result<()> {
+    use session_store::session_info::OwnedTabData;
+
+    let mut total_remove_count = 0;
+    let mut session = session_store::serde_unstructured::view(session_data)
+        .cast::<session_store::FirefoxSessionStore>();
+
+    // Windows that had all their tabs removed:
+    let mut emptied_windows = Vec::new();
+
+    let windows = session.as_mut().project(|p| p.windows())?;
+    for window in windows.try_array_iter()? {
+        let window_result = (|| -> Result<bool> {
+            let mut window_remove_count = 0;
+
+            let (tabs, selected) = window.project(|p| (p.tabs(), p.selected()));
+            let tabs = tabs?;
+            let mut selected_tab = try_!({
+                let selected = selected?;
+                let value = selected.as_ref().deserialize()?;
+                (selected, value)
+            })
+            .map_err(|e| {
+                error!(
+                    "could not get selected tab info for a window, \
+                    so can't update it if any tabs are removed: {e}"
+                );
+            })
+            .ok();
+
+            // Remove unwanted tabs from the array:
+            let mut idx = 0;
+            tabs.try_retain(|tab| {
                 // Deserialize the tab to get structured access to its data:
-                let keep_tab = match tab.as_ref().deserialize() {
-                    Ok(structured_tab) => {
+                let keep_tab = match OwnedTabData::from_value(tab.data) {
+                    Ok(owned_tab) => {
                         let removed_sidebery_color = matches!(
-                            &structured_tab.ext_data.sidebery_data,
+                            &owned_tab.data.ext_data.sidebery_data,
                             Some(data) if matches!(&data.custom_color,
                                 Some(color) if options.sidebery_colors.contains(color)
                             )
                         );
 
                         if removed_sidebery_color
-                            || structured_tab.ext_data.marked_for_removal.is_some()
+                            || owned_tab.data.ext_data.marked_for_removal.is_some()
                         {
-                            let info = session_store::session_info::TabInfo::new(&structured_tab);
+                            let info = owned_tab.info();
                             trace!(
                                 r#"Removing tab with title "{}" and the URL "{}""#,
                                 info.title(),
@@ -568,10 +1326,14 @@ pub fn remove_marked_tabs(
                     *slot.data = selected_tab.into();
                 }
             }
-            Ok(())
+            Ok(window_remove_count > 0 && idx == window_remove_count)
         })();
-        if let Err(e) = window_result {
-            warn!("failed to remove marked tabs from a window: {e}");
+        match window_result {
+            Ok(emptied) => emptied_windows.push(emptied),
+            Err(e) => {
+                warn!("failed to remove marked tabs from a window: {e}");
+                emptied_windows.push(false);
+            }
         }
     }
 
@@ -580,6 +1342,454 @@ pub fn remove_marked_tabs(
         total_remove_count
     );
 
+    if !options.keep_empty_windows && emptied_windows.contains(&true) {
+        if emptied_windows.iter().all(|&emptied| emptied) {
+            // Firefox always restores at least one window:
+            emptied_windows[0] = false;
+        }
+
+        let (windows, selected_window) = session.project(|p| (p.windows(), p.selected_window()));
+
+        let mut idx = 0;
+        windows?.try_retain(|_| {
+            let keep = !emptied_windows[idx];
+            idx += 1;
+            keep
+        })?;
+
+        let selected_window = try_!({
+            let selected_window = selected_window?;
+            let value: i64 = selected_window.as_ref().deserialize()?;
+            (selected_window, value)
+        });
+        match selected_window {
+            Ok((slot, selected)) => {
+                let new_selected = selected_index_after_removal(selected, &emptied_windows);
+                debug!("Changed selected window index from {selected} to {new_selected}.");
+                *slot.data = new_selected.into();
+            }
+            Err(e) => error!(
+                "could not get the selected window index, \
+                so it wasn't updated after empty windows were removed: {e}"
+            ),
+        }
+
+        info!(
+            "Removed {} empty windows from the sessionstore file",
+            emptied_windows.iter().filter(|&&emptied| emptied).count()
+        );
+    }
+
+    Ok(())
+}
+
+/// Get the new 1-based index of a selected item after the items that are
+/// `true` in `removed` have been removed from a list.
+///
+/// If the selected item was removed then the item after it is selected, or
+/// the last remaining item if there was no item after it.
+fn selected_index_after_removal(selected: i64, removed: &[bool]) -> i64 {
+    let remaining = removed.iter().filter(|&&removed| !removed).count() as i64;
+    let removed_before = removed
+        .iter()
+        .take(usize::try_from(selected - 1).unwrap_or(0))
+        .filter(|&&removed| removed)
+        .count() as i64;
+    (selected - removed_before).clamp(1.min(remaining), remaining)
+}
+
+/// Run the `tabs-to-links` command for a single input.
+fn tabs_to_links_for_input(
+    command: &to_links::TabsToLinksOpt,
+    reader_creator: &io_utils::InputReader,
+    mut writer_creator: io_utils::OutputWriter,
+) -> Result<()> {
+    let mut options = command.parse_options()?;
+
+    info!(
+        "Deserializing JSON data from {}",
+        reader_creator.reader_info()
+    );
+
+    let mut session = if command.exclude_extension_data {
+        if !command.tree_data.is_empty() {
+            warn!("Tab trees can't be shown since extension data is excluded");
+        }
+        let mut session = reader_creator.deserialize_json_data::<serde_json::Value>()?;
+        strip_extension_data(&mut session)?;
+        serde_json::from_value::<session_store::FirefoxSessionStore>(session).with_context(
+            || {
+                format!(
+                    "Failed to parse session data from {}",
+                    reader_creator.reader_info()
+                )
+            },
+        )?
+    } else {
+        reader_creator.deserialize_json_data::<session_store::FirefoxSessionStore>()?
+    };
+    if command.normalize_urls {
+        let changed = command.normalize_urls(&mut session);
+        info!("Removed tracking parameters from {} URLs", changed);
+    }
+    if command.resolve_titles {
+        let changed = command.resolve_titles(&mut session)?;
+        info!("Downloaded titles for {} tabs", changed);
+    }
+
+    let writer_info = writer_creator.output_info().to_string();
+
+    info!("Writing links to {}", writer_info);
+
+    // Select windows/groups:
+    let groups = command.tab_group_options.get_groups(&session);
+    let mut groups = if !command.tab_group_indexes.is_empty() || !command.tab_group_names.is_empty()
+    {
+        groups
+            .enumerate()
+            .filter(|(index, group)| {
+                command.tab_group_indexes.contains(&(*index as u64))
+                    || command
+                        .tab_group_names
+                        .iter()
+                        .any(|name| name == group.name())
+            })
+            .map(|(_, group)| group)
+            .collect::<Vec<_>>()
+    } else {
+        groups.collect::<Vec<_>>()
+    };
+    command.filter_tabs(&mut groups);
+    command.pinned_section(&mut groups);
+    command.flatten_groups(&mut groups);
+    if let Some(total) = command.limit_tabs(&mut groups, &options.conversion_options) {
+        info!(
+            "Only writing {} of {} tabs because of the tab limit",
+            command.tab_limit.unwrap_or_default(),
+            total
+        );
+        options.conversion_options.truncated_from = Some(total);
+    }
+    command.paginate_groups(&mut groups);
+
+    if command.split_output_by_group {
+        drop(options);
+        return tabs_to_links_per_group(command, &groups, &writer_creator);
+    }
+
+    tabs_to_links(&groups, options, &mut writer_creator)
+        .with_context(|| format!("Failed to write links to {}.", writer_info))?;
+    drop(session);
+
+    command
+        .session_store_opt
+        .in_out_info
+        .handle_output(writer_creator)?;
+
+    Ok(())
+}
+
+/// Write the links for each group to a separate file. The files are named after
+/// `writer_creator`'s path with the group's name appended.
+/// Choose an output path for each group. Groups whose names are the same
+/// after being sanitized get different paths even if no files have been
+/// written yet.
+fn group_output_paths<'a>(
+    dir: &Path,
+    stem: &str,
+    extension: &str,
+    group_names: impl IntoIterator<Item = &'a str>,
+    overwrite: bool,
+) -> Result<Vec<PathBuf>> {
+    let mut chosen = HashSet::new();
+    let mut paths = Vec::new();
+    for (index, name) in group_names.into_iter().enumerate() {
+        let mut group_name = find::sanitize_file_name(name);
+        if group_name.is_empty() {
+            group_name = format!("Window {}", index + 1);
+        }
+        let path = find::generate_file_names(dir, |index| {
+            format!(
+                "{} - {}{}.{}",
+                stem,
+                group_name,
+                if index == 0 {
+                    String::new()
+                } else {
+                    format!(" ({})", index)
+                },
+                extension
+            )
+        })
+        .find(|path| !chosen.contains(path) && (overwrite || !path.exists()))
+        .ok_or_else(|| eyre::eyre!("Couldn't find an unused path to use for the output file."))?;
+        chosen.insert(path.clone());
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+fn tabs_to_links_per_group(
+    command: &to_links::TabsToLinksOpt,
+    groups: &[session_store::session_info::TabGroup<'_>],
+    writer_creator: &io_utils::OutputWriter,
+) -> Result<()> {
+    let io_utils::OutputWriter::OutputPath { path, overwrite } = writer_creator else {
+        eyre::bail!("Can't write a separate file for each window when writing to stdout");
+    };
+    let overwrite = *overwrite;
+    let dir = path.parent().map(PathBuf::from).unwrap_or_default();
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = command.parse_options()?.file_extension();
+    let jobs = command.jobs.get();
+
+    let group_names = groups.iter().map(|group| group.name());
+    let paths = group_output_paths(&dir, &stem, extension, group_names, overwrite)?;
+
+    let mut outputs = Vec::with_capacity(groups.len());
+    for path in paths {
+        let writer_creator = io_utils::OutputWriter::OutputPath { path, overwrite };
+        outputs.push(if jobs > 1 {
+            reserve_output(writer_creator)?
+        } else {
+            writer_creator
+        });
+    }
+
+    let results = run_jobs(
+        jobs,
+        groups.iter().zip(outputs),
+        |(group, mut writer_creator)| {
+            let mut options = command.parse_options()?;
+            options.conversion_options.table_of_contents = false;
+
+            info!(
+                "Writing links for \"{}\" to {}",
+                group.name(),
+                writer_creator.output_info()
+            );
+            tabs_to_links(std::slice::from_ref(group), options, &mut writer_creator)
+                .with_context(|| format!("Failed to write links to {}.", writer_creator))?;
+            command
+                .session_store_opt
+                .in_out_info
+                .handle_output(writer_creator)
+        },
+    );
+    results.into_iter().collect()
+}
+
+/// Create an empty file at the output path so that jobs that run at the same
+/// time can't choose the same path. The returned writer overwrites the
+/// reserved file.
+fn reserve_output(writer_creator: io_utils::OutputWriter) -> Result<io_utils::OutputWriter> {
+    match writer_creator {
+        io_utils::OutputWriter::OutputPath {
+            path,
+            overwrite: false,
+        } => {
+            find::create_file(false, &path).with_context(|| {
+                format!("Failed to create an output file at \"{}\"", path.display())
+            })?;
+            Ok(io_utils::OutputWriter::OutputPath {
+                path,
+                overwrite: true,
+            })
+        }
+        writer_creator => Ok(writer_creator),
+    }
+}
+
+/// Call `task` for each item using up to `jobs` threads. The results are in the
+/// same order as the items.
+fn run_jobs<T, R>(
+    jobs: usize,
+    items: impl IntoIterator<Item = T>,
+    task: impl Fn(T) -> R + Sync,
+) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+{
+    if jobs <= 1 {
+        return items.into_iter().map(task).collect();
+    }
+    let items = items
+        .into_iter()
+        .map(Some)
+        .map(Mutex::new)
+        .collect::<Vec<_>>();
+    let results = items.iter().map(|_| Mutex::new(None)).collect::<Vec<_>>();
+    let next = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for _ in 0..jobs.min(items.len()) {
+            s.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let item = item
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .expect("each item is taken once");
+                *results[index].lock().unwrap() = Some(task(item));
+            });
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| {
+            result
+                .into_inner()
+                .unwrap()
+                .expect("all jobs have completed")
+        })
+        .collect()
+}
+
+/// Run the `tabs-to-links` command for every file that matches a glob
+/// pattern.
+#[cfg(feature = "glob")]
+fn tabs_to_links_for_glob(command: &to_links::TabsToLinksOpt, pattern: &str) -> Result<()> {
+    let paths = glob::glob(pattern)
+        .with_context(|| format!("Invalid input glob pattern: \"{}\"", pattern))?
+        .map(|path| path.context("Failed to read a path that matched the input glob"))
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        eyre::bail!("No input files matched the glob pattern: \"{}\"", pattern);
+    }
+    tabs_to_links_for_paths(command, paths)
+}
+
+/// Run the `tabs-to-links` command for every path that is listed in a file or
+/// in stdin if the path is `-`.
+fn tabs_to_links_for_file_list(command: &to_links::TabsToLinksOpt, list_path: &Path) -> Result<()> {
+    let list = if list_path == Path::new("-") {
+        let mut list = String::new();
+        io::stdin()
+            .read_to_string(&mut list)
+            .context("Failed to read the input file list from stdin")?;
+        list
+    } else {
+        std::fs::read_to_string(list_path).with_context(|| {
+            format!(
+                "Failed to read the input file list at \"{}\"",
+                list_path.display()
+            )
+        })?
+    };
+    let paths = parse_input_file_list(&list)
+        .map(|path| Ok(PathBuf::from(path)))
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        eyre::bail!("The input file list didn't contain any paths");
+    }
+    tabs_to_links_for_paths(command, paths)
+}
+
+/// The paths in an input file list. Blank lines and lines that start with `#`
+/// are ignored.
+fn parse_input_file_list(list: &str) -> impl Iterator<Item = &str> {
+    list.trim_start_matches('\u{FEFF}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Run the `tabs-to-links` command for multiple input files. Each input file
+/// gets its own output file that is named after the input file. If
+/// `--continue-on-error` was specified then files that fail are skipped and a
+/// summary is logged at the end.
+fn tabs_to_links_for_paths(
+    command: &to_links::TabsToLinksOpt,
+    paths: Vec<Result<PathBuf>>,
+) -> Result<()> {
+    let file_extension = command.parse_options()?.file_extension();
+    let in_out_info = &command.session_store_opt.in_out_info;
+
+    let jobs = command.jobs.get();
+
+    let total = paths.len();
+    // Choose output paths before starting any jobs so that they don't collide:
+    let inputs = paths
+        .into_iter()
+        .map(|path| {
+            try_!({
+                let reader_creator = io_utils::InputReader {
+                    state: io_utils::InputReaderState::InputPath(path?),
+                    is_compressed: command.session_store_opt.compression.input_is_compressed(),
+                };
+                let writer_creator = in_out_info.get_writer_creator_from_reader_creator(
+                    &reader_creator,
+                    "Links",
+                    "",
+                    "",
+                    file_extension,
+                )?;
+                let writer_creator = if jobs > 1 && !command.split_output_by_group {
+                    reserve_output(writer_creator)?
+                } else {
+                    writer_creator
+                };
+                (reader_creator, writer_creator)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let stop = std::sync::atomic::AtomicBool::new(false);
+    let results = run_jobs(jobs, inputs, |input| {
+        if stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        let result = input.and_then(|(reader_creator, writer_creator)| {
+            info!(
+                "Reading input from file at: {}",
+                reader_creator.reader_info()
+            );
+            let reserved_path = writer_creator.path().map(PathBuf::from);
+            let result = tabs_to_links_for_input(command, &reader_creator, writer_creator);
+            if let (Err(_), Some(path), true) = (&result, reserved_path, jobs > 1) {
+                // Don't leave an empty reserved file behind:
+                if std::fs::metadata(&path).is_ok_and(|info| info.len() == 0) {
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+            result
+        });
+        if result.is_err() && !command.continue_on_error {
+            stop.store(true, Ordering::Relaxed);
+        }
+        Some(result)
+    });
+
+    let mut failed = 0;
+    for result in results.into_iter().flatten() {
+        if let Err(e) = result {
+            if !command.continue_on_error {
+                return Err(e);
+            }
+            failed += 1;
+            error!("{:#}", e);
+        }
+    }
+
+    info!(
+        "Processed {} input files: {} succeeded and {} failed",
+        total,
+        total - failed,
+        failed
+    );
+    if failed > 0 {
+        eyre::bail!(
+            "Failed to write links for {} of {} input files",
+            failed,
+            total
+        );
+    }
     Ok(())
 }
 
@@ -603,7 +1813,14 @@ where
                 .start(html_to_pdf::PdfScope::scoped(s), &mut writer_creator)?,
             )
         } else {
-            Right(writer_creator.get_writer()?)
+            let writer = writer_creator.get_writer()?;
+            Right(match options.encoding {
+                to_links::OutputEncoding::Utf8 => Left(writer),
+                to_links::OutputEncoding::Utf16le => Right(
+                    io_utils::Utf16LeWriter::new(writer)
+                        .context("Failed to write UTF-16 Byte Order Mark.")?,
+                ),
+            })
         };
 
         // TODO: only write utf8 BOM for some file formats (maybe only for Text or Markdown?).
@@ -613,8 +1830,16 @@ where
             .conversion_options
             .write_links(groups, &mut writer)?;
 
-        if let Left(pdf_writer) = writer {
-            pdf_writer.complete().context("PDF conversion failed")?;
+        match writer {
+            Left(pdf_writer) => {
+                pdf_writer.complete().context("PDF conversion failed")?;
+            }
+            Right(Right(utf16_writer)) => {
+                utf16_writer
+                    .finish()
+                    .context("Failed to write UTF-16 encoded links.")?;
+            }
+            Right(Left(_)) => {}
         }
 
         Ok(())
@@ -624,10 +1849,22 @@ where
 fn modify_sessionstore(
     session_opt: &SessionstoreOpt,
     overwrite_opt: &OverwriteInputOpt,
+    output_compression: &OutputCompressionOpt,
+    free_space: &FreeSpaceOpt,
     output_postfix: &str,
     modify: impl FnOnce(Arc<Vec<u8>>, &InputReader) -> Result<Vec<u8>>,
 ) -> Result<()> {
     let reader_creator = session_opt.get_reader_creator()?;
+    let overwrite_path = if overwrite_opt.overwrite_input || overwrite_opt.swap {
+        // Checked before anything is read since stdin or a URL can't be
+        // overwritten:
+        let io_utils::InputReaderState::InputPath(input_path) = &reader_creator.state else {
+            eyre::bail!("--overwrite-input/--swap require a local input file");
+        };
+        Some(input_path)
+    } else {
+        None
+    };
     let mut input_data;
     let mut encoder = {
         let modified_json_data = {
@@ -644,15 +1881,14 @@ fn modify_sessionstore(
         info!("Compressing modified JSON data");
 
         // TODO: Allow writing uncompressed sessionstore files.
-        compression::Encoder::compress(&modified_json_data, None, COMPRESSION_LIBRARY)
+        output_compression
+            .compress(&modified_json_data, COMPRESSION_LIBRARY)
             .context("Failed to compress modified sessionstore data.")?
         // Drop modified_json_data here.
     };
 
-    if overwrite_opt.overwrite_input || overwrite_opt.swap {
-        let io_utils::InputReaderState::InputPath(input_path) = &reader_creator.state else {
-            unreachable!("argument parser should ensure we don't read from stdin when overwriting input file");
-        };
+    if let Some(input_path) = overwrite_path {
+        free_space.check(Some(input_path), encoder.compressed_len() as u64)?;
 
         let writer_creator = if overwrite_opt.swap {
             let writer_creator = session_opt
@@ -678,6 +1914,7 @@ fn modify_sessionstore(
                     "We should always remember the input data when run with the --swap flag"
                 );
             };
+            free_space.check(writer_creator.path(), input_data.len() as u64)?;
 
             io::copy(&mut &**input_data, &mut writer_creator.get_writer()?).with_context(|| {
                 format!("Failed to write original input data to {}.", writer_creator)
@@ -730,6 +1967,8 @@ fn modify_sessionstore(
                 "jsonlz4",
             )?;
 
+        free_space.check(writer_creator.path(), encoder.compressed_len() as u64)?;
+
         info!(
             "Writing compressed data to {}",
             writer_creator.output_info()
@@ -748,14 +1987,335 @@ fn modify_sessionstore(
     Ok(())
 }
 
+/// Count how many of the URLs use each host. Hosts are counted together if
+/// `domain_key` returns the same domain for them, it isn't called for IP
+/// addresses. The most used domains come
+/// first and domains that are used equally often are sorted by name.
+fn count_domains<'a>(
+    urls: impl IntoIterator<Item = &'a str>,
+    domain_key: impl Fn(&str) -> String,
+) -> Vec<(String, u32)> {
+    // Code inspired by blog post at:
+    // https://blog.dend.ro/decoding-firefox-session-store-data/
+    let mut domains = HashMap::<String, u32>::new();
+    for url in urls {
+        match url::Url::parse(url) {
+            Ok(url) => {
+                // skip about:blank, about:reader etc.
+                let domain = match url.host() {
+                    Some(url::Host::Domain(domain)) => domain_key(domain),
+                    Some(ip_address) => ip_address.to_string(),
+                    None => continue,
+                };
+                *domains.entry(domain).or_default() += 1;
+            }
+            Err(e) => {
+                error!("Failed to parse the tab URL {:?} because: {}", url, e);
+            }
+        }
+    }
+    let mut domains = domains.into_iter().collect::<Vec<_>>();
+    domains.sort_unstable_by(|(a_host, a_count), (b_host, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_host.cmp(b_host))
+    });
+    domains
+}
+
+/// Maps a host to the domain it is counted as by the `domains` command.
+type DomainKey = Box<dyn Fn(&str) -> String>;
+
+/// Get a function that maps a host to its registrable domain using the public
+/// suffix list at `public_suffix_list`.
+#[cfg_attr(not(feature = "publicsuffix"), allow(unused_variables))]
+fn registrable_domain_key(public_suffix_list: Option<&Path>) -> Result<DomainKey> {
+    #[cfg(not(feature = "publicsuffix"))]
+    {
+        eyre::bail!(
+            "Counting registrable domains isn't supported since this program was built without the \"publicsuffix\" feature"
+        );
+    }
+    #[cfg(feature = "publicsuffix")]
+    {
+        let path = public_suffix_list
+            .ok_or_else(|| eyre::eyre!("A public suffix list must be specified"))?;
+        let list = std::fs::read_to_string(path)
+            .with_context(|| {
+                format!(
+                    "Failed to read the public suffix list at \"{}\"",
+                    path.display()
+                )
+            })?
+            .parse::<publicsuffix::List>()
+            .map_err(|e| {
+                eyre::eyre!(
+                    "Failed to parse the public suffix list at \"{}\": {}",
+                    path.display(),
+                    e
+                )
+            })?;
+        Ok(Box::new(move |host| registrable_domain(&list, host)))
+    }
+}
+
+/// The registrable domain (eTLD+1) of a host or the host itself if it doesn't
+/// have one.
+#[cfg(feature = "publicsuffix")]
+fn registrable_domain(list: &publicsuffix::List, host: &str) -> String {
+    use publicsuffix::Psl;
+
+    list.domain(host.as_bytes())
+        .and_then(|domain| std::str::from_utf8(domain.as_bytes()).ok())
+        .unwrap_or(host)
+        .to_owned()
+}
+
+/// Get the JSON data for the tab at index `tab` in the window at index
+/// `window`. The window is one of the session's recently closed windows if
+/// `closed_window` is `true`.
+///
+/// Returns an error that mentions how many windows or tabs there are if an
+/// index is out of range.
+pub fn find_tab_data(
+    session_data: &serde_json::Value,
+    window: usize,
+    tab: usize,
+    closed_window: bool,
+) -> Result<&serde_json::Value> {
+    let (key, kind) = if closed_window {
+        ("_closedWindows", "closed windows")
+    } else {
+        ("windows", "open windows")
+    };
+    let windows = session_data
+        .get(key)
+        .and_then(serde_json::Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let window_data = windows.get(window).ok_or_else(|| {
+        eyre::eyre!(
+            "There is no window at index {} since the session has {} {}",
+            window,
+            windows.len(),
+            kind
+        )
+    })?;
+    let tabs = window_data
+        .get("tabs")
+        .and_then(serde_json::Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    tabs.get(tab).ok_or_else(|| {
+        eyre::eyre!(
+            "There is no tab at index {} since the window at index {} has {} tabs",
+            tab,
+            window,
+            tabs.len()
+        )
+    })
+}
+
+/// Print the number of windows and tabs in every Firefox profile's sessionstore
+/// file, profiles with the most tabs first.
+fn compare_profiles(json: bool) -> Result<()> {
+    #[derive(serde::Serialize)]
+    struct ProfileCounts {
+        profile: String,
+        session_file: Option<PathBuf>,
+        /// `None` if the profile's sessionstore file couldn't be read.
+        window_count: Option<usize>,
+        tab_count: Option<usize>,
+    }
+
+    let finder = find::FirefoxProfileFinder::new()?;
+    info!(
+        r#"Reading sessionstore files for Firefox profiles in "{}""#,
+        finder.profile_root.display()
+    );
+
+    let mut profiles = finder
+        .all_profiles()?
+        .iter()
+        .map(|(profile_dir, _)| {
+            let profile = find::path_to_file_name(profile_dir);
+            let session_file =
+                find::default_session_file(profile_dir, &["jsonlz4".into(), "js".into()]);
+            let session = match &session_file {
+                Some(path) => InputReader {
+                    state: io_utils::InputReaderState::InputPath(path.clone()),
+                    is_compressed: None,
+                }
+                .deserialize_json_data::<session_store::FirefoxSessionStore>()
+                .map_err(|e| {
+                    warn!(r#"Failed to read the sessionstore file of the "{profile}" profile: {e}"#)
+                })
+                .ok(),
+                None => {
+                    info!(r#"No sessionstore file found for the "{profile}" profile"#);
+                    None
+                }
+            };
+            ProfileCounts {
+                window_count: session.as_ref().map(|session| session.window_count()),
+                tab_count: session.as_ref().map(|session| session.tab_count()),
+                profile,
+                session_file,
+            }
+        })
+        .collect::<Vec<_>>();
+    profiles.sort_by_key(|profile| Reverse(profile.tab_count));
+
+    let mut stdout = io::stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut stdout, &profiles)
+            .context("Failed to serialize profile info to stdout")?;
+        return Ok(());
+    }
+    try_!({
+        let name_width = profiles
+            .iter()
+            .map(|profile| profile.profile.chars().count())
+            .chain(Some("Profile".len()))
+            .max()
+            .unwrap_or_default();
+        writeln!(
+            stdout,
+            "{:name_width$}  {:>7}  {:>7}",
+            "Profile", "Windows", "Tabs"
+        )?;
+        let count =
+            |count: Option<usize>| count.map_or_else(|| "n/a".to_owned(), |c| c.to_string());
+        for profile in &profiles {
+            writeln!(
+                stdout,
+                "{:name_width$}  {:>7}  {:>7}",
+                profile.profile,
+                count(profile.window_count),
+                count(profile.tab_count)
+            )?;
+        }
+    })
+    .context("Failed to write profile info to stdout.")
+}
+
+/// Information about a command line option, see [`describe_options`].
+#[derive(Debug, serde::Serialize)]
+pub struct OptionDescription {
+    /// Unique identifier for the option.
+    pub id: String,
+    pub long: Option<String>,
+    pub short: Option<char>,
+    pub aliases: Vec<String>,
+    /// Either "flag" for options without values, "count" for flags that can
+    /// be repeated or "value" for options that take values.
+    pub kind: &'static str,
+    /// `true` if the option can be specified more than once or accepts
+    /// multiple values.
+    pub multiple: bool,
+    pub required: bool,
+    pub value_name: Option<String>,
+    /// The only values that are allowed, empty if any value is allowed.
+    pub possible_values: Vec<String>,
+    pub default_values: Vec<String>,
+    pub help: Option<String>,
+    pub long_help: Option<String>,
+    pub heading: Option<String>,
+}
+
+/// Describe the options that `command` accepts. Hidden options and the
+/// `--help` and `--version` flags are skipped.
+pub fn describe_options(command: &clap::Command) -> Vec<OptionDescription> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .filter_map(|arg| {
+            let kind = match arg.get_action() {
+                clap::ArgAction::SetTrue | clap::ArgAction::SetFalse => "flag",
+                clap::ArgAction::Count => "count",
+                clap::ArgAction::Set | clap::ArgAction::Append => "value",
+                _ => return None,
+            };
+            Some(OptionDescription {
+                id: arg.get_id().to_string(),
+                long: arg.get_long().map(str::to_owned),
+                short: arg.get_short(),
+                aliases: arg
+                    .get_visible_aliases()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(str::to_owned)
+                    .collect(),
+                kind,
+                multiple: matches!(arg.get_action(), clap::ArgAction::Append)
+                    || arg
+                        .get_num_args()
+                        .is_some_and(|range| range.max_values() > 1),
+                required: arg.is_required_set(),
+                value_name: arg
+                    .get_value_names()
+                    .and_then(|names| names.first())
+                    .map(|name| name.to_string())
+                    .filter(|_| kind == "value"),
+                possible_values: arg
+                    .get_possible_values()
+                    .iter()
+                    .filter(|value| !value.is_hide_set())
+                    .map(|value| value.get_name().to_owned())
+                    .collect(),
+                default_values: arg
+                    .get_default_values()
+                    .iter()
+                    .map(|value| value.to_string_lossy().into_owned())
+                    .collect(),
+                help: arg.get_help().map(ToString::to_string),
+                long_help: arg.get_long_help().map(ToString::to_string),
+                heading: arg.get_help_heading().map(str::to_owned),
+            })
+        })
+        .collect()
+}
+
+/// Generate a script that provides completions for this program in the
+/// specified shell.
+fn completion_script(shell: clap_complete::Shell) -> Vec<u8> {
+    let mut script = Vec::new();
+    clap_complete::generate(
+        shell,
+        &mut <Opt as clap::CommandFactory>::command(),
+        // Same as the binary's name in "Cargo.toml":
+        "firefox-session-data",
+        &mut script,
+    );
+    script
+}
+
 pub fn run() -> Result<()> {
     color_eyre::install()?;
 
     let result = try_!({
         let opt = Opt::parse();
 
-        if let Opt::TabsToLinksFormats { json } = opt {
-            if json {
+        if let Opt::Completions { shell } = opt {
+            // Generate to a buffer since `clap_complete` panics on write errors
+            // (such as when the output is piped into `head`):
+            let script = completion_script(shell);
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&script)?;
+            stdout.flush()?;
+            return Ok(());
+        }
+        if let Opt::TabsToLinksFormats {
+            json,
+            describe_options: describe,
+        } = opt
+        {
+            if describe {
+                let command = <Opt as clap::CommandFactory>::command();
+                let command = command
+                    .find_subcommand("tabs-to-links")
+                    .expect("the tabs-to-links command should exist");
+                serde_json::to_writer_pretty(io::stdout().lock(), &describe_options(command))
+                    .context("Failed to serialize option info to stdout")?;
+            } else if json {
                 #[derive(serde::Serialize)]
                 struct JsonInfo<'a> {
                     name: &'a str,
@@ -777,6 +2337,7 @@ pub fn run() -> Result<()> {
                             file_extension: to_links::TabsToLinksOutput {
                                 format: link_format,
                                 as_pdf,
+                                encoding: to_links::OutputEncoding::Utf8,
                                 conversion_options: Default::default(),
                             }
                             .file_extension(),
@@ -820,14 +2381,141 @@ pub fn run() -> Result<()> {
                 session,
                 type_script,
                 max_object_keys,
+                distinct_strings,
+                histogram,
+                counts_only,
+                number_locale,
+                flat,
+                show_largest,
+                compare_with,
+                json,
+                exclude_extension_data,
             } => {
                 debug!("Executing: Analyze command");
+                let mut formatter = StandardStatisticsFormatter::standard();
+                if histogram {
+                    formatter.add_info_to_print(ExtraFMTInfo::Histogram);
+                }
+                if let Some(code) = &number_locale {
+                    #[cfg(feature = "with_num_format")]
+                    {
+                        formatter.format_options.number_locale = Some(
+                            json_statistics::num_format::Locale::from_name(code)
+                                .with_context(|| format!("Unknown number locale \"{}\".", code))?,
+                        );
+                    }
+                    #[cfg(not(feature = "with_num_format"))]
+                    warn!(
+                        "Ignoring the number locale \"{}\" since this program was built without support for formatting numbers",
+                        code
+                    );
+                }
                 let reader_creator = session.get_reader_creator()?;
+                let collect_options = CollectOptions {
+                    distinct_strings,
+                    largest_values: show_largest,
+                    collect_sizes: !counts_only,
+                };
+                let collect_stats = |reader: &InputReader| -> Result<JSONValueStatistics> {
+                    if exclude_extension_data {
+                        let mut value = reader.deserialize_json_data::<serde_json::Value>()?;
+                        strip_extension_data(&mut value)?;
+                        return Ok(collect_statistics_with_options(&value, &collect_options));
+                    }
+                    // Collect statistics while parsing so that large inputs
+                    // (for example piped to stdin) never need to be held in
+                    // memory as a `serde_json::Value`:
+                    match reader.get_reader()? {
+                        Either::Left(slice_reader) => collect_statistics_from_reader(
+                            slice_reader.data.as_slice(),
+                            &collect_options,
+                        )
+                        .map_err(|e| json_parse_error_context(e, &slice_reader.data)),
+                        Either::Right(stream) => {
+                            collect_statistics_from_reader(stream, &collect_options)
+                                .map_err(eyre::Report::new)
+                        }
+                    }
+                    .with_context(|| {
+                        format!("Failed to analyze JSON data from {}", reader.reader_info())
+                    })
+                };
+
+                if let Some(original) = compare_with {
+                    let original_reader = InputReader {
+                        state: io_utils::InputReaderState::InputPath(original),
+                        is_compressed: None,
+                    };
+
+                    info!(
+                        "Analyzing JSON data from {} and {}",
+                        original_reader.reader_info(),
+                        reader_creator.reader_info()
+                    );
+                    let original_stats = collect_stats(&original_reader)?;
+                    let stats = collect_stats(&reader_creator)?;
+                    let paths = json_statistics::print::compare_paths(&original_stats, &stats);
+                    drop((original_stats, stats));
+
+                    let writer_creator =
+                        session.in_out_info.get_writer_creator_from_reader_creator(
+                            &reader_creator,
+                            "",
+                            "-",
+                            "json-comparison",
+                            if json { "json" } else { "txt" },
+                        )?;
+                    {
+                        let mut writer = writer_creator.get_writer()?;
+
+                        if json {
+                            #[derive(serde::Serialize)]
+                            struct JsonPath<'a> {
+                                path: &'a str,
+                                old_count: usize,
+                                new_count: usize,
+                                old_size: u64,
+                                new_size: u64,
+                            }
+                            let json_paths = paths
+                                .iter()
+                                .map(|path| JsonPath {
+                                    path: &path.path,
+                                    old_count: path.old_count,
+                                    new_count: path.new_count,
+                                    old_size: path.old_size,
+                                    new_size: path.new_size,
+                                })
+                                .collect::<Vec<_>>();
+                            serde_json::to_writer_pretty(writer, &json_paths).with_context(
+                                || {
+                                    format!(
+                                        "Failed to serialize JSON comparison to {}",
+                                        writer_creator
+                                    )
+                                },
+                            )?;
+                        } else {
+                            write!(
+                                writer,
+                                "{}",
+                                json_statistics::print::ComparisonTable {
+                                    paths: &paths,
+                                    ..Default::default()
+                                }
+                            )
+                            .with_context(|| {
+                                format!("Failed to write JSON comparison to {}.", writer_creator)
+                            })?;
+                        }
+                    }
+
+                    session.in_out_info.handle_output(writer_creator)?;
+                    return Ok(());
+                }
 
                 info!("Analyzing JSON data");
-                let stats = collect_statistics(
-                    &reader_creator.deserialize_json_data::<serde_json::Value>()?,
-                );
+                let stats = collect_stats(&reader_creator)?;
 
                 let writer_creator = session.in_out_info.get_writer_creator_from_reader_creator(
                     &reader_creator,
@@ -857,8 +2545,14 @@ pub fn run() -> Result<()> {
                                 max_object_keys,
                             })
                         )
+                    } else if flat {
+                        write!(
+                            writer,
+                            "{}",
+                            stats.with_formatter(FlatStatisticsFormatter::new())
+                        )
                     } else {
-                        write!(writer, "{}", stats)
+                        write!(writer, "{}", stats.with_formatter(formatter))
                     })
                     .with_context(|| {
                         format!(
@@ -872,7 +2566,10 @@ pub fn run() -> Result<()> {
 
                 session.in_out_info.handle_output(writer_creator)?;
             }
-            Opt::Copy(command) => {
+            Opt::Copy {
+                preserve_mtime,
+                session: command,
+            } => {
                 debug!("Executing: Copy command");
                 let reader_creator = command.get_reader_creator()?;
 
@@ -901,22 +2598,48 @@ pub fn run() -> Result<()> {
                     format!("Failed to write input data to {}.", writer_creator)
                 })?;
                 drop(reader);
+                preserve_mtime.apply(&reader_creator, &writer_creator)?;
 
                 command.in_out_info.handle_output(writer_creator)?;
             }
-            Opt::Compress(command) => {
+            Opt::Compress {
+                output_compression,
+                free_space,
+                dry_run,
+                preserve_mtime,
+                in_out: command,
+            } => {
                 debug!("Executing: Compress command");
+                let reader_creator = command.get_reader_creator(Some(false), &["js".into()])?;
                 let mut encoder = {
-                    let reader_creator = command.get_reader_creator(Some(false), &["js".into()])?;
                     let data = reader_creator.create_slice_reader()?.data;
 
-                    info!("Compressing data from {}", reader_creator.reader_info());
+                    info!(
+                        "Compressing data from {} using the \"{}\" compression mode",
+                        reader_creator.reader_info(),
+                        output_compression.compression_mode
+                    );
 
-                    compression::Encoder::compress(&data, None, COMPRESSION_LIBRARY)
+                    output_compression
+                        .compress(&data, COMPRESSION_LIBRARY)
                         .context("Failed to compress data.")?
                 };
 
+                info!(
+                    "Compressed {} bytes to {} bytes ({:.1}% of the original size)",
+                    encoder.uncompressed_len(),
+                    encoder.compressed_len(),
+                    encoder.compressed_len() as f64 * 100.0
+                        / encoder.uncompressed_len().max(1) as f64
+                );
+                if dry_run {
+                    info!("Dry run so no output was written");
+                    info!("Finished");
+                    return Ok(());
+                }
+
                 let writer_creator = command.get_writer_creator("sessionstore", "jsonlz4")?;
+                free_space.check(writer_creator.path(), encoder.compressed_len() as u64)?;
 
                 info!(
                     "Writing compressed data to {}",
@@ -927,48 +2650,119 @@ pub fn run() -> Result<()> {
                     format!("Failed to write compressed data to {}.", writer_creator)
                 })?;
                 drop(encoder);
+                preserve_mtime.apply(&reader_creator, &writer_creator)?;
 
                 command.handle_output(writer_creator)?;
             }
-            Opt::Decompress(command) => {
-                debug!("Executing: Decompress command");
-                let decompressed = {
-                    let reader_creator =
-                        command.get_reader_creator(Some(false), &["jsonlz4".into()])?;
-                    let data = reader_creator.create_slice_reader()?.data;
+            Opt::ImportLinks {
+                output_compression,
+                free_space,
+                in_out: command,
+            } => {
+                debug!("Executing: Import links command");
+                let reader_creator = command.get_reader_creator(Some(false), &["json".into()])?;
+                info!(
+                    "Reading exported tabs from {}",
+                    reader_creator.reader_info()
+                );
+                let export = reader_creator
+                    .deserialize_json_data::<session_store::link_export::SessionExport>()?;
+
+                let session = export.to_session_json();
+                // Ensure that the built session has all required fields:
+                let store =
+                    <session_store::FirefoxSessionStore as serde::Deserialize>::deserialize(
+                        &session,
+                    )
+                    .context("The built session wasn't valid sessionstore data")?;
+                let data = serde_json::to_vec(&session)
+                    .context("Failed to serialize the built session")?;
+
+                let mut encoder = output_compression
+                    .compress(&data, COMPRESSION_LIBRARY)
+                    .context("Failed to compress the built session.")?;
+
+                let writer_creator = command.get_writer_creator("sessionstore", "jsonlz4")?;
+                free_space.check(writer_creator.path(), encoder.compressed_len() as u64)?;
 
-                    info!("Decompressing data from {}", reader_creator.reader_info());
+                info!(
+                    "Writing a session with {} windows and {} tabs to {}",
+                    store.window_count(),
+                    store.tab_count(),
+                    writer_creator.output_info()
+                );
 
-                    compression::decompress(&data, COMPRESSION_LIBRARY)
+                io::copy(&mut encoder, &mut writer_creator.get_writer()?).with_context(|| {
+                    format!("Failed to write compressed data to {}.", writer_creator)
+                })?;
+                drop(encoder);
+
+                command.handle_output(writer_creator)?;
+            }
+            Opt::Decompress {
+                dry_run,
+                free_space,
+                preserve_mtime,
+                in_out: command,
+            } => {
+                debug!("Executing: Decompress command");
+                let reader_creator =
+                    command.get_reader_creator(Some(false), &["jsonlz4".into()])?;
+                let data = reader_creator.create_slice_reader()?.data;
+
+                if dry_run {
+                    info!(
+                        "Decompressing data from {} without writing any output",
+                        reader_creator.reader_info()
+                    );
+                    let decompressed_len = io_utils::decompress_data(&data)
                         .context("Failed to decompress data.")?
-                };
+                        .len();
+                    info!(
+                        "Decompressed {} bytes to {} bytes (compressed to {:.1}% of the original size)",
+                        data.len(),
+                        decompressed_len,
+                        data.len() as f64 * 100.0 / decompressed_len.max(1) as f64
+                    );
+                    info!("Finished");
+                    return Ok(());
+                }
 
                 let writer_creator = command.get_writer_creator("sessionstore", "js")?;
+                if free_space.min_free_space.is_some() {
+                    // The header stores the decompressed size:
+                    let size = compression::parse_header(&data)
+                        .context("Failed to parse the mozLz4 header of the input")?;
+                    free_space.check(writer_creator.path(), size.into())?;
+                }
 
                 info!(
-                    "Writing decompressed data to {}",
+                    "Decompressing data from {} and writing it to {}",
+                    reader_creator.reader_info(),
                     writer_creator.output_info()
                 );
 
-                writer_creator
-                    .get_writer()?
-                    .write_all(&decompressed)
-                    .with_context(|| {
-                        format!("Failed to write decompressed data to {}.", writer_creator)
-                    })?;
-                drop(decompressed);
+                io_utils::decompress_data_to_writer(&data, writer_creator.get_writer()?)
+                    .with_context(|| format!("Failed to decompress data to {}.", writer_creator))?;
+                drop(data);
+                preserve_mtime.apply(&reader_creator, &writer_creator)?;
 
                 command.handle_output(writer_creator)?;
             }
             Opt::RemoveMarkedTabs {
                 remove_options,
                 overwrite_input,
+                output_compression,
+                free_space,
+                json_format,
                 session,
             } => {
                 debug!("Executing: RemoveMarkedTabs command");
                 modify_sessionstore(
                     &session,
                     &overwrite_input,
+                    &output_compression,
+                    &free_space,
                     "removed-tabs",
                     |input, input_info| {
                         info!("Deserializing JSON data from {}", input_info.reader_info());
@@ -980,7 +2774,7 @@ pub fn run() -> Result<()> {
 
                         info!("Serializing modified data to JSON");
 
-                        serde_json::to_vec(&session).context(
+                        json_format.to_vec(&session).context(
                             "Failed to serialize modified sessionstore data to a JSON object.",
                         )
                     },
@@ -989,12 +2783,17 @@ pub fn run() -> Result<()> {
             Opt::RemoveTreeData {
                 remove_options,
                 overwrite_input,
+                output_compression,
+                free_space,
+                json_format,
                 session,
             } => {
                 debug!("Executing: RemoveTreeData command");
                 modify_sessionstore(
                     &session,
                     &overwrite_input,
+                    &output_compression,
+                    &free_space,
                     "removed-tree-data",
                     |input, input_info| {
                         info!("Deserializing JSON data from {}", input_info.reader_info());
@@ -1006,7 +2805,68 @@ pub fn run() -> Result<()> {
 
                         info!("Serializing modified data to JSON");
 
-                        serde_json::to_vec(&session).context(
+                        json_format.to_vec(&session).context(
+                            "Failed to serialize modified sessionstore data to a JSON object.",
+                        )
+                    },
+                )?;
+            }
+            Opt::StripCookies {
+                overwrite_input,
+                output_compression,
+                free_space,
+                json_format,
+                session,
+            } => {
+                debug!("Executing: StripCookies command");
+                modify_sessionstore(
+                    &session,
+                    &overwrite_input,
+                    &output_compression,
+                    &free_space,
+                    "without-cookies",
+                    |input, input_info| {
+                        info!("Deserializing JSON data from {}", input_info.reader_info());
+                        let mut session = deserialize_from_slice(&input).with_context(|| {
+                            format!("Failed to parse JSON from {}", input_info.reader_info())
+                        })?;
+
+                        strip_cookies(&mut session)?;
+
+                        info!("Serializing modified data to JSON");
+
+                        json_format.to_vec(&session).context(
+                            "Failed to serialize modified sessionstore data to a JSON object.",
+                        )
+                    },
+                )?;
+            }
+            Opt::Sanitize {
+                sanitize_options,
+                overwrite_input,
+                output_compression,
+                free_space,
+                json_format,
+                session,
+            } => {
+                debug!("Executing: Sanitize command");
+                modify_sessionstore(
+                    &session,
+                    &overwrite_input,
+                    &output_compression,
+                    &free_space,
+                    "sanitized",
+                    |input, input_info| {
+                        info!("Deserializing JSON data from {}", input_info.reader_info());
+                        let mut session = deserialize_from_slice(&input).with_context(|| {
+                            format!("Failed to parse JSON from {}", input_info.reader_info())
+                        })?;
+
+                        sanitize(&mut session, &sanitize_options)?;
+
+                        info!("Serializing modified data to JSON");
+
+                        json_format.to_vec(&session).context(
                             "Failed to serialize modified sessionstore data to a JSON object.",
                         )
                     },
@@ -1014,6 +2874,9 @@ pub fn run() -> Result<()> {
             }
             Opt::Modify {
                 overwrite_input,
+                output_compression,
+                free_space,
+                json_format,
                 session,
                 command,
                 stop_exit_code,
@@ -1038,6 +2901,8 @@ pub fn run() -> Result<()> {
                 let res = modify_sessionstore(
                     &session,
                     &overwrite_input,
+                    &output_compression,
+                    &free_space,
                     "modified",
                     |input, input_info| {
                         debug!(
@@ -1047,16 +2912,15 @@ pub fn run() -> Result<()> {
                         if !skip_json_verification {
                             let deserialize_start = Instant::now();
                             info!("Deserializing JSON data from {}", input_info.reader_info());
-                            drop(
-                                serde_json::from_slice::<serde_json::Value>(&input)
-                                    .map_err(|e| json_parse_error_context(e, &input))
-                                    .with_context(|| {
-                                        format!(
-                                            "Failed to parse JSON from {}",
-                                            input_info.reader_info()
-                                        )
-                                    })?,
-                            );
+                            // Only validate the JSON, don't build a value in memory:
+                            serde_json::from_slice::<IgnoredAny>(&input)
+                                .map_err(|e| json_parse_error_context(e, &input))
+                                .with_context(|| {
+                                    format!(
+                                        "Failed to parse JSON from {}",
+                                        input_info.reader_info()
+                                    )
+                                })?;
                             debug!(
                                 "Validation of original firefox sessionstore JSON data finished after {:?}",
                                 deserialize_start.elapsed()
@@ -1077,6 +2941,8 @@ pub fn run() -> Result<()> {
                             })?;
                         info!("Started command \"{}\"", first.to_string_lossy());
                         let after_spawn = Instant::now();
+                        // The modified data is usually about as large as the original:
+                        let expected_output_len = input.len();
 
                         let (read_res, write_res, command_writing_after) = thread::scope(|s| {
                             let (tx, rx) = std::sync::mpsc::sync_channel::<()>(1);
@@ -1091,8 +2957,12 @@ pub fn run() -> Result<()> {
                                     after_spawn.elapsed()
                                 );
                                 let read_start = Instant::now();
+                                // The output is buffered instead of being streamed into
+                                // the encoder since a mozLz4 file is a single lz4 block
+                                // whose header stores the uncompressed size, so the
+                                // encoder needs all of the data before it can start:
                                 let res = {
-                                    let mut data = Vec::new();
+                                    let mut data = Vec::with_capacity(expected_output_len);
                                     stdout
                                         .read_to_end(&mut data)
                                         .context("failed to read from command's stdout")
@@ -1152,18 +3022,30 @@ pub fn run() -> Result<()> {
                         let modified_data = read_res?;
                         write_res?;
 
-                        if skip_json_verification {
-                            Ok(modified_data)
-                        } else {
-                            info!("Validating modified sessionstore JSON from command");
+                        if json_format.is_specified() {
+                            info!("Reformatting modified sessionstore JSON from command");
                             let start = Instant::now();
-                            let json =  serde_json::from_slice::<serde_json::Value>(&modified_data)
+                            let json = serde_json::from_slice::<serde_json::Value>(&modified_data)
+                                .map_err(|e| json_parse_error_context(e, &modified_data))
                                 .context("The data written to the commands stdout could not be parsed as JSON")?;
-                            let data = serde_json::to_vec(&json)
+                            drop(modified_data);
+                            let data = json_format
+                                .to_vec(&json)
                                 .context("Failed to serialize modified sessionstore data");
+                            debug!("Reformatting finished after {:?}", start.elapsed());
+                            return data;
+                        }
+                        if !skip_json_verification {
+                            // Validate without building a value so that the
+                            // command's output is the only copy of the data:
+                            info!("Validating modified sessionstore JSON from command");
+                            let start = Instant::now();
+                            serde_json::from_slice::<IgnoredAny>(&modified_data)
+                                .map_err(|e| json_parse_error_context(e, &modified_data))
+                                .context("The data written to the commands stdout could not be parsed as JSON")?;
                             debug!("Validation finished after {:?}", start.elapsed());
-                            data
                         }
+                        Ok(modified_data)
                     },
                 );
                 debug!("Execution completed after {:?}", start.elapsed());
@@ -1175,46 +3057,39 @@ pub fn run() -> Result<()> {
                     res?;
                 }
             }
-            Opt::Domains(command) => {
+            Opt::Domains {
+                session: command,
+                registrable_domain,
+                public_suffix_list,
+                host_filter,
+            } => {
                 debug!("Executing: Domains command");
+                let domain_key: DomainKey = if registrable_domain {
+                    registrable_domain_key(public_suffix_list.as_deref())?
+                } else {
+                    Box::new(str::to_owned)
+                };
                 let reader_creator = command.get_reader_creator()?;
 
                 info!(
                     "Deserializing JSON data from {}",
                     reader_creator.reader_info()
-                );
-
-                let session =
-                    reader_creator.deserialize_json_data::<session_store::FirefoxSessionStore>()?;
-
-                // Code inspired by blog post at:
-                // https://blog.dend.ro/decoding-firefox-session-store-data/
-                let domains = {
-                    let mut domains = HashMap::<String, u32>::new();
-                    for window in &session.windows {
-                        for tab in &window.tabs {
-                            let tab = session_store::session_info::TabInfo::new(tab);
-                            match url::Url::parse(tab.url()) {
-                                Ok(url) => {
-                                    // skip about:blank, about:reader etc.
-                                    if let Some(host) = url.host_str() {
-                                        *domains.entry(host.to_string()).or_default() += 1;
-                                    }
-                                }
-                                Err(e) => {
-                                    error!(
-                                        "Failed to parse the tab URL {:?} because: {}",
-                                        tab.url(),
-                                        e
-                                    );
-                                }
-                            }
-                        }
-                    }
-                    let mut domains = domains.into_iter().collect::<Vec<_>>();
-                    domains.sort_unstable_by_key(|&(_, count): &(_, u32)| Reverse(count));
-                    domains
-                };
+                );
+
+                let session =
+                    reader_creator.deserialize_json_data::<session_store::FirefoxSessionStore>()?;
+
+                let tabs = session
+                    .windows
+                    .iter()
+                    .flat_map(|window| &window.tabs)
+                    .map(session_store::session_info::TabInfo::new)
+                    .collect::<Vec<_>>();
+                let urls = tabs
+                    .iter()
+                    .map(|tab| tab.url())
+                    .filter(|url| !host_filter.is_excluded(url));
+                let domains = count_domains(urls, domain_key);
 
                 let writer_creator = command.in_out_info.get_writer_creator_from_reader_creator(
                     &reader_creator,
@@ -1242,10 +3117,218 @@ pub fn run() -> Result<()> {
 
                 command.in_out_info.handle_output(writer_creator)?;
             }
+            Opt::Stats {
+                session: session_store_opt,
+                json,
+            } => {
+                debug!("Executing: Stats command");
+                let reader_creator = session_store_opt.get_reader_creator()?;
+
+                info!(
+                    "Deserializing JSON data from {}",
+                    reader_creator.reader_info()
+                );
+
+                let session =
+                    reader_creator.deserialize_json_data::<session_store::FirefoxSessionStore>()?;
+                let stats = session.stats();
+                drop(session);
+
+                let writer_creator = session_store_opt
+                    .in_out_info
+                    .get_writer_creator("session-stats", if json { "json" } else { "txt" })?;
+                {
+                    let mut writer = writer_creator.get_writer()?;
+
+                    if json {
+                        serde_json::to_writer_pretty(writer, &stats).with_context(|| {
+                            format!(
+                                "Failed to serialize session stats as JSON to {}",
+                                writer_creator
+                            )
+                        })?;
+                    } else {
+                        write!(writer, "{}", stats).with_context(|| {
+                            format!("Failed to write session stats to {}.", writer_creator)
+                        })?;
+                    }
+                }
+
+                session_store_opt
+                    .in_out_info
+                    .handle_output(writer_creator)?;
+            }
+            Opt::ShowTab {
+                session: session_store_opt,
+                window,
+                tab,
+                closed_window,
+                raw,
+            } => {
+                debug!("Executing: ShowTab command");
+                let reader_creator = session_store_opt.get_reader_creator()?;
+
+                info!(
+                    "Deserializing JSON data from {}",
+                    reader_creator.reader_info()
+                );
+
+                let session_data = reader_creator.deserialize_json_data::<serde_json::Value>()?;
+                let tab_data = find_tab_data(&session_data, window, tab, closed_window)?;
+
+                let writer_creator = session_store_opt
+                    .in_out_info
+                    .get_writer_creator("tab", "json")?;
+                {
+                    let mut writer = writer_creator.get_writer()?;
+                    let json = if raw {
+                        serde_json::to_vec(tab_data)
+                    } else {
+                        serde_json::to_vec_pretty(tab_data)
+                    }
+                    .context("Failed to serialize tab data as JSON")?;
+                    writer
+                        .write_all(&json)
+                        .and_then(|_| writer.write_all(b"\n"))
+                        .with_context(|| {
+                            format!("Failed to write tab data to {}", writer_creator)
+                        })?;
+                }
+
+                session_store_opt
+                    .in_out_info
+                    .handle_output(writer_creator)?;
+            }
+            Opt::Extensions {
+                session: session_store_opt,
+                json,
+            } => {
+                debug!("Executing: Extensions command");
+                let reader_creator = session_store_opt.get_reader_creator()?;
+
+                info!(
+                    "Deserializing JSON data from {}",
+                    reader_creator.reader_info()
+                );
+
+                let mut session = reader_creator.deserialize_json_data::<serde_json::Value>()?;
+                let extensions = extension_data_keys(&mut session)?;
+                drop(session);
+
+                let writer_creator = session_store_opt
+                    .in_out_info
+                    .get_writer_creator("session-extensions", if json { "json" } else { "txt" })?;
+                {
+                    let mut writer = writer_creator.get_writer()?;
+
+                    if json {
+                        #[derive(serde::Serialize)]
+                        struct JsonExtension<'a> {
+                            prefix: &'a str,
+                            name: Option<&'static str>,
+                            windows: u64,
+                            tabs: u64,
+                        }
+                        let json_extensions = extensions
+                            .iter()
+                            .map(|(prefix, count)| JsonExtension {
+                                prefix,
+                                name: known_extension_name(prefix),
+                                windows: count.windows,
+                                tabs: count.tabs,
+                            })
+                            .collect::<Vec<_>>();
+                        serde_json::to_writer_pretty(writer, &json_extensions).with_context(
+                            || {
+                                format!(
+                                    "Failed to serialize extension info as JSON to {}",
+                                    writer_creator
+                                )
+                            },
+                        )?;
+                    } else {
+                        try_!({
+                            for (prefix, count) in &extensions {
+                                write!(
+                                    writer,
+                                    "{:>7} tabs {:>5} windows   {}",
+                                    count.tabs, count.windows, prefix
+                                )?;
+                                if let Some(name) = known_extension_name(prefix) {
+                                    write!(writer, " ({name})")?;
+                                }
+                                writeln!(writer)?;
+                            }
+                        })
+                        .with_context(|| {
+                            format!(
+                                "Failed to write extension information to {}.",
+                                writer_creator
+                            )
+                        })?;
+                    }
+                }
+
+                session_store_opt
+                    .in_out_info
+                    .handle_output(writer_creator)?;
+            }
+            Opt::Diff {
+                original,
+                session: session_store_opt,
+                json,
+            } => {
+                debug!("Executing: Diff command");
+                let reader_creator = session_store_opt.get_reader_creator()?;
+                let original_reader = InputReader {
+                    state: io_utils::InputReaderState::InputPath(original),
+                    is_compressed: None,
+                };
+
+                info!(
+                    "Deserializing JSON data from {} and {}",
+                    original_reader.reader_info(),
+                    reader_creator.reader_info()
+                );
+
+                let original = original_reader
+                    .deserialize_json_data::<session_store::FirefoxSessionStore>()?;
+                let session =
+                    reader_creator.deserialize_json_data::<session_store::FirefoxSessionStore>()?;
+
+                let diff = session_store::diff(&original, &session);
+                drop(original);
+                drop(session);
+
+                let writer_creator = session_store_opt
+                    .in_out_info
+                    .get_writer_creator("session-diff", if json { "json" } else { "txt" })?;
+                {
+                    let mut writer = writer_creator.get_writer()?;
+
+                    if json {
+                        serde_json::to_writer_pretty(writer, &diff).with_context(|| {
+                            format!(
+                                "Failed to serialize session diff as JSON to {}",
+                                writer_creator
+                            )
+                        })?;
+                    } else {
+                        write!(writer, "{}", diff).with_context(|| {
+                            format!("Failed to write session diff to {}.", writer_creator)
+                        })?;
+                    }
+                }
+
+                session_store_opt
+                    .in_out_info
+                    .handle_output(writer_creator)?;
+            }
             Opt::GetGroups {
                 session: session_store_opt,
                 tab_group_options,
                 json,
+                include_window_geometry,
             } => {
                 debug!("Executing: GetGroups command");
                 let reader_creator = session_store_opt.get_reader_creator()?;
@@ -1258,13 +3341,7 @@ pub fn run() -> Result<()> {
                 let session =
                     reader_creator.deserialize_json_data::<session_store::FirefoxSessionStore>()?;
 
-                let groups = session_store::session_info::get_groups_from_session(
-                    &session,
-                    !tab_group_options.only_closed_windows,
-                    tab_group_options.closed_windows || tab_group_options.only_closed_windows,
-                    !tab_group_options.no_sorting,
-                )
-                .collect::<Vec<_>>();
+                let groups = tab_group_options.get_groups(&session).collect::<Vec<_>>();
 
                 let writer_creator = session_store_opt
                     .in_out_info
@@ -1278,6 +3355,8 @@ pub fn run() -> Result<()> {
                             name: &'a str,
                             tab_count: u64,
                             is_closed: bool,
+                            #[serde(skip_serializing_if = "Option::is_none")]
+                            geometry: Option<session_store::session_info::WindowGeometry<'a>>,
                         }
                         let json_groups = groups
                             .iter()
@@ -1285,6 +3364,10 @@ pub fn run() -> Result<()> {
                                 name: group.name(),
                                 tab_count: u64::try_from(group.tabs().len()).unwrap(),
                                 is_closed: group.is_closed(),
+                                geometry: group
+                                    .window()
+                                    .filter(|_| include_window_geometry)
+                                    .map(|window| window.geometry()),
                             })
                             .collect::<Vec<_>>();
                         serde_json::to_writer_pretty(writer, &json_groups).with_context(|| {
@@ -1295,14 +3378,22 @@ pub fn run() -> Result<()> {
                         })?;
                     } else {
                         try_!({
-                            let mut is_closed = false;
+                            let mut is_closed = tab_group_options.closed_first;
                             for group in groups {
                                 if is_closed != group.is_closed() {
-                                    // Closed windows come after open ones.
+                                    // Empty line between open and closed windows.
                                     writeln!(writer)?;
-                                    is_closed = true;
+                                    is_closed = group.is_closed();
+                                }
+                                match group.window().filter(|_| include_window_geometry) {
+                                    Some(window) => writeln!(
+                                        writer,
+                                        "{} [{}]",
+                                        group.name(),
+                                        window.geometry()
+                                    )?,
+                                    None => writeln!(writer, "{}", group.name())?,
                                 }
-                                writeln!(writer, "{}", group.name())?;
                             }
                         })
                         .with_context(|| {
@@ -1321,62 +3412,32 @@ pub fn run() -> Result<()> {
             }
             Opt::TabsToLinks(command) => {
                 debug!("Executing: TabsToLinks command");
-                let options = command.parse_options()?;
+
+                #[cfg(feature = "glob")]
+                if let Some(pattern) = &command.input_glob {
+                    tabs_to_links_for_glob(&command, pattern)?;
+                    info!("Finished");
+                    return Ok(());
+                }
+                if let Some(list_path) = &command.input_file_list {
+                    tabs_to_links_for_file_list(&command, list_path)?;
+                    info!("Finished");
+                    return Ok(());
+                }
 
                 let session_store_opt = &command.session_store_opt;
                 let reader_creator = session_store_opt.get_reader_creator()?;
-
-                info!(
-                    "Deserializing JSON data from {}",
-                    reader_creator.reader_info()
-                );
-
-                let session =
-                    reader_creator.deserialize_json_data::<session_store::FirefoxSessionStore>()?;
-
-                let mut writer_creator = session_store_opt
+                let writer_creator = session_store_opt
                     .in_out_info
-                    .get_writer_creator("Links", options.file_extension())?;
-
-                let writer_info = writer_creator.output_info().to_string();
+                    .get_writer_creator("Links", command.parse_options()?.file_extension())?;
 
-                info!("Writing links to {}", writer_info);
-
-                // Select windows/groups:
-                let groups = session_store::session_info::get_groups_from_session(
-                    &session,
-                    !command.tab_group_options.only_closed_windows,
-                    command.tab_group_options.closed_windows
-                        || command.tab_group_options.only_closed_windows,
-                    !command.tab_group_options.no_sorting,
-                );
-                let groups = if !command.tab_group_indexes.is_empty()
-                    || !command.tab_group_names.is_empty()
-                {
-                    groups
-                        .enumerate()
-                        .filter(|(index, group)| {
-                            command.tab_group_indexes.contains(&(*index as u64))
-                                || command
-                                    .tab_group_names
-                                    .iter()
-                                    .any(|name| name == group.name())
-                        })
-                        .map(|(_, group)| group)
-                        .collect::<Vec<_>>()
-                } else {
-                    groups.collect::<Vec<_>>()
-                };
-
-                tabs_to_links(&groups, options, &mut writer_creator)
-                    .with_context(|| format!("Failed to write links to {}.", writer_info))?;
-                drop(session);
-
-                session_store_opt
-                    .in_out_info
-                    .handle_output(writer_creator)?;
+                tabs_to_links_for_input(&command, &reader_creator, writer_creator)?;
+            }
+            Opt::CompareProfiles { common: _, json } => {
+                debug!("Executing: CompareProfiles command");
+                compare_profiles(json)?;
             }
-            Opt::TabsToLinksFormats { .. } => {
+            Opt::TabsToLinksFormats { .. } | Opt::Completions { .. } => {
                 unreachable!("We handled this earlier");
             }
         }
@@ -1449,3 +3510,512 @@ fn init_logger(default_level: Option<log::Level>) {
 
     builder.init();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// JSON for a loaded tab with a single history entry.
+    fn tab_json(url: &str, title: &str) -> serde_json::Value {
+        serde_json::json!({
+            "entries": [{ "url": url, "title": title }],
+            "lastAccessed": 0,
+            "hidden": false,
+            "attributes": {},
+            "userContextId": 0,
+            "index": 1,
+        })
+    }
+
+    /// JSON for a window where the first tab is selected.
+    fn window_json(tabs: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({
+            "tabs": tabs,
+            "selected": 1,
+            "width": 800,
+            "height": 600,
+            "screenX": 0,
+            "screenY": 0,
+            "sizemode": "normal",
+        })
+    }
+
+    /// JSON for a sessionstore file with some open and closed windows.
+    fn session_json(
+        windows: Vec<serde_json::Value>,
+        closed_windows: Vec<serde_json::Value>,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "version": ["sessionrestore", 1],
+            "windows": windows,
+            "_closedWindows": closed_windows,
+            "selectedWindow": 1,
+            "session": { "lastUpdate": 0, "startTime": 0, "recentCrashes": 0 },
+            "global": {},
+        })
+    }
+
+    #[test]
+    fn domains_with_equal_counts_are_sorted_by_host() {
+        let domains = count_domains(
+            [
+                "https://www.wikipedia.org/",
+                "https://c.example.com/",
+                "https://a.example.com/page",
+                "https://www.wikipedia.org/wiki/Firefox",
+                "about:newtab",
+                "https://b.example.com/",
+                "https://a.example.com/other",
+                "https://b.example.com/other",
+            ],
+            str::to_owned,
+        );
+        assert_eq!(
+            domains,
+            [
+                ("a.example.com".to_owned(), 2),
+                ("b.example.com".to_owned(), 2),
+                ("www.wikipedia.org".to_owned(), 2),
+                ("c.example.com".to_owned(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn extension_key_prefixes() {
+        assert_eq!(
+            extension_key_prefix("extension:treestyletab@piro.sakura.ne.jp:insert-after"),
+            "extension:treestyletab@piro.sakura.ne.jp"
+        );
+        assert_eq!(
+            extension_key_prefix("extension:{3c078156-979c-498b-8990-85f7987dd929}:groups"),
+            "extension:{3c078156-979c-498b-8990-85f7987dd929}"
+        );
+        assert_eq!(extension_key_prefix("treestyletab_parent"), "treestyletab");
+        assert_eq!(extension_key_prefix("tabview-group"), "tabview");
+        assert_eq!(
+            extension_key_prefix("extension:no-name"),
+            "extension:no-name"
+        );
+        assert_eq!(extension_key_prefix("plain"), "plain");
+    }
+
+    #[test]
+    fn selected_window_is_updated_when_windows_are_removed() {
+        // Window before the selected one was removed:
+        assert_eq!(selected_index_after_removal(3, &[false, true, false]), 2);
+        // Window after the selected one was removed:
+        assert_eq!(selected_index_after_removal(1, &[false, true, false]), 1);
+        // Selected window was removed so the next window is selected:
+        assert_eq!(selected_index_after_removal(2, &[false, true, false]), 2);
+        assert_eq!(selected_index_after_removal(1, &[true, true, false]), 1);
+        // Selected window was the last one so the new last window is selected:
+        assert_eq!(selected_index_after_removal(3, &[false, false, true]), 2);
+        assert_eq!(selected_index_after_removal(3, &[false, true, true]), 1);
+
+        // The second window is emptied and removed while the third window is
+        // selected:
+        let mut marked = tab_json("https://example.com/b", "b");
+        marked["extData"] = serde_json::json!({
+            "extension:{dab33964-ee66-494e-a816-b064ca5518c4}:marked": "true",
+        });
+        let mut session_data = session_json(
+            vec![
+                window_json(vec![tab_json("https://example.com/a", "a")]),
+                window_json(vec![marked]),
+                window_json(vec![tab_json("https://example.com/c", "c")]),
+            ],
+            vec![],
+        );
+        session_data["selectedWindow"] = 3.into();
+        remove_marked_tabs(&mut session_data, &RemoveMarkedTabsOptions::default()).unwrap();
+
+        let urls = session_data["windows"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|window| window["tabs"][0]["entries"][0]["url"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(urls, ["https://example.com/a", "https://example.com/c"]);
+        assert_eq!(session_data["selectedWindow"], 2);
+    }
+
+    #[test]
+    fn describe_tabs_to_links_options() {
+        let command = <Opt as clap::CommandFactory>::command();
+        let options = describe_options(command.find_subcommand("tabs-to-links").unwrap());
+        let option = |id: &str| options.iter().find(|option| option.id == id).unwrap();
+
+        let format = option("format");
+        assert_eq!(format.kind, "value");
+        assert_eq!(format.long.as_deref(), Some("format"));
+        assert!(format.help.is_some());
+
+        let encoding = option("encoding");
+        assert_eq!(encoding.possible_values, ["utf8", "utf16le"]);
+        assert_eq!(encoding.default_values, ["utf8"]);
+
+        assert_eq!(option("flatten").kind, "flag");
+        assert!(option("tree_data").multiple);
+        assert!(!options.iter().any(|option| option.id == "help"));
+    }
+
+    #[test]
+    fn input_file_list_skips_comments_and_blank_lines() {
+        let list = "\u{FEFF}# Backups\nbackups/a.jsonlz4\n\n  # Older\n  backups/b c.jsonlz4  \r\nrecovery.jsonlz4";
+        assert_eq!(
+            parse_input_file_list(list).collect::<Vec<_>>(),
+            [
+                "backups/a.jsonlz4",
+                "backups/b c.jsonlz4",
+                "recovery.jsonlz4"
+            ]
+        );
+    }
+
+    #[test]
+    fn pinned_tabs_are_moved_to_their_own_group() {
+        use session_store::session_info::OwnedWindowData;
+
+        let window = |tabs: &[(&str, bool)]| {
+            let tabs = tabs
+                .iter()
+                .map(|&(title, pinned)| {
+                    let mut tab = tab_json("https://example.com/", title);
+                    tab["pinned"] = pinned.into();
+                    tab
+                })
+                .collect();
+            OwnedWindowData::from_value(&window_json(tabs), false).unwrap()
+        };
+        let first = window(&[("a", true), ("b", false)]);
+        let second = window(&[("c", true)]);
+        let mut groups = vec![
+            first.info().as_group("Window 1"),
+            second.info().as_group("Window 2"),
+        ];
+        to_links::move_pinned_tabs_to_group(&mut groups, "Pinned");
+        let groups = groups
+            .iter()
+            .map(|group| {
+                let titles = group
+                    .tabs()
+                    .iter()
+                    .map(|tab| tab.title())
+                    .collect::<Vec<_>>();
+                (group.name(), titles)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            [("Pinned", vec!["a", "c"]), ("Window 1", vec!["b"])]
+        );
+    }
+
+    #[test]
+    fn completion_scripts_include_subcommands() {
+        for shell in [
+            clap_complete::Shell::Bash,
+            clap_complete::Shell::Zsh,
+            clap_complete::Shell::Fish,
+            clap_complete::Shell::PowerShell,
+        ] {
+            let script = String::from_utf8(completion_script(shell)).unwrap();
+            assert!(script.contains("firefox-session-data"), "{shell}");
+            assert!(script.contains("tabs-to-links"), "{shell}");
+            assert!(script.contains("completions"), "{shell}");
+        }
+    }
+
+    #[cfg(feature = "publicsuffix")]
+    #[test]
+    fn registrable_domains_are_counted_together() {
+        let list = "// ===BEGIN ICANN DOMAINS===\ncom\nuk\nco.uk\n// ===END ICANN DOMAINS===\n"
+            .parse::<publicsuffix::List>()
+            .unwrap();
+        let domains = count_domains(
+            [
+                "https://a.example.com/",
+                "https://b.example.com/",
+                "https://www.example.co.uk/",
+                "http://127.0.0.1:8080/",
+            ],
+            |host| registrable_domain(&list, host),
+        );
+        assert_eq!(
+            domains,
+            [
+                ("example.com".to_owned(), 2),
+                ("127.0.0.1".to_owned(), 1),
+                ("example.co.uk".to_owned(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn session_facade_round_trips_data() {
+        let tab = |url| tab_json(url, url);
+        let window = window_json(vec![
+            tab("https://example.com/"),
+            tab("https://example.org/"),
+        ]);
+        let mut data = session_json(vec![window], vec![]);
+        data["unknownField"] = serde_json::json!([1, 2, 3]);
+
+        let session = session::Session::from_bytes(&serde_json::to_vec(&data).unwrap()).unwrap();
+        assert!(!session.was_compressed());
+        assert_eq!(session.store().tab_count(), 2);
+
+        let group_options = to_links::TabGroupOptions::default();
+        assert_eq!(session.groups(&group_options).len(), 1);
+        let links = session
+            .to_links(&group_options, &Default::default())
+            .unwrap();
+        let links = String::from_utf8(links).unwrap();
+        assert!(links.contains("https://example.com/"));
+        assert!(links.contains("https://example.org/"));
+
+        let mut compressed = Vec::new();
+        session.save(&mut compressed, true).unwrap();
+        let loaded = session::Session::from_bytes(&compressed).unwrap();
+        assert!(loaded.was_compressed());
+        assert_eq!(loaded.data(), &data);
+    }
+
+    #[test]
+    fn modified_sessions_keep_key_order() {
+        let json = r#"{"version":["sessionrestore",1],"windows":[],"selectedWindow":0,"session":{"startTime":0,"recentCrashes":0,"lastUpdate":0},"global":{},"zUnknown":{"b":1,"a":2},"_closedWindows":[]}"#;
+
+        let mut saved = Vec::new();
+        session::Session::from_bytes(json.as_bytes())
+            .unwrap()
+            .save(&mut saved, false)
+            .unwrap();
+        assert_eq!(String::from_utf8(saved).unwrap(), json);
+
+        // Removing keys while sanitizing mustn't reorder the remaining ones:
+        let tab = |removed: &str| {
+            format!(
+                r#"{{"entries":[],"zUnknown":1{removed},"attributes":{{}},"index":1,"aUnknown":2}}"#
+            )
+        };
+        let mut session_data: serde_json::Value = serde_json::from_str(&format!(
+            r#"{{"windows":[{{"tabs":[{}]}}]}}"#,
+            tab(r#","formdata":{"id":{}},"scroll":{"scroll":"0,10"}"#)
+        ))
+        .unwrap();
+        sanitize(
+            &mut session_data,
+            &SanitizeOptions {
+                form_data: true,
+                scroll: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            session_data.to_string(),
+            format!(r#"{{"windows":[{{"tabs":[{}]}}]}}"#, tab(""))
+        );
+
+        // Tree data is removed from between other extension data:
+        let ext_data = |tree_data: &str| {
+            format!(
+                r#"{{"windows":[{{"tabs":[{{"entries":[],"zUnknown":1,"extData":{{"zKey":"1"{tree_data},"aKey":"2"}},"index":1,"aUnknown":2}}]}}]}}"#
+            )
+        };
+        let mut session_data: serde_json::Value = serde_json::from_str(&ext_data(
+            r#","treestyletab_id":"tab-1","mKey":"3","extension:treestyletab@piro.sakura.ne.jp:tree-structure":"[]""#,
+        ))
+        .unwrap();
+        remove_tree_data(
+            &mut session_data,
+            &RemoveTreeDataOptions {
+                all: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(session_data.to_string(), ext_data(r#","mKey":"3""#));
+    }
+
+    #[test]
+    fn window_limit_counts_open_then_closed_windows() {
+        let window = |url| window_json(vec![tab_json(url, url)]);
+        let session = session::Session::from_value(session_json(
+            vec![window("https://a.example/"), window("https://b.example/")],
+            vec![window("https://c.example/"), window("https://d.example/")],
+        ))
+        .unwrap();
+
+        let urls = |options: to_links::TabGroupOptions| {
+            session
+                .groups(&options)
+                .iter()
+                .map(|group| group.tabs()[0].url().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            urls(to_links::TabGroupOptions {
+                limit_windows: Some(3),
+                closed_windows: true,
+                ..Default::default()
+            }),
+            [
+                "https://a.example/",
+                "https://b.example/",
+                "https://c.example/"
+            ]
+        );
+        assert_eq!(
+            urls(to_links::TabGroupOptions {
+                limit_windows: Some(1),
+                closed_windows: true,
+                closed_first: true,
+                ..Default::default()
+            }),
+            ["https://c.example/"]
+        );
+    }
+
+    #[test]
+    fn find_tab_data_reports_available_counts() {
+        let session = serde_json::json!({
+            "windows": [
+                { "tabs": [{ "index": 1 }, { "index": 2 }] },
+                { "tabs": [] },
+            ],
+            "_closedWindows": [{ "tabs": [{ "index": 3 }] }],
+        });
+        assert_eq!(
+            find_tab_data(&session, 0, 1, false).unwrap(),
+            &serde_json::json!({ "index": 2 })
+        );
+        assert_eq!(
+            find_tab_data(&session, 0, 0, true).unwrap(),
+            &serde_json::json!({ "index": 3 })
+        );
+        assert_eq!(
+            find_tab_data(&session, 2, 0, false)
+                .unwrap_err()
+                .to_string(),
+            "There is no window at index 2 since the session has 2 open windows"
+        );
+        assert_eq!(
+            find_tab_data(&session, 1, 0, false)
+                .unwrap_err()
+                .to_string(),
+            "There is no tab at index 0 since the window at index 1 has 0 tabs"
+        );
+    }
+
+    #[test]
+    fn group_output_paths_are_unique() {
+        let dir = Path::new("no such dir");
+        let names = ["Docs", "Docs", "Docs?", ""];
+        for overwrite in [false, true] {
+            let paths = group_output_paths(dir, "tabs", "md", names, overwrite).unwrap();
+            let file_names = paths
+                .iter()
+                .map(|path| path.file_name().unwrap().to_str().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                file_names,
+                [
+                    "tabs - Docs.md",
+                    "tabs - Docs (1).md",
+                    "tabs - Docs_.md",
+                    "tabs - Window 4.md"
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn strip_cookies_keeps_other_data() {
+        let cookie = |name: &str| serde_json::json!({ "host": "example.com", "name": name });
+        let window = |cookies: Vec<serde_json::Value>| serde_json::json!({ "tabs": [], "selected": 1, "cookies": cookies, "zUnknown": 1 });
+        let mut session_data = serde_json::json!({
+            "cookies": [cookie("a"), cookie("b")],
+            "windows": [window(vec![cookie("c")]), { "tabs": [] }],
+            "_closedWindows": [window(vec![cookie("d"), cookie("e")])],
+            "global": { "cookies": "kept" },
+        });
+
+        assert_eq!(strip_cookies(&mut session_data).unwrap(), 5);
+        assert_eq!(
+            session_data,
+            serde_json::json!({
+                "cookies": [],
+                "windows": [window(vec![]), { "tabs": [] }],
+                "_closedWindows": [window(vec![])],
+                "global": { "cookies": "kept" },
+            })
+        );
+        // Nothing left to remove:
+        assert_eq!(strip_cookies(&mut session_data).unwrap(), 0);
+    }
+
+    #[test]
+    fn sanitize_removes_selected_data() {
+        let entry = |url: &str| serde_json::json!({ "url": url, "title": url });
+        let tab = |index: i64, extra: serde_json::Value| {
+            let mut tab = serde_json::json!({
+                "entries": [entry("https://a/"), entry("https://b/"), entry("https://c/")],
+                "index": index,
+            });
+            tab.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            tab
+        };
+        let mut session_data = serde_json::json!({
+            "windows": [{
+                "tabs": [
+                    tab(2, serde_json::json!({
+                        "formdata": { "id": {} },
+                        "scroll": { "scroll": "0,10" },
+                        "userTypedValue": "abc",
+                    })),
+                    // Out of range indexes keep the last entry:
+                    tab(7, serde_json::json!({})),
+                ],
+                "cookies": [{ "host": "example.com" }],
+            }],
+            "_closedWindows": [{ "tabs": [], "cookies": [{ "host": "example.com" }] }],
+        });
+        sanitize(
+            &mut session_data,
+            &SanitizeOptions {
+                cookies: true,
+                history: true,
+                form_data: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let sanitized = |url: &str, extra: serde_json::Value| {
+            let mut tab = serde_json::json!({ "entries": [entry(url)], "index": 1 });
+            tab.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            tab
+        };
+        let expected = serde_json::json!({
+            "windows": [{
+                "tabs": [
+                    sanitized("https://b/", serde_json::json!({
+                        "scroll": { "scroll": "0,10" },
+                        "userTypedValue": "abc",
+                    })),
+                    sanitized("https://c/", serde_json::json!({})),
+                ],
+                "cookies": [],
+            }],
+            "_closedWindows": [{ "tabs": [], "cookies": [] }],
+        });
+        // Compare the JSON text so that the key order is checked as well:
+        assert_eq!(session_data.to_string(), expected.to_string());
+    }
+}
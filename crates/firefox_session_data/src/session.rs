@@ -0,0 +1,125 @@
+//! A high-level API for working with a loaded sessionstore file without going
+//! through the command line interface.
+
+use crate::io_utils::{self, deserialize_from_slice};
+use crate::to_links::TabGroupOptions;
+use crate::{compression, session_store, RemoveMarkedTabsOptions, RemoveTreeDataOptions, Result};
+use eyre::WrapErr;
+use serde::Deserialize;
+use session_store::session_info::TabGroup;
+use session_store::to_links::ToLinksOptions;
+use session_store::FirefoxSessionStore;
+
+use std::io::{self, Write};
+
+/// A sessionstore file that has been loaded into memory.
+///
+/// The raw JSON data is kept so that modifications preserve any data that
+/// [`FirefoxSessionStore`] doesn't know about. The typed view of the data is
+/// updated after every modification.
+#[derive(Debug, Clone)]
+pub struct Session {
+    data: serde_json::Value,
+    store: FirefoxSessionStore,
+    was_compressed: bool,
+}
+impl Session {
+    /// Load a sessionstore file's content. The data is decompressed first if it
+    /// starts with the `mozLz4` magic header.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let was_compressed = data.starts_with(compression::MAGIC_HEADER);
+        let decompressed;
+        let json = if was_compressed {
+            decompressed =
+                io_utils::decompress_data(data).context("Failed to decompress session data")?;
+            &decompressed[..]
+        } else {
+            data
+        };
+        let mut session = Self::from_value(deserialize_from_slice(json)?)?;
+        session.was_compressed = was_compressed;
+        Ok(session)
+    }
+    /// Use JSON data that has already been parsed.
+    pub fn from_value(data: serde_json::Value) -> Result<Self> {
+        let store = FirefoxSessionStore::deserialize(&data)
+            .context("Failed to parse JSON as sessionstore data")?;
+        Ok(Self {
+            data,
+            store,
+            was_compressed: false,
+        })
+    }
+
+    /// The parsed session data.
+    pub fn store(&self) -> &FirefoxSessionStore {
+        &self.store
+    }
+    /// The raw JSON data, including anything that isn't part of
+    /// [`FirefoxSessionStore`].
+    pub fn data(&self) -> &serde_json::Value {
+        &self.data
+    }
+    pub fn into_data(self) -> serde_json::Value {
+        self.data
+    }
+    /// `true` if the session was loaded from compressed data.
+    pub fn was_compressed(&self) -> bool {
+        self.was_compressed
+    }
+
+    /// Get the windows/groups that were selected by `options`.
+    pub fn groups(&self, options: &TabGroupOptions) -> Vec<TabGroup<'_>> {
+        options.get_groups(&self.store).collect()
+    }
+
+    /// Remove marked tabs, see [`crate::remove_marked_tabs`].
+    pub fn remove_marked_tabs(&mut self, options: &RemoveMarkedTabsOptions) -> Result<()> {
+        crate::remove_marked_tabs(&mut self.data, options)?;
+        self.reload()
+    }
+    /// Remove tree data for some extensions, see [`crate::remove_tree_data`].
+    pub fn remove_tree_data(&mut self, options: &RemoveTreeDataOptions) -> Result<()> {
+        crate::remove_tree_data(&mut self.data, options)?;
+        self.reload()
+    }
+    /// Parse the raw JSON data again after it was modified.
+    fn reload(&mut self) -> Result<()> {
+        self.store = FirefoxSessionStore::deserialize(&self.data)
+            .context("Modified JSON is no longer valid sessionstore data")?;
+        Ok(())
+    }
+
+    /// Write links to the tabs in the groups selected by `group_options`.
+    ///
+    /// PDF output isn't supported here since it requires an external
+    /// conversion, use [`crate::tabs_to_links`] for that.
+    pub fn to_links(
+        &self,
+        group_options: &TabGroupOptions,
+        link_options: &ToLinksOptions<'_>,
+    ) -> Result<Vec<u8>> {
+        let mut links = Vec::new();
+        link_options
+            .write_links(&self.groups(group_options), &mut links)
+            .context("Failed to write links")?;
+        Ok(links)
+    }
+
+    /// Write the session as JSON, optionally compressed the same way Firefox
+    /// compresses its sessionstore files.
+    pub fn save(&self, mut writer: impl Write, compressed: bool) -> Result<()> {
+        let json = serde_json::to_vec(&self.data).context("Failed to serialize session data")?;
+        if compressed {
+            let mut encoder =
+                compression::Encoder::compress(&json, None, crate::COMPRESSION_LIBRARY)
+                    .context("Failed to compress session data")?;
+            io::copy(&mut encoder, &mut writer).context("Failed to write session data")?;
+        } else {
+            writer
+                .write_all(&json)
+                .context("Failed to write session data")?;
+        }
+        writer.flush().context("Failed to write session data")
+    }
+}
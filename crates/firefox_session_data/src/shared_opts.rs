@@ -4,7 +4,7 @@ use std::{
     borrow::Cow,
     borrow::{Borrow, BorrowMut},
     convert::AsRef,
-    io,
+    fs, io,
     path::{Path, PathBuf},
 };
 
@@ -13,8 +13,9 @@ use color_eyre::Help;
 use eyre::{bail, ContextCompat, WrapErr};
 
 use crate::{
-    find, init_logger,
+    compression, find, init_logger,
     io_utils::{InputReader, InputReaderState, OutputWriter},
+    session_store::session_info::TabGroup,
     try_, verbosity_level, Result,
 };
 
@@ -89,11 +90,13 @@ impl SessionstoreOpt {
 #[clap(rename_all = "kebab-case")]
 pub struct CompressInfoOpt {
     /// Indicate that the input file is compressed. If the file extension ends
-    /// with "lz4" this is automatically detected.
+    /// with "lz4" or the data starts with the "mozLz4" header this is
+    /// automatically detected.
     #[clap(short, long, help_heading = "INPUT")]
     pub compressed: bool,
 
-    /// Indicates that the input file is uncompressed.
+    /// Indicates that the input file is uncompressed. Disables detecting
+    /// compression from the file's content.
     #[clap(short, long, conflicts_with = "compressed", help_heading = "INPUT")]
     pub uncompressed: bool,
 }
@@ -133,6 +136,284 @@ pub struct OverwriteInputOpt {
     pub swap: bool,
 }
 
+/// Option to keep the input file's metadata for the output file.
+#[derive(Debug, Args, Clone, Default)]
+#[clap(rename_all = "kebab-case")]
+pub struct PreserveMtimeOpt {
+    #[clap(long, conflicts_with = "stdin", help_heading = "OUTPUT")]
+    /// Set the output file's modification time to the input file's
+    /// modification time. Useful when archiving dated backups. Ignored when
+    /// writing to stdout.
+    pub preserve_mtime: bool,
+}
+impl PreserveMtimeOpt {
+    /// Copy the input file's modification time to the output file if that was
+    /// requested. Must be called after the output file has been written.
+    pub fn apply(&self, reader_creator: &InputReader, writer_creator: &OutputWriter) -> Result<()> {
+        if !self.preserve_mtime {
+            return Ok(());
+        }
+        let (Some(input), Some(output)) = (reader_creator.path(), writer_creator.path()) else {
+            return Ok(());
+        };
+        let modified = std::fs::metadata(input)
+            .and_then(|meta| meta.modified())
+            .with_context(|| {
+                format!(
+                    "Failed to get the modification time of the input file at \"{}\"",
+                    input.display()
+                )
+            })?;
+        std::fs::File::options()
+            .write(true)
+            .open(output)
+            .and_then(|file| file.set_modified(modified))
+            .with_context(|| {
+                format!(
+                    "Failed to set the modification time of the output file at \"{}\"",
+                    output.display()
+                )
+            })?;
+        debug!("Copied the input file's modification time to the output file");
+        Ok(())
+    }
+}
+
+/// Option to check that there is enough free disk space before writing output.
+#[derive(Debug, Args, Clone, Default)]
+#[clap(rename_all = "kebab-case")]
+pub struct FreeSpaceOpt {
+    #[clap(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_byte_size,
+        help_heading = "OUTPUT"
+    )]
+    /// Check that the output's disk has room for the output and will still
+    /// have at least this much free space afterwards before anything is
+    /// written. Accepts sizes such as "0", "500M" or "2G". Ignored when
+    /// writing to stdout.
+    ///
+    /// The size of a file that is overwritten isn't taken into account.
+    pub min_free_space: Option<u64>,
+}
+impl FreeSpaceOpt {
+    /// Fail if writing `size` bytes to a file at `output` would leave less free
+    /// space than was requested. Does nothing if `output` is `None`, for
+    /// example when writing to stdout.
+    pub fn check(&self, output: Option<&Path>, size: u64) -> Result<()> {
+        let (Some(min_free_space), Some(output)) = (self.min_free_space, output) else {
+            return Ok(());
+        };
+        let dir = output
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let available = available_space(dir)?;
+        let required = size.saturating_add(min_free_space);
+        if available < required {
+            bail!(
+                "Not enough free disk space to write {} bytes to \"{}\": {} bytes are available but {} bytes are required",
+                size,
+                output.display(),
+                available,
+                required
+            );
+        }
+        debug!(
+            "There are {} bytes of free disk space for {} bytes of output",
+            available, size
+        );
+        Ok(())
+    }
+}
+
+#[cfg(feature = "fs2")]
+fn available_space(dir: &Path) -> Result<u64> {
+    fs2::available_space(dir).with_context(|| {
+        format!(
+            "Failed to get the free disk space for \"{}\"",
+            dir.display()
+        )
+    })
+}
+#[cfg(not(feature = "fs2"))]
+fn available_space(_dir: &Path) -> Result<u64> {
+    bail!("Checking free disk space isn't supported since this program was built without the \"fs2\" feature")
+}
+
+/// Parse a size in bytes such as "1024", "500K", "500MB" or "2GiB". Units are
+/// powers of 1024.
+pub fn parse_byte_size(text: &str) -> Result<u64> {
+    let text = text.trim();
+    let unit_start = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(unit_start);
+    let value = value
+        .parse::<u64>()
+        .with_context(|| format!("Invalid number in size \"{}\"", text))?;
+    let shift = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 10,
+        "m" | "mb" | "mib" => 20,
+        "g" | "gb" | "gib" => 30,
+        "t" | "tb" | "tib" => 40,
+        _ => bail!(
+            "Unknown unit in size \"{}\", expected one of \"K\", \"M\", \"G\" or \"T\"",
+            text
+        ),
+    };
+    value
+        .checked_mul(1 << shift)
+        .with_context(|| format!("The size \"{}\" is too large", text))
+}
+
+/// Options that affect how re-serialized JSON data is formatted.
+#[derive(Debug, Args, Clone, Default)]
+#[clap(rename_all = "kebab-case")]
+pub struct JsonFormatOpt {
+    #[clap(long, help_heading = "OUTPUT")]
+    /// Pretty print the output JSON. Firefox can read either form but pretty
+    /// printed data is much larger, even when compressed.
+    pub json_pretty: bool,
+
+    #[clap(long, conflicts_with = "json_pretty", help_heading = "OUTPUT")]
+    /// Write compact JSON the same way Firefox does. This is the default.
+    pub json_compact: bool,
+}
+impl JsonFormatOpt {
+    /// `true` if a JSON format was explicitly requested.
+    pub fn is_specified(&self) -> bool {
+        self.json_pretty || self.json_compact
+    }
+
+    /// Serialize a value using the requested format.
+    pub fn to_vec<T>(&self, value: &T) -> serde_json::Result<Vec<u8>>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        if self.json_pretty {
+            serde_json::to_vec_pretty(value)
+        } else {
+            serde_json::to_vec(value)
+        }
+    }
+}
+
+/// Option to ignore tabs that are open on specific hosts.
+#[derive(Debug, Args, Clone, Default)]
+#[clap(rename_all = "kebab-case")]
+pub struct HostFilterOpt {
+    #[clap(
+        long,
+        value_name = "HOST",
+        action = ArgAction::Append,
+        use_value_delimiter = true
+    )]
+    /// Ignore tabs whose URL has this host, for example "localhost". Hosts
+    /// must match exactly (ignoring case) so "example.com" doesn't match
+    /// "www.example.com". Tabs without a host, such as "about:" pages, are
+    /// never ignored.
+    ///
+    /// Multiple hosts can be specified by separating them with commas (,) or
+    /// by using this option multiple times.
+    pub exclude_host: Vec<String>,
+}
+impl HostFilterOpt {
+    /// `true` if a tab with this URL should be ignored.
+    pub fn is_excluded(&self, url: &str) -> bool {
+        if self.exclude_host.is_empty() {
+            return false;
+        }
+        let Ok(url) = url::Url::parse(url) else {
+            return false;
+        };
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        // IPv6 addresses are written inside brackets in URLs:
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        self.exclude_host.iter().any(|excluded| {
+            excluded
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .eq_ignore_ascii_case(host)
+        })
+    }
+
+    /// Remove ignored tabs from groups. Groups that don't have any tabs left
+    /// are also removed.
+    pub fn filter_groups(&self, groups: &mut Vec<TabGroup<'_>>) {
+        if self.exclude_host.is_empty() {
+            return;
+        }
+        for group in groups.iter_mut() {
+            group.retain_tabs(|tab| !self.is_excluded(tab.url()));
+        }
+        groups.retain(|group| !group.tabs().is_empty());
+    }
+}
+
+/// Options that affect how output data is compressed.
+#[derive(Debug, Args, Clone, Default)]
+#[clap(rename_all = "kebab-case")]
+pub struct OutputCompressionOpt {
+    #[clap(long, default_value = "default", help_heading = "OUTPUT")]
+    /// The lz4 compression mode to use when writing compressed data. Can be
+    /// "default", "fast:<acceleration>" (1-65537) or "high:<level>" (1-12).
+    ///
+    /// Higher acceleration compresses faster but produces larger files while
+    /// higher levels produce smaller files but compress slower.
+    pub compression_mode: compression::CompressionMode,
+
+    #[clap(long, help_heading = "OUTPUT")]
+    /// Decompress the compressed output and check that it matches the
+    /// uncompressed data before anything is written.
+    pub verify: bool,
+}
+impl OutputCompressionOpt {
+    /// The mode that should be passed to [`compression::Encoder::compress`].
+    ///
+    /// Warns if the output won't be byte identical to what Firefox would have
+    /// written.
+    pub fn encoder_mode(
+        &self,
+        library: compression::SupportedCompressionLibrary,
+    ) -> Option<compression::CompressionMode> {
+        if self.compression_mode.is_default() {
+            return None;
+        }
+        if library.to_compression_lib().same_as_firefox_compression() {
+            warn!(
+                "Compressing using the \"{}\" compression mode, the output will differ from what Firefox would write but Firefox can still read it",
+                self.compression_mode
+            );
+        }
+        Some(self.compression_mode.clone())
+    }
+
+    /// Compress some data and verify the result if that was requested.
+    pub fn compress(
+        &self,
+        data: &[u8],
+        library: compression::SupportedCompressionLibrary,
+    ) -> Result<compression::Encoder> {
+        let encoder = compression::Encoder::compress(data, self.encoder_mode(library), library)?;
+        if self.verify {
+            info!("Verifying compressed data");
+            let matches = encoder
+                .verify(data, library)
+                .context("Failed to decompress the compressed data while verifying it")?;
+            if !matches {
+                bail!("The compressed data didn't decompress to the original data");
+            }
+            debug!("Compressed data was verified successfully");
+        }
+        Ok(encoder)
+    }
+}
+
 /// Options to select an input file that is a firefox sessionstore file and also
 /// options to select an output location.
 #[derive(Debug, Args, Clone)]
@@ -144,7 +425,10 @@ pub struct InOutOpt {
     /// Path to the input file. If not provided then attempts to find the last
     /// modified file with the correct file extension. If the path ends with
     /// "\" or "/" then attempts to find the last modified file in the specified
-    /// directory.
+    /// directory. Use "-" to read from stdin.
+    ///
+    /// Can also be a "http://" or "https://" URL to download the input from,
+    /// if this program was built with the "ureq" feature.
     #[clap(short, long, value_parser, help_heading = "INPUT")]
     pub input: Option<PathBuf>,
 
@@ -177,19 +461,34 @@ pub struct InOutOpt {
     )]
     pub firefox_profile: Vec<String>,
 
+    /// Use the Firefox profile whose directory name matches a wildcard
+    /// pattern, for example "*.dev-edition-default". A "*" matches any number
+    /// of characters and a "?" matches a single character.
+    ///
+    /// If several profiles match then the latest modified one is used. Input
+    /// paths are resolved the same way as for "--firefox-profile".
+    #[clap(
+        long,
+        value_name = "PATTERN",
+        conflicts_with = "firefox_profile",
+        help_heading = "INPUT"
+    )]
+    pub profile_glob: Option<String>,
+
     /// Read input from stdin instead of from a file.
     #[clap(
         long,
         visible_alias = "si",
         conflicts_with = "input",
         conflicts_with = "firefox_profile",
+        conflicts_with = "profile_glob",
         help_heading = "INPUT"
     )]
     pub stdin: bool,
 
     /// Path to the output file. If not provided then guess from the input path
     /// or if that isn't provided then use a default name and place the file in
-    /// the current working directory.
+    /// the current working directory. Use "-" to write to stdout.
     #[clap(short, long, value_parser, help_heading = "OUTPUT")]
     pub output: Option<PathBuf>,
 
@@ -197,6 +496,30 @@ pub struct InOutOpt {
     #[clap(long, visible_alias = "ow", help_heading = "OUTPUT")]
     pub overwrite: bool,
 
+    /// Overwrite the output file if it already exists, but only when its name
+    /// was chosen by this program (i.e. "--output" wasn't given or was a
+    /// directory). Without this a numbered name like "name (1).txt" is used.
+    ///
+    /// This never overwrites the input file, use "--overwrite-input" for that.
+    #[clap(
+        short = 'y',
+        long,
+        visible_alias = "yes",
+        conflicts_with = "stdout",
+        help_heading = "OUTPUT"
+    )]
+    pub assume_yes: bool,
+
+    /// Instead of writing to a new numbered file like "name (3).txt" when the
+    /// output file already exists, overwrite the existing file with the
+    /// highest number. Useful when regenerating the same output repeatedly.
+    #[clap(
+        long,
+        conflicts_with_all = &["stdout", "overwrite"],
+        help_heading = "OUTPUT"
+    )]
+    pub replace_latest: bool,
+
     /// Write the output to stdout instead of a file.
     #[clap(
         long,
@@ -284,17 +607,40 @@ impl InOutOpt {
         }
     }
 
+    /// `true` if the input should be read from stdin, either because of the
+    /// `--stdin` flag or because the input path is `-`.
+    pub fn reads_from_stdin(&self) -> bool {
+        self.stdin || self.input.as_deref() == Some(Path::new("-"))
+    }
+
+    /// `true` if the input should be found inside a Firefox profile directory.
+    pub fn uses_firefox_profile(&self) -> bool {
+        !self.firefox_profile.is_empty() || self.profile_glob.is_some()
+    }
+
+    /// The input if it is a `http` or `https` URL that the data should be
+    /// downloaded from.
+    pub fn input_url(&self) -> Option<&str> {
+        let input = self.input.as_deref()?.to_str()?;
+        let (scheme, _) = input.split_once("://")?;
+        (scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https"))
+            .then_some(input)
+    }
+
     /// Resolve an input path. Returns `None` if stdin should be used.
     ///
     /// `file_extensions` is the file extensions that should be used for the
     /// default file. ("jsonlz4" for compressed files and "js" for uncompressed
     /// files.)
     pub fn get_input_path(&self, file_extensions: &[Cow<'static, str>]) -> Result<Option<PathBuf>> {
-        if self.stdin {
+        if self.reads_from_stdin() {
+            if self.uses_firefox_profile() {
+                bail!("A Firefox profile can't be used when reading from stdin (\"--input -\").");
+            }
             trace!("Use stdin as input source");
             return Ok(None);
         }
-        if self.firefox_profile.is_empty() {
+        if !self.uses_firefox_profile() {
             // Input path is relative to the current working directory.
             trace!("Finding input source relative to the current working directory.");
 
@@ -318,12 +664,15 @@ impl InOutOpt {
         }
 
         trace!("Finding input source in a Firefox profile directory");
-        let all_firefox_names = self
-            .firefox_profile
-            .iter()
-            .map(|s| format!("\"{s}\""))
-            .collect::<Vec<_>>()
-            .join(", ");
+        let all_firefox_names = match &self.profile_glob {
+            Some(pattern) => format!("\"{pattern}\" (wildcard pattern)"),
+            None => self
+                .firefox_profile
+                .iter()
+                .map(|s| format!("\"{s}\""))
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
 
         let mut result = try_!({
             // Input path is relative to a Firefox profile directory.
@@ -331,14 +680,29 @@ impl InOutOpt {
             info!(r#"Searching for one of the Firefox profiles {all_firefox_names} in "{}""#, finder.profile_root.display());
 
             // Find the correct Firefox profile:
-            let profile_dir = self.firefox_profile
-                .iter()
-                // Ignore names that could not be found (but not errors)
-                .find_map(|name| finder.find_profile(name).transpose())
-                .with_context(|| format!("Failed to find one of specified Firefox profile directories: {all_firefox_names}"))??;
+            let profile_dir = if let Some(pattern) = &self.profile_glob {
+                finder.find_profile_by_glob(pattern)?
+            } else {
+                self.firefox_profile
+                    .iter()
+                    // Ignore names that could not be found (but not errors)
+                    .find_map(|name| finder.find_profile(name).transpose())
+                    .transpose()?
+            }
+            .with_context(|| format!("Failed to find one of specified Firefox profile directories: {all_firefox_names}"))?;
+
+            // Explain why the session might not be what the user expects:
+            match find::read_clean_shutdown(&profile_dir) {
+                Ok(Some(false)) => warn!(
+                    r#"Firefox didn't shut down cleanly according to "{}", it might still be running or have crashed so the session data might be from a crash recovery state instead of the last clean session"#,
+                    find::SESSION_CHECKPOINTS_FILE
+                ),
+                Ok(_) => {}
+                Err(e) => debug!("Couldn't check if Firefox shut down cleanly: {e:#}"),
+            }
 
             // Find the specified input file inside the Firefox profile:
-            let backup_dir_name = "sessionstore-backups";
+            let backup_dir_name = find::SESSION_BACKUP_DIR;
 
             let (path, is_dir) = self.resolve_input_path(&profile_dir);
 
@@ -364,24 +728,8 @@ impl InOutOpt {
                 }
             } else {
                 info!("No input path was specified so checking default sessionstore file names");
-                let backup_dir = profile_dir.join(backup_dir_name);
-
-                for extension in file_extensions.iter() {
-                    // Check if `sessionstore.` exists.
-
-                    let mut path = profile_dir.join("sessionstore");
-                    path.set_extension(&**extension);
-                    info!(r#"Checking for input file at: "{}""#, path.display());
-                    if path.is_file() {
-                        return Ok(Some(path));
-                    }
-
-                    let mut path = backup_dir.join("recovery");
-                    path.set_extension(&**extension);
-                    info!(r#"Checking for input file at: "{}""#, path.display());
-                    if path.is_file() {
-                        return Ok(Some(path));
-                    }
+                if let Some(path) = find::default_session_file(&profile_dir, file_extensions) {
+                    return Ok(Some(path));
                 }
                 bail!(
                     "Failed to find an input file for the Firefox profile at: \"{}\"",
@@ -438,7 +786,13 @@ impl InOutOpt {
         file_extensions: &[Cow<'static, str>],
     ) -> Result<InputReader> {
         trace!("Determining input source");
-        let state = if let Some(input_path) = self
+        let state = if let Some(url) = self.input_url() {
+            if self.uses_firefox_profile() {
+                bail!("A Firefox profile can't be used when reading from a URL.");
+            }
+            info!(r#"Reading input from URL: "{}""#, url);
+            InputReaderState::Remote(url.to_owned())
+        } else if let Some(input_path) = self
             .get_input_path(file_extensions)
             .context("Failed to find input path.")?
         {
@@ -459,6 +813,30 @@ impl InOutOpt {
         &self,
         default_name: impl Into<Cow<'a, str>>,
         default_extension: impl Into<Cow<'a, str>>,
+    ) -> Result<OutputWriter> {
+        let input_path = self.input.as_deref().filter(|_| self.input_url().is_none());
+        self.get_writer_creator_for_input(default_name, default_extension, input_path)
+    }
+
+    /// `true` if the output file's name is chosen by this program instead of
+    /// being fully specified by the "--output" option.
+    fn output_uses_default_name(&self) -> bool {
+        match &self.output {
+            None => true,
+            Some(output) => {
+                let output = output.to_string_lossy();
+                output.is_empty() || output.ends_with(['\\', '/'])
+            }
+        }
+    }
+
+    /// Like [`get_writer_creator`](Self::get_writer_creator) but ensures that
+    /// "--assume-yes" never overwrites the file at `input_path`.
+    fn get_writer_creator_for_input<'a>(
+        &self,
+        default_name: impl Into<Cow<'a, str>>,
+        default_extension: impl Into<Cow<'a, str>>,
+        input_path: Option<&Path>,
     ) -> Result<OutputWriter> {
         let default_name = default_name.into();
         let default_extension = default_extension.into();
@@ -467,32 +845,66 @@ impl InOutOpt {
             default_name,
             default_extension
         );
-        if self.stdout {
+        if self.writes_to_stdout() {
+            if self.open {
+                bail!("The output can't be opened when writing to stdout (\"--output -\").");
+            }
             trace!("Writing to stdout");
             Ok(OutputWriter::Stdout(io::stdout()))
         } else {
             trace!("Resolving output path.");
-            Ok(OutputWriter::OutputPath {
-                path: find::resolve_to_unused_path(
+            let resolve = |overwrite: bool, replace_latest: bool| {
+                find::resolve_to_unused_path(
                     self.output
                         .as_ref()
                         .map(|v| v.to_string_lossy().into_owned()),
-                    self.overwrite,
-                    default_name,
-                    default_extension,
+                    overwrite,
+                    replace_latest,
+                    default_name.clone(),
+                    default_extension.clone(),
                 )
                 .with_context(|| {
                     format!(
                         "Failed to resolve output path from: {{ path: {:?}, overwrite: {} }}",
-                        self.output, self.overwrite
+                        self.output, overwrite
+                    )
+                })
+            };
+            let overwrite_default =
+                self.overwrite || (self.assume_yes && self.output_uses_default_name());
+            let mut path = resolve(overwrite_default, self.replace_latest)?;
+            // The latest file might not exist yet in which case this is still
+            // an unused path:
+            let mut overwrite = overwrite_default || self.replace_latest;
+            if overwrite && !self.overwrite {
+                let is_input = input_path.is_some_and(|input_path| {
+                    matches!(
+                        (fs::canonicalize(&path), fs::canonicalize(input_path)),
+                        (Ok(a), Ok(b)) if a == b
                     )
-                })?
-                .into(),
-                overwrite: self.overwrite,
+                });
+                if is_input {
+                    info!(
+                        "Not overwriting the input file at \"{}\", use \"--overwrite-input\" for that",
+                        path
+                    );
+                    overwrite = false;
+                    path = resolve(false, false)?;
+                }
+            }
+            Ok(OutputWriter::OutputPath {
+                path: path.into(),
+                overwrite,
             })
         }
     }
 
+    /// `true` if the output should be written to stdout, either because of the
+    /// `--stdout` flag or because the output path is `-`.
+    pub fn writes_to_stdout(&self) -> bool {
+        self.stdout || self.output.as_deref() == Some(Path::new("-"))
+    }
+
     /// Wraps [`get_writer_creator`] but first tries to use the input's filename
     /// without extension to determine the output path.
     pub fn get_writer_creator_from_reader_creator<'a>(
@@ -523,7 +935,7 @@ impl InOutOpt {
             input_stem
         );
 
-        self.get_writer_creator(input_stem, default_extension)
+        self.get_writer_creator_for_input(input_stem, default_extension, reader_creator.path())
     }
 
     pub fn handle_output(&self, mut writer_creator: impl BorrowMut<OutputWriter>) -> Result<()> {
@@ -535,3 +947,39 @@ impl InOutOpt {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_byte_sizes() {
+        assert_eq!(parse_byte_size("0").unwrap(), 0);
+        assert_eq!(parse_byte_size("1024").unwrap(), 1024);
+        assert_eq!(parse_byte_size("500K").unwrap(), 500 * 1024);
+        assert_eq!(parse_byte_size("2 MB").unwrap(), 2 * 1024 * 1024);
+        assert_eq!(parse_byte_size("1GiB").unwrap(), 1024 * 1024 * 1024);
+        assert!(parse_byte_size("").is_err());
+        assert!(parse_byte_size("5X").is_err());
+        assert!(parse_byte_size("5kbb").is_err());
+        assert!(parse_byte_size("1iB").is_err());
+        assert!(parse_byte_size("1ki").is_err());
+        assert_eq!(parse_byte_size("7b").unwrap(), 7);
+        assert!(parse_byte_size("99999999999T").is_err());
+    }
+
+    #[test]
+    fn excluded_hosts_match_exactly() {
+        let filter = HostFilterOpt {
+            exclude_host: vec!["LocalHost".to_owned(), "::1".to_owned()],
+        };
+        assert!(filter.is_excluded("http://localhost:8080/index.html"));
+        assert!(filter.is_excluded("https://LOCALHOST/"));
+        assert!(filter.is_excluded("http://[::1]:3000/"));
+        assert!(!filter.is_excluded("http://www.localhost/"));
+        assert!(!filter.is_excluded("https://example.com/localhost"));
+        assert!(!filter.is_excluded("about:blank"));
+        assert!(!filter.is_excluded("not a url"));
+        assert!(!HostFilterOpt::default().is_excluded("http://localhost/"));
+    }
+}
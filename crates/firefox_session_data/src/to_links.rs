@@ -1,14 +1,26 @@
 //! Print tabs stored in sessionstore file as links.
 
 use crate::{
+    io_utils,
     pdf_converter::{self, DotNetFrameworkItextMode},
-    session_store, Result, SessionstoreOpt,
+    session_store,
+    shared_opts::HostFilterOpt,
+    Result, SessionstoreOpt,
 };
 use clap::{Parser, ValueEnum};
-use eyre::anyhow;
+use eyre::{anyhow, WrapErr};
 use session_store::{
-    session_info::TreeDataSource,
-    to_links::{LinkFormat, ToLinksOptions},
+    session_info::{GroupOptions, TabGroup, TabInfo, TreeDataSource, WindowNaming},
+    to_links::{HtmlTheme, LinkFormat, ToLinksOptions},
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    num::NonZeroUsize,
+    ops::Range,
+    path::PathBuf,
+    ptr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 pub mod ttl_formats {
@@ -269,6 +281,33 @@ pub mod ttl_formats {
         /// Typst is a modern alternative to LaTeX and can easily be converted to
         /// a PDF.
         TYPST = "typst",
+        /// Write the tab trees as a Graphviz DOT graph (".dot" file extension).
+        /// Each window or group has a root node that tabs without a parent tab
+        /// are connected to. The graph can be rendered using Graphviz, for
+        /// example: dot -Tsvg tabs.dot -o tabs.svg
+        DOT = "dot",
+        /// Write only the tabs' URLs, one per line (".txt" file extension). No
+        /// titles, windows or tab trees are included so the output can easily
+        /// be used by other programs such as wget or curl.
+        URLS = "urls",
+        /// Write the windows and tabs as JSON (".json" file extension) with
+        /// their order, selected and pinned state and container. The file can
+        /// be edited and then turned back into a sessionstore file using the
+        /// "import-links" command.
+        JSON = "json",
+        /// Draw the tab trees as an SVG image (".svg" file extension) with
+        /// each tab's title indented by its depth in the tree and lines to its
+        /// parent tab. Titles link to the tabs' URLs. Unlike the DOT format
+        /// this doesn't require any external program to view.
+        SVG = "svg",
+        /// Write the same data as the "json" format but as YAML (".yaml" file
+        /// extension).
+        [extra_info(
+            "{}",
+            if cfg!(not(feature = "yaml")) {" [Note: YAML support was not included when this version of the program was compiled and so this format will fail.]"} else {""},
+        )]
+        [supported(cfg!(feature = "yaml"))]
+        YAML = "yaml",
 
         /// Use Typst as a library (not an external program) to generate a PDF file.
         [extra_info(
@@ -361,6 +400,52 @@ impl TreeData {
     }
 }
 
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum NameWindowsBy {
+    /// Name windows after their index, for example "Window 1".
+    #[default]
+    Index,
+    /// Name windows after the title of their selected tab. Windows with the
+    /// same name get an index appended to their names.
+    ActiveTab,
+}
+impl From<NameWindowsBy> for WindowNaming {
+    fn from(value: NameWindowsBy) -> Self {
+        match value {
+            NameWindowsBy::Index => WindowNaming::Index,
+            NameWindowsBy::ActiveTab => WindowNaming::ActiveTab,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum HtmlThemeOpt {
+    /// Dark text on a light background.
+    Light,
+    /// Light text on a dark background.
+    Dark,
+    /// Follow the reader's light or dark mode preference.
+    Auto,
+}
+impl From<HtmlThemeOpt> for HtmlTheme {
+    fn from(value: HtmlThemeOpt) -> Self {
+        match value {
+            HtmlThemeOpt::Light => HtmlTheme::Light,
+            HtmlThemeOpt::Dark => HtmlTheme::Dark,
+            HtmlThemeOpt::Auto => HtmlTheme::Auto,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OutputEncoding {
+    /// UTF-8 without a Byte Order Mark.
+    Utf8,
+    /// UTF-16 in little endian byte order with a Byte Order Mark. Some older
+    /// Windows programs expect text files to use this encoding.
+    Utf16le,
+}
+
 impl ttl_formats::Format {
     pub fn to_link_format(self) -> (LinkFormat, Option<pdf_converter::PdfConversionMethod>) {
         use pdf_converter::PdfConversionMethod as PdfMode;
@@ -384,6 +469,11 @@ impl ttl_formats::Format {
             Format::MARKDOWN => (Markdown, None),
             Format::HTML => (HTML, None),
             Format::TYPST => (Typst, None),
+            Format::DOT => (Dot, None),
+            Format::URLS => (UrlList, None),
+            Format::JSON => (Json, None),
+            Format::SVG => (Svg, None),
+            Format::YAML => (Yaml, None),
             Format::PDF_TYPST => (Typst, Some(PdfMode::Typst)),
             Format::PDF_LEGACY => (
                 HTML,
@@ -412,7 +502,7 @@ impl ttl_formats::Format {
     }
 }
 
-#[derive(Debug, Parser, Clone)]
+#[derive(Debug, Parser, Clone, Default)]
 #[clap(rename_all = "kebab-case")]
 pub struct TabGroupOptions {
     #[clap(long, visible_alias = "no_sort")]
@@ -427,6 +517,187 @@ pub struct TabGroupOptions {
     #[clap(long)]
     /// Include info from recently closed windows as well as open windows.
     pub closed_windows: bool,
+
+    #[clap(long, requires = "closed_windows")]
+    /// List recently closed windows before open windows.
+    pub closed_first: bool,
+
+    #[clap(long, value_enum, default_value_t = NameWindowsBy::Index)]
+    /// Determines how windows are named if no extension has given them a
+    /// name.
+    pub name_windows_by: NameWindowsBy,
+
+    #[clap(long)]
+    /// Split windows into the tab groups of Firefox's old Panorama (also
+    /// called TabView) feature. Useful for sessions from old profiles. Groups
+    /// whose title isn't known are named after their id.
+    pub tabview_groups: bool,
+
+    #[clap(long, value_name = "N")]
+    /// Only include the first N windows, counted after closed windows were
+    /// included and windows were sorted. Useful to preview very large
+    /// sessions.
+    pub limit_windows: Option<usize>,
+}
+impl TabGroupOptions {
+    /// Get the windows/groups that were selected by these options.
+    pub fn get_groups<'a>(
+        &self,
+        session: &'a session_store::FirefoxSessionStore,
+    ) -> impl Iterator<Item = TabGroup<'a>> {
+        let tabview_groups = self.tabview_groups;
+        session_store::session_info::get_groups_from_session(
+            session,
+            &GroupOptions {
+                include_open_windows: !self.only_closed_windows,
+                include_closed_windows: self.closed_windows || self.only_closed_windows,
+                sort_names: !self.no_sorting,
+                closed_first: self.closed_first,
+                naming: self.name_windows_by.into(),
+            },
+        )
+        .take(self.limit_windows.unwrap_or(usize::MAX))
+        .flat_map(move |group| {
+            if tabview_groups {
+                group.split_by_tabview_groups()
+            } else {
+                vec![group]
+            }
+        })
+    }
+}
+
+/// The name of the group created by `--include-pinned-section`.
+pub const PINNED_GROUP_NAME: &str = "Pinned";
+
+/// Move the pinned tabs of all groups into a new group that is inserted before
+/// the other groups. Groups that don't have any tabs left are removed. Nothing
+/// is changed if there are no pinned tabs.
+pub fn move_pinned_tabs_to_group<'a>(groups: &mut Vec<TabGroup<'a>>, name: &'a str) {
+    let pinned = groups
+        .iter()
+        .flat_map(|group| group.tabs())
+        .filter(|tab| tab.is_pinned())
+        .copied()
+        .collect::<Vec<_>>();
+    if pinned.is_empty() {
+        return;
+    }
+    let is_closed = pinned
+        .iter()
+        .all(|tab| tab.window.is_some_and(|window| window.is_closed));
+    for group in groups.iter_mut() {
+        group.retain_tabs(|tab| !tab.is_pinned());
+    }
+    groups.retain(|group| !group.tabs().is_empty());
+    groups.insert(0, TabGroup::new(name, pinned, is_closed));
+}
+
+/// Split `is_root.len()` tabs into ranges of at most `max_size` tabs. Ranges
+/// start at a tab where `is_root` is `true` when possible so that tab trees
+/// aren't split, trees that have more than `max_size` tabs are split anyway.
+pub fn page_ranges(is_root: &[bool], max_size: usize) -> Vec<Range<usize>> {
+    let max_size = max_size.max(1);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < is_root.len() {
+        let limit = (start + max_size).min(is_root.len());
+        let end = if limit == is_root.len() {
+            limit
+        } else {
+            (start + 1..=limit)
+                .rev()
+                .find(|&index| is_root[index])
+                .unwrap_or(limit)
+        };
+        ranges.push(start..end);
+        start = end;
+    }
+    ranges
+}
+
+/// The number of pages that `--resolve-titles` downloads at the same time.
+const RESOLVE_TITLES_CONCURRENCY: usize = 8;
+
+/// The number of bytes that `--resolve-titles` downloads from each page. The
+/// `<title>` element is usually near the start of the page.
+const RESOLVE_TITLES_MAX_LEN: u64 = 64 * 1024;
+
+/// Get the text of the first `<title>` element in a HTML page. Whitespace is
+/// collapsed and the most common character references are decoded.
+///
+/// Returns `None` if there was no title or if it was empty.
+pub fn extract_html_title(html: &str) -> Option<String> {
+    // ASCII lowercase keeps byte offsets valid for the original text:
+    let lowercase = html.to_ascii_lowercase();
+    let start = lowercase.find("<title")?;
+    let start = start + lowercase[start..].find('>')? + 1;
+    let end = start
+        + lowercase[start..]
+            .find("</title")
+            .unwrap_or(html.len() - start);
+
+    let title = html[start..end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let title = title
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&");
+    let title = title.trim();
+    (!title.is_empty()).then(|| title.to_owned())
+}
+
+/// Download the start of a page and get its title, see
+/// [`extract_html_title`].
+fn fetch_page_title(url: &str, timeout: Duration) -> Result<Option<String>> {
+    let data = io_utils::download_prefix(url, RESOLVE_TITLES_MAX_LEN, timeout)?;
+    Ok(extract_html_title(&String::from_utf8_lossy(&data)))
+}
+
+/// Query parameters that are removed by `--normalize-urls` by default.
+pub const DEFAULT_TRACKING_PARAMS: &[&str] = &["utm_*", "fbclid", "gclid"];
+
+/// Remove query parameters matching `params` and optionally the fragment from
+/// a URL. A parameter name ending with `*` matches all parameters that start
+/// with the text before it.
+///
+/// Returns `None` if the URL couldn't be parsed or if nothing was removed.
+pub fn normalize_url(url: &str, params: &[String], strip_fragment: bool) -> Option<String> {
+    let mut url = url::Url::parse(url).ok()?;
+    let is_tracking_param = |name: &str| {
+        params.iter().any(|param| match param.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == param,
+        })
+    };
+
+    let mut changed = false;
+    if url.query().is_some() {
+        let pairs = url.query_pairs().into_owned().collect::<Vec<_>>();
+        let kept = pairs
+            .iter()
+            .filter(|(name, _)| !is_tracking_param(name))
+            .collect::<Vec<_>>();
+        if kept.len() != pairs.len() {
+            changed = true;
+            if kept.is_empty() {
+                url.set_query(None);
+            } else {
+                url.query_pairs_mut().clear().extend_pairs(kept);
+            }
+        }
+    }
+    if strip_fragment && url.fragment().is_some() {
+        changed = true;
+        url.set_fragment(None);
+    }
+    changed.then(|| url.into())
 }
 
 #[derive(Debug, Parser, Clone)]
@@ -435,6 +706,38 @@ pub struct TabsToLinksOpt {
     #[clap(flatten)]
     pub session_store_opt: SessionstoreOpt,
 
+    #[cfg(feature = "glob")]
+    #[clap(
+        long,
+        value_name = "PATTERN",
+        group = "multiple_inputs",
+        conflicts_with_all = &["input", "stdin"],
+        help_heading = "INPUT"
+    )]
+    /// Write links for every file that matches this glob pattern, for example
+    /// "backups/*.jsonlz4". Each input file gets its own output file that is
+    /// named after it.
+    pub input_glob: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "PATH",
+        group = "multiple_inputs",
+        conflicts_with_all = &["input", "stdin"],
+        help_heading = "INPUT"
+    )]
+    /// Write links for every file listed in this file, one path per line. Use
+    /// "-" to read the list from stdin. Blank lines and lines that start with
+    /// "#" are ignored. Each input file gets its own output file that is
+    /// named after it.
+    pub input_file_list: Option<PathBuf>,
+
+    #[clap(long, requires = "multiple_inputs", help_heading = "INPUT")]
+    /// Skip input files that can't be read or processed instead of stopping
+    /// at the first failure. The command still fails after all files have
+    /// been processed if any of them failed.
+    pub continue_on_error: bool,
+
     #[clap(flatten)]
     pub format: ttl_formats::FormatOpt,
 
@@ -448,6 +751,52 @@ pub struct TabsToLinksOpt {
     /// Don't include a table of contents in the beginning of the output file.
     pub no_table_of_contents: bool,
 
+    #[clap(long, conflicts_with = "stdout", help_heading = "OUTPUT")]
+    /// Write each window's links to a separate file instead of writing all
+    /// links to one file. The files are named after the output file and the
+    /// window. No table of contents is written in this mode.
+    pub split_output_by_group: bool,
+
+    #[clap(long, conflicts_with = "split_output_by_group")]
+    /// Merge the tabs of all windows into a single list instead of writing
+    /// them under a heading for each window. Tab trees are still indented.
+    /// No table of contents is written in this mode.
+    pub flatten: bool,
+
+    #[clap(long, requires = "flatten", default_value = "All Tabs")]
+    /// The name of the single list of tabs when using "--flatten".
+    pub title: String,
+
+    #[clap(long, conflicts_with = "flatten")]
+    /// Move the pinned tabs of all windows into a separate group named
+    /// "Pinned" that is written before all other groups. Windows that only had
+    /// pinned tabs are removed.
+    ///
+    /// Tabs are filtered (for example using "--since") before they are moved,
+    /// so the group only contains the pinned tabs that would otherwise have
+    /// been written.
+    pub include_pinned_section: bool,
+
+    #[clap(long, value_name = "N")]
+    /// Split windows that have more than this many tabs into several groups
+    /// named "Name (1/3)", "Name (2/3)" and so on. Groups are split before tabs
+    /// that don't have a parent tab when possible so that tab trees are kept
+    /// together.
+    pub max_group_size: Option<NonZeroUsize>,
+
+    #[clap(long, value_name = "N")]
+    /// Only write the first N tabs of all windows combined. A notice about
+    /// how many tabs were left out is written at the end of the output.
+    /// Useful to quickly preview the output for a large session.
+    pub tab_limit: Option<usize>,
+
+    #[clap(short, long, value_name = "N", default_value = "1")]
+    /// The number of output files that are written at the same time. Only
+    /// has an effect when writing multiple output files, for example when
+    /// using "--input-glob" or "--split-output-by-group". Log messages for
+    /// different files can be interleaved when this is larger than 1.
+    pub jobs: NonZeroUsize,
+
     #[clap(long, visible_alias = "ial")]
     /// Indent all links so that word wrap doesn't make it hard to determine
     /// where a new link starts.
@@ -478,8 +827,392 @@ pub struct TabsToLinksOpt {
     /// be used. (So if you ever installed Tree Style Tab and haven't closed all
     /// tabs that existed last it was installed then its data will exist.)
     pub tree_data: Vec<TreeData>,
+
+    #[clap(long)]
+    /// Ignore the data that extensions stored for windows and tabs. Window
+    /// names from extensions and tab trees will then not be available.
+    pub exclude_extension_data: bool,
+
+    #[clap(long, value_parser = parse_duration)]
+    /// Only include tabs that were accessed within this duration, for example
+    /// "7d" for the last week. Supported units are "s", "m", "h", "d" and "w".
+    pub since: Option<Duration>,
+
+    #[clap(long, requires = "since")]
+    /// Include tabs that don't have any info about when they were last
+    /// accessed when filtering using `--since`.
+    pub include_undated: bool,
+
+    #[clap(flatten)]
+    pub host_filter: HostFilterOpt,
+
+    #[clap(long)]
+    /// Remove tracking query parameters such as "utm_source", "fbclid" and
+    /// "gclid" from the tabs' URLs.
+    pub normalize_urls: bool,
+
+    #[clap(
+        long,
+        value_name = "NAME",
+        action = clap::ArgAction::Append,
+        requires = "normalize_urls"
+    )]
+    /// A query parameter to remove when using "--normalize-urls". Can be
+    /// specified multiple times and replaces the default list of tracking
+    /// parameters. A name ending with "*" removes all parameters that start
+    /// with the text before it, for example "utm_*".
+    pub strip_param: Vec<String>,
+
+    #[clap(long, requires = "normalize_urls")]
+    /// Also remove the fragment (the part after "#") from the tabs' URLs when
+    /// using "--normalize-urls".
+    pub strip_fragment: bool,
+
+    #[clap(long)]
+    /// Download the pages of tabs that don't have a title and use the page's
+    /// `<title>` instead. Only works for "http" and "https" URLs and requires
+    /// that this program was built with the "ureq" feature.
+    ///
+    /// Note that this makes network requests to every such URL.
+    pub resolve_titles: bool,
+
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        default_value = "10",
+        requires = "resolve_titles"
+    )]
+    /// The maximum number of seconds to wait for each page when using
+    /// "--resolve-titles".
+    pub resolve_titles_timeout: u64,
+
+    #[clap(long)]
+    /// Write the date each tab was last accessed after its link. Only
+    /// supported for Markdown and HTML output.
+    pub last_accessed_timestamps: bool,
+
+    #[clap(
+        long,
+        value_name = "MAX_LENGTH",
+        num_args = 0..=1,
+        default_missing_value = "60"
+    )]
+    /// Use a shortened form of each tab's URL (host and truncated path) as
+    /// the text of its link instead of the tab's title. Links still point to
+    /// the full URL. Optionally specify the maximum length of the shortened
+    /// URL (defaults to 60 characters).
+    pub relative_urls: Option<usize>,
+
+    #[clap(long, allow_hyphen_values = true)]
+    /// Text used to indent tabs once for each of their ancestors when
+    /// visualizing tab trees. The text is written as is so it might need to be
+    /// escaped for the output format. Defaults to a value that depends on the
+    /// output format, for example "|    " for plain text.
+    pub indent_unit: Option<String>,
+
+    #[clap(long, allow_hyphen_values = true)]
+    /// Text written before tabs that have a parent when visualizing tab
+    /// trees. The text is written as is so it might need to be escaped for
+    /// the output format. Defaults to a value that depends on the output
+    /// format, for example "|--- " for plain text.
+    pub tree_connector: Option<String>,
+
+    #[clap(long, visible_alias = "max-depth")]
+    /// The maximum number of levels that tabs are indented when visualizing
+    /// tab trees. Tabs that are nested deeper are shown at this level.
+    pub tree_max_depth: Option<usize>,
+
+    #[clap(long)]
+    /// Add an extra link after tabs that had text typed into their address
+    /// bar without navigating to it, for example a half typed URL. Useful to
+    /// recover such text after a crash.
+    pub typed_values: bool,
+
+    #[clap(long)]
+    /// Include tabs that have no history entries, which are usually unloaded
+    /// tabs, instead of skipping them. They are written using any text typed
+    /// into their address bar or otherwise as an "Unloaded tab" without a URL.
+    pub include_unloaded_tabs: bool,
+
+    #[clap(long, visible_alias = "color-scheme", value_enum)]
+    /// Color scheme for HTML output. Uses the browser's default styling if
+    /// not specified.
+    pub html_theme: Option<HtmlThemeOpt>,
+
+    #[clap(long)]
+    /// Put each window's links in a section that can be collapsed. Only
+    /// supported for HTML output.
+    pub collapsible_groups: bool,
+
+    #[clap(long)]
+    /// Emphasize the tab that was active in each window. The link is shown in
+    /// bold for most formats and prefixed with "*" for plain text. Not
+    /// supported for template output.
+    pub mark_selected: bool,
+
+    #[clap(long)]
+    /// Merge consecutive tabs that have the same URL and tree depth into a
+    /// single link with a count like "×3" added to its title. Only adjacent
+    /// tabs are merged so the tree structure is kept.
+    pub fold_adjacent_duplicates: bool,
+
+    #[clap(long, value_name = "N")]
+    /// Truncate tab titles that are longer than this many characters and end
+    /// them with "…". Links still point to the full URL.
+    pub max_title_length: Option<NonZeroUsize>,
+
+    #[clap(long)]
+    /// Hide tabs inside subtrees that are collapsed in Tree Style Tab's
+    /// sidebar. The collapsed tab is shown with the number of hidden tabs,
+    /// like "(+3 hidden)".
+    pub collapse_subtrees: bool,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = OutputEncoding::Utf8,
+        help_heading = "OUTPUT"
+    )]
+    /// Text encoding of the output file. Only plain text, Markdown, HTML, URL
+    /// list and template output can be written as UTF-16.
+    pub encoding: OutputEncoding,
+
+    #[clap(long, value_name = "FILE", conflicts_with = "format")]
+    /// Write each tab using a custom template read from this file instead of
+    /// using one of the normal formats.
+    ///
+    /// The template is written once for each tab with the placeholders
+    /// "{title}", "{url}", "{group}", "{depth}", "{last_accessed}",
+    /// "{typed_value}" and "{favicon}" replaced with info about the tab. Use
+    /// "{{" and "}}" to write "{" and "}". The replaced text isn't escaped in
+    /// any way so that is the template's responsibility. End the template
+    /// with a line break to put each tab on its own line.
+    pub template: Option<PathBuf>,
+
+    #[clap(long)]
+    /// Write the whole favicon for the "{favicon}" template placeholder and
+    /// the JSON and YAML formats.
+    /// By default only the MIME type, for example "image/png", is written for
+    /// favicons that are stored as "data:" URIs since their image data can be
+    /// large.
+    pub full_favicon: bool,
 }
 impl TabsToLinksOpt {
+    /// Remove tabs that shouldn't be included in the output. Groups that don't
+    /// have any tabs left are also removed.
+    pub fn filter_tabs(&self, groups: &mut Vec<TabGroup<'_>>) {
+        self.host_filter.filter_groups(groups);
+
+        let Some(since) = self.since else {
+            return;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        // Firefox stores the time in milliseconds since the Unix epoch:
+        let cutoff = i64::try_from(now.saturating_sub(since).as_millis()).unwrap_or(i64::MAX);
+
+        for group in groups.iter_mut() {
+            group.retain_tabs(|tab| match tab.data.last_accessed {
+                last_accessed if last_accessed <= 0 => self.include_undated,
+                last_accessed => last_accessed >= cutoff,
+            });
+        }
+        groups.retain(|group| !group.tabs().is_empty());
+    }
+
+    /// Remove tracking parameters from all tabs' URLs if `--normalize-urls`
+    /// was specified, see [`normalize_url`]. Returns the number of URLs that
+    /// were changed.
+    pub fn normalize_urls(&self, session: &mut session_store::FirefoxSessionStore) -> usize {
+        if !self.normalize_urls {
+            return 0;
+        }
+        let params = if self.strip_param.is_empty() {
+            DEFAULT_TRACKING_PARAMS
+                .iter()
+                .map(|&param| param.to_owned())
+                .collect()
+        } else {
+            self.strip_param.clone()
+        };
+        let mut changed = 0;
+        let windows = session
+            .windows
+            .iter_mut()
+            .chain(&mut session._closed_windows);
+        for entry in windows
+            .flat_map(|window| &mut window.tabs)
+            .flat_map(|tab| &mut tab.entries)
+        {
+            if let Some(url) = normalize_url(&entry.url, &params, self.strip_fragment) {
+                entry.url = url;
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Download titles for tabs that don't have any if `--resolve-titles` was
+    /// specified. Each URL is only downloaded once even if several tabs use it.
+    /// Returns the number of tabs that got a title.
+    pub fn resolve_titles(
+        &self,
+        session: &mut session_store::FirefoxSessionStore,
+    ) -> Result<usize> {
+        if !self.resolve_titles {
+            return Ok(0);
+        }
+        if cfg!(not(feature = "ureq")) {
+            eyre::bail!(
+                "The \"--resolve-titles\" option isn't supported since this program was built without the \"ureq\" feature"
+            );
+        }
+        let timeout = Duration::from_secs(self.resolve_titles_timeout);
+
+        let windows = || session.windows.iter().chain(&session._closed_windows);
+        let urls = windows()
+            .flat_map(|window| &window.tabs)
+            .filter_map(|tab| TabInfo::new(tab).current_entry())
+            .filter(|entry| entry.title.is_empty())
+            .map(|entry| entry.url.as_str())
+            .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+            .collect::<BTreeSet<_>>();
+        if urls.is_empty() {
+            return Ok(0);
+        }
+        log::info!("Downloading titles for {} URLs", urls.len());
+
+        let titles = crate::run_jobs(
+            RESOLVE_TITLES_CONCURRENCY,
+            urls,
+            |url| match fetch_page_title(url, timeout) {
+                Ok(title) => title.map(|title| (url.to_owned(), title)),
+                Err(e) => {
+                    log::warn!("Failed to resolve the title for \"{url}\": {e:#}");
+                    None
+                }
+            },
+        )
+        .into_iter()
+        .flatten()
+        .collect::<HashMap<_, _>>();
+
+        let mut changed = 0;
+        let windows = session
+            .windows
+            .iter_mut()
+            .chain(&mut session._closed_windows);
+        for tab in windows.flat_map(|window| &mut window.tabs) {
+            let Some(index) = TabInfo::new(tab).current_entry_index() else {
+                continue;
+            };
+            let entry = &mut tab.entries[index];
+            if !entry.title.is_empty() {
+                continue;
+            }
+            if let Some(title) = titles.get(&entry.url) {
+                entry.title = title.clone();
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Merge all groups into a single group if the `--flatten` flag was
+    /// specified. The tabs keep their windows so that tab trees can still be
+    /// resolved.
+    pub fn flatten_groups<'a>(&'a self, groups: &mut Vec<TabGroup<'a>>) {
+        if !self.flatten || groups.is_empty() {
+            return;
+        }
+        let is_closed = groups.iter().all(|group| group.is_closed());
+        let tabs = groups
+            .drain(..)
+            .flat_map(|group| group.tabs().to_vec())
+            .collect();
+        groups.push(TabGroup::new(self.title.as_str(), tabs, is_closed));
+    }
+
+    /// Move pinned tabs into their own group if `--include-pinned-section`
+    /// was specified, see [`move_pinned_tabs_to_group`].
+    pub fn pinned_section(&self, groups: &mut Vec<TabGroup<'_>>) {
+        if self.include_pinned_section {
+            move_pinned_tabs_to_group(groups, PINNED_GROUP_NAME);
+        }
+    }
+
+    /// Split groups that have more tabs than `--max-group-size` into several
+    /// smaller groups, see [`page_ranges`].
+    pub fn paginate_groups(&self, groups: &mut Vec<TabGroup<'_>>) {
+        let Some(max_size) = self.max_group_size else {
+            return;
+        };
+        let tree_sources = TreeData::to_tree_sources(self.tree_data.as_slice());
+        for group in std::mem::take(groups) {
+            let tabs = group.tabs();
+            if tabs.len() <= max_size.get() {
+                groups.push(group);
+                continue;
+            }
+            // Tabs whose parent isn't in this group start a new subtree:
+            let is_root = tabs
+                .iter()
+                .map(|tab| match tab.tst_parent(&tree_sources) {
+                    Some(parent) => !tabs
+                        .iter()
+                        .any(|other| ptr::eq(other.data, parent.value.data)),
+                    None => true,
+                })
+                .collect::<Vec<_>>();
+            let ranges = page_ranges(&is_root, max_size.get());
+            let page_count = ranges.len();
+            for (index, range) in ranges.into_iter().enumerate() {
+                let mut page = TabGroup::new(
+                    format!("{} ({}/{})", group.name(), index + 1, page_count),
+                    tabs[range].to_vec(),
+                    group.is_closed(),
+                );
+                if let Some(window) = group.window() {
+                    page = page.with_window(window);
+                }
+                groups.push(page);
+            }
+        }
+    }
+
+    /// Only keep the first `--tab-limit` tabs of all groups combined. Tabs
+    /// that `options` won't write aren't counted. Groups that don't have any
+    /// tabs left are removed. Returns the number of tabs that would have been
+    /// written if any tabs were removed.
+    pub fn limit_tabs(
+        &self,
+        groups: &mut Vec<TabGroup<'_>>,
+        options: &session_store::to_links::ToLinksOptions<'_>,
+    ) -> Option<usize> {
+        let limit = self.tab_limit?;
+        let total = groups
+            .iter()
+            .flat_map(|group| group.tabs())
+            .filter(|tab| options.includes_tab(tab))
+            .count();
+        if total <= limit {
+            return None;
+        }
+        let mut remaining = limit;
+        for group in groups.iter_mut() {
+            group.retain_tabs(|tab| {
+                let keep = remaining > 0;
+                if options.includes_tab(tab) {
+                    remaining = remaining.saturating_sub(1);
+                }
+                keep
+            });
+        }
+        groups.retain(|group| !group.tabs().is_empty());
+        Some(total)
+    }
+
     pub fn get_options_for_format(&self, format: ttl_formats::Format) -> TabsToLinksOutput {
         let (format, as_pdf) = format.to_link_format();
 
@@ -489,14 +1222,32 @@ impl TabsToLinksOpt {
             page_breaks_after_group: self.page_breaks,
             skip_page_break_after_last_group: (format.is_html() || format.is_typst())
                 && self.page_breaks,
-            table_of_contents: !self.no_table_of_contents,
+            table_of_contents: !self.no_table_of_contents && !self.flatten,
             indent_all_links: self.indent_all_links,
             custom_page_break: "".into(),
             tree_sources: tree_sources.into(),
+            last_accessed_timestamps: self.last_accessed_timestamps,
+            shorten_urls: self.relative_urls,
+            indent_unit: self.indent_unit.clone().map(Into::into),
+            tree_connector: self.tree_connector.clone().map(Into::into),
+            tree_max_depth: self.tree_max_depth,
+            typed_values: self.typed_values,
+            include_unloaded_tabs: self.include_unloaded_tabs,
+            html_theme: self.html_theme.map(Into::into),
+            collapsible_groups: self.collapsible_groups,
+            template: "".into(),
+            full_favicon: self.full_favicon,
+            mark_selected: self.mark_selected,
+            fold_adjacent_duplicates: self.fold_adjacent_duplicates,
+            max_title_length: self.max_title_length.map(NonZeroUsize::get),
+            collapse_subtrees: self.collapse_subtrees,
+            // Set after the groups have been truncated using `limit_tabs`:
+            truncated_from: None,
         };
         TabsToLinksOutput {
             format,
             as_pdf,
+            encoding: self.encoding,
             conversion_options,
         }
     }
@@ -513,13 +1264,76 @@ impl TabsToLinksOpt {
     /// normal file extension for the produced format.
     pub fn parse_options(&self) -> Result<TabsToLinksOutput> {
         let format = self.parse_format()?;
-        Ok(self.get_options_for_format(format))
+        let mut output = self.get_options_for_format(format);
+        if let Some(template_path) = &self.template {
+            let template = fs::read_to_string(template_path).with_context(|| {
+                format!(
+                    "Failed to read template file at \"{}\"",
+                    template_path.display()
+                )
+            })?;
+            output.format = LinkFormat::Template;
+            output.as_pdf = None;
+            output.conversion_options.format = LinkFormat::Template;
+            output.conversion_options.template = template.into();
+        }
+        let supports_utf16 = output.as_pdf.is_none()
+            && match output.format {
+                LinkFormat::TXT
+                | LinkFormat::Markdown
+                | LinkFormat::HTML
+                | LinkFormat::Template
+                | LinkFormat::UrlList => true,
+                LinkFormat::RTF { .. }
+                | LinkFormat::Typst
+                | LinkFormat::Dot
+                | LinkFormat::Json
+                | LinkFormat::Svg
+                | LinkFormat::Yaml => false,
+            };
+        if output.encoding != OutputEncoding::Utf8 && !supports_utf16 {
+            return Err(anyhow!(
+                "The {:?} encoding is only supported for text, Markdown, HTML, URL list and template output",
+                output.encoding
+            ));
+        }
+        Ok(output)
     }
 }
 
+/// Parse a duration such as "30m", "12h" or "7d".
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let text = text.trim();
+    let unit_start = text
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("Missing unit for duration \"{}\", try \"{}d\"", text, text))?;
+    let (value, unit) = text.split_at(unit_start);
+    let value = value
+        .parse::<u64>()
+        .map_err(|e| anyhow!("Invalid number in duration \"{}\": {}", text, e))?;
+    let seconds_per_unit = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        unit => {
+            return Err(anyhow!(
+            "Unknown unit \"{}\" for duration, expected one of \"s\", \"m\", \"h\", \"d\" or \"w\"",
+            unit
+        ))
+        }
+    };
+    value
+        .checked_mul(seconds_per_unit)
+        .map(Duration::from_secs)
+        .ok_or_else(|| anyhow!("The duration \"{}\" is too large", text))
+}
+
 pub struct TabsToLinksOutput {
     pub format: LinkFormat,
     pub as_pdf: Option<pdf_converter::PdfConversionMethod>,
+    pub encoding: OutputEncoding,
     pub conversion_options: ToLinksOptions<'static>,
 }
 impl TabsToLinksOutput {
@@ -536,6 +1350,85 @@ impl TabsToLinksOutput {
             HTML => "html",
             Markdown => "md",
             Typst => "typ",
+            Template => "txt",
+            Dot => "dot",
+            UrlList => "txt",
+            Json => "json",
+            Svg => "svg",
+            Yaml => "yaml",
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_urls_removes_tracking_params() {
+        let params = DEFAULT_TRACKING_PARAMS
+            .iter()
+            .map(|&param| param.to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            normalize_url(
+                "https://example.com/a?id=1&utm_source=x&fbclid=y#top",
+                &params,
+                false
+            )
+            .as_deref(),
+            Some("https://example.com/a?id=1#top")
+        );
+        assert_eq!(
+            normalize_url("https://example.com/a?utm_medium=x#top", &params, true).as_deref(),
+            Some("https://example.com/a")
+        );
+        assert_eq!(
+            normalize_url("https://example.com/a?id=1", &params, false),
+            None
+        );
+        assert_eq!(normalize_url("not a url", &params, true), None);
+        assert_eq!(
+            normalize_url(
+                "https://example.com/?ref=a&utm_source=b",
+                &["ref".to_owned()],
+                false
+            )
+            .as_deref(),
+            Some("https://example.com/?utm_source=b")
+        );
+    }
+
+    #[test]
+    fn html_titles_are_extracted() {
+        assert_eq!(
+            extract_html_title(
+                "<html><HEAD><Title data-x=\"1\">\n  Tom &amp; Jerry\n  &lt;3 </TITLE></head></html>"
+            )
+            .as_deref(),
+            Some("Tom & Jerry <3")
+        );
+        assert_eq!(
+            extract_html_title("<title>Ünïcode tïtle</title>").as_deref(),
+            Some("Ünïcode tïtle")
+        );
+        // Truncated pages still have a title:
+        assert_eq!(
+            extract_html_title("<title>Cut off").as_deref(),
+            Some("Cut off")
+        );
+        assert_eq!(extract_html_title("<title> </title>"), None);
+        assert_eq!(extract_html_title("<p>No title</p>"), None);
+    }
+
+    #[test]
+    fn large_groups_are_split_between_trees() {
+        let (t, f) = (true, false);
+        assert_eq!(page_ranges(&[t, f, f, t, f, t, t], 4), [0..3, 3..7]);
+        assert_eq!(page_ranges(&[t, t, t, t, t], 2), [0..2, 2..4, 4..5]);
+        // Trees that are larger than a page are split anyway:
+        assert_eq!(page_ranges(&[t, f, f, f, f, t], 2), [0..2, 2..4, 4..6]);
+        assert_eq!(page_ranges(&[t, f, t], 5), vec![0..3]);
+        assert!(page_ranges(&[], 3).is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+//! End-to-end test for `analyze-json` reading large inputs from stdin.
+
+use std::io::{BufWriter, Write};
+use std::process::{Command, Stdio};
+
+const WINDOWS: usize = 20;
+const TABS_PER_WINDOW: usize = 2_500;
+
+/// Write a large sessionstore-like JSON document without ever holding all of
+/// it in memory.
+fn write_large_session(writer: impl Write) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    write!(writer, r#"{{"version":["sessionrestore",1],"windows":["#)?;
+    for window in 0..WINDOWS {
+        if window > 0 {
+            write!(writer, ",")?;
+        }
+        write!(writer, r#"{{"selected":1,"tabs":["#)?;
+        for tab in 0..TABS_PER_WINDOW {
+            if tab > 0 {
+                write!(writer, ",")?;
+            }
+            write!(
+                writer,
+                r#"{{"entries":[{{"url":"https://example.com/{window}/{tab}","title":"Tab {tab}"}}],"index":1,"pinned":false}}"#
+            )?;
+        }
+        write!(writer, "]}}")?;
+    }
+    write!(writer, "]}}")?;
+    writer.flush()
+}
+
+#[test]
+fn analyze_json_streams_large_stdin_input() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_firefox-session-data"))
+        .args(["analyze-json", "--stdin", "--stdout", "--flat", "--quiet"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .expect("failed to start firefox-session-data");
+
+    let stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || write_large_session(stdin));
+
+    let output = child.wait_with_output().unwrap();
+    writer
+        .join()
+        .unwrap()
+        .expect("failed to write JSON to stdin");
+    assert!(output.status.success(), "analyze-json failed");
+
+    // Compare with statistics collected from a fully parsed value:
+    let mut data = Vec::new();
+    write_large_session(&mut data).unwrap();
+    assert!(data.len() > 4_000_000, "test input should be large");
+    let expected = json_statistics::collect_statistics(&serde_json::from_slice(&data).unwrap())
+        .with_formatter(json_statistics::print::FlatStatisticsFormatter::new())
+        .to_string();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
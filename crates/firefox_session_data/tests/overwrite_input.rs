@@ -0,0 +1,51 @@
+//! End-to-end tests for overwriting the input of the `modify` command.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Run `modify` with `cat` as the command and some extra arguments.
+fn modify(args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_firefox-session-data"))
+        .arg("modify")
+        .args(args)
+        .args(["--", "cat"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start firefox-session-data");
+    // The input might not be read at all so ignore broken pipes:
+    let _ = child.stdin.take().unwrap().write_all(br#"{"windows":[]}"#);
+    child.wait_with_output().unwrap()
+}
+
+fn assert_requires_local_file(output: &Output) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    assert!(
+        stderr.contains("--overwrite-input/--swap require a local input file"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn overwrite_input_rejects_stdin_and_urls() {
+    assert_requires_local_file(&modify(&["--input", "-", "--overwrite-input"]));
+    assert_requires_local_file(&modify(&[
+        "--input",
+        "https://example.invalid/sessionstore.jsonlz4",
+        "--overwrite-input",
+    ]));
+}
+
+#[test]
+fn swap_rejects_stdin_and_urls() {
+    assert_requires_local_file(&modify(&["--input", "-", "--swap"]));
+    assert_requires_local_file(&modify(&[
+        "--input",
+        "https://example.invalid/sessionstore.jsonlz4",
+        "--swap",
+    ]));
+}
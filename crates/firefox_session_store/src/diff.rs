@@ -0,0 +1,311 @@
+//! Compare two Firefox sessions to find out what tabs and windows changed.
+//!
+//! Tabs are matched using their URL and the name of the group (window) they
+//! are in. The order of tabs is ignored so reordering tabs inside a window
+//! isn't reported as a change. A tab whose URL disappears from one group and
+//! appears in another is reported as moved.
+
+use serde::Serialize;
+
+use crate::session_info::{get_groups_from_session, GroupOptions};
+use crate::FirefoxSessionStore;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+
+/// A tab that was only present in one of the compared sessions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TabDiffEntry {
+    /// The name of the group (window) that contained the tab.
+    pub group: String,
+    pub title: String,
+    pub url: String,
+}
+
+/// A tab that was present in both sessions but in different groups.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MovedTab {
+    pub title: String,
+    pub url: String,
+    /// The group that the tab was in for the first session.
+    pub from_group: String,
+    /// The group that the tab was in for the second session.
+    pub to_group: String,
+}
+
+/// The differences between two sessions, see [`diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SessionDiff {
+    /// Names of groups that only exist in the second session.
+    pub added_groups: Vec<String>,
+    /// Names of groups that only exist in the first session.
+    pub removed_groups: Vec<String>,
+    /// Tabs that only exist in the second session.
+    pub added_tabs: Vec<TabDiffEntry>,
+    /// Tabs that only exist in the first session.
+    pub removed_tabs: Vec<TabDiffEntry>,
+    /// Tabs that changed group.
+    pub moved_tabs: Vec<MovedTab>,
+}
+impl SessionDiff {
+    /// `true` if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.added_groups.is_empty()
+            && self.removed_groups.is_empty()
+            && self.added_tabs.is_empty()
+            && self.removed_tabs.is_empty()
+            && self.moved_tabs.is_empty()
+    }
+}
+impl fmt::Display for SessionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences found");
+        }
+        for group in &self.added_groups {
+            writeln!(f, "+ Group \"{}\"", group)?;
+        }
+        for group in &self.removed_groups {
+            writeln!(f, "- Group \"{}\"", group)?;
+        }
+        for tab in &self.added_tabs {
+            writeln!(f, "+ [{}] {} ({})", tab.group, tab.title, tab.url)?;
+        }
+        for tab in &self.removed_tabs {
+            writeln!(f, "- [{}] {} ({})", tab.group, tab.title, tab.url)?;
+        }
+        for tab in &self.moved_tabs {
+            writeln!(
+                f,
+                "~ [{} -> {}] {} ({})",
+                tab.from_group, tab.to_group, tab.title, tab.url
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Get all tabs in a session, both from open and closed windows.
+fn collect_tabs(session: &FirefoxSessionStore) -> (Vec<String>, Vec<TabDiffEntry>) {
+    let mut group_names = Vec::new();
+    let mut tabs = Vec::new();
+    let options = GroupOptions {
+        include_closed_windows: true,
+        ..Default::default()
+    };
+    for group in get_groups_from_session(session, &options) {
+        for tab in group.tabs() {
+            tabs.push(TabDiffEntry {
+                group: group.name().to_owned(),
+                title: tab.title().to_owned(),
+                url: tab.url().to_owned(),
+            });
+        }
+        group_names.push(group.name().to_owned());
+    }
+    (group_names, tabs)
+}
+
+/// Tabs from two sessions after they have been matched with each other.
+struct MatchedTabs {
+    pairs: Vec<(TabDiffEntry, TabDiffEntry)>,
+    only_old: Vec<TabDiffEntry>,
+    only_new: Vec<TabDiffEntry>,
+}
+
+/// Match tabs that have the same `key`. Duplicates are matched one to one and
+/// the order of the tabs is preserved.
+fn match_tabs<K, F>(old: Vec<TabDiffEntry>, new: Vec<TabDiffEntry>, key: F) -> MatchedTabs
+where
+    K: Hash + Eq,
+    F: Fn(&TabDiffEntry) -> K,
+{
+    let mut new_indexes = HashMap::<K, Vec<usize>>::new();
+    for (index, tab) in new.iter().enumerate().rev() {
+        new_indexes.entry(key(tab)).or_default().push(index);
+    }
+    let mut new = new.into_iter().map(Some).collect::<Vec<_>>();
+
+    let mut pairs = Vec::new();
+    let mut only_old = Vec::new();
+    for tab in old {
+        let matched = new_indexes
+            .get_mut(&key(&tab))
+            .and_then(Vec::pop)
+            .and_then(|index| new[index].take());
+        match matched {
+            Some(new_tab) => pairs.push((tab, new_tab)),
+            None => only_old.push(tab),
+        }
+    }
+    MatchedTabs {
+        pairs,
+        only_old,
+        only_new: new.into_iter().flatten().collect(),
+    }
+}
+
+/// Compare two sessions where `old` is the original session and `new` is the
+/// modified one. Tabs in both open and closed windows are compared.
+pub fn diff(old: &FirefoxSessionStore, new: &FirefoxSessionStore) -> SessionDiff {
+    let (old_groups, old_tabs) = collect_tabs(old);
+    let (new_groups, new_tabs) = collect_tabs(new);
+
+    // Tabs that stayed in the same group:
+    let unchanged = match_tabs(old_tabs, new_tabs, |tab| {
+        (tab.group.clone(), tab.url.clone())
+    });
+    // Tabs that changed group:
+    let moved = match_tabs(unchanged.only_old, unchanged.only_new, |tab| {
+        tab.url.clone()
+    });
+
+    let old_group_set = old_groups.iter().collect::<HashSet<_>>();
+    let new_group_set = new_groups.iter().collect::<HashSet<_>>();
+
+    SessionDiff {
+        added_groups: new_groups
+            .iter()
+            .filter(|name| !old_group_set.contains(name))
+            .cloned()
+            .collect(),
+        removed_groups: old_groups
+            .iter()
+            .filter(|name| !new_group_set.contains(name))
+            .cloned()
+            .collect(),
+        added_tabs: moved.only_new,
+        removed_tabs: moved.only_old,
+        moved_tabs: moved
+            .pairs
+            .into_iter()
+            .map(|(old_tab, new_tab)| MovedTab {
+                title: new_tab.title,
+                url: new_tab.url,
+                from_group: old_tab.group,
+                to_group: new_tab.group,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A session where each window is a list of `(url, title)` tabs.
+    fn session(windows: &[&[(&str, &str)]]) -> FirefoxSessionStore {
+        let windows = windows
+            .iter()
+            .map(|tabs| {
+                let tabs = tabs
+                    .iter()
+                    .map(|(url, title)| {
+                        json!({
+                            "entries": [{ "url": url, "title": title }],
+                            "lastAccessed": 0,
+                            "hidden": false,
+                            "attributes": {},
+                            "userContextId": 0,
+                            "index": 1,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                json!({
+                    "tabs": tabs,
+                    "selected": 1,
+                    "width": 800,
+                    "height": 600,
+                    "screenX": 0,
+                    "screenY": 0,
+                    "sizemode": "normal",
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::from_value(json!({
+            "version": ["sessionrestore", 1],
+            "windows": windows,
+            "selectedWindow": 1,
+            "session": { "lastUpdate": 0, "startTime": 0, "recentCrashes": 0 },
+            "global": {},
+        }))
+        .unwrap()
+    }
+
+    fn entry(group: &str, url: &str, title: &str) -> TabDiffEntry {
+        TabDiffEntry {
+            group: group.to_owned(),
+            title: title.to_owned(),
+            url: url.to_owned(),
+        }
+    }
+
+    #[test]
+    fn added_and_removed_tabs() {
+        let old = session(&[&[("https://a/", "A"), ("https://b/", "B")]]);
+        let new = session(&[&[("https://b/", "B"), ("https://c/", "C")]]);
+        assert_eq!(
+            diff(&old, &new),
+            SessionDiff {
+                added_tabs: vec![entry("Window 1", "https://c/", "C")],
+                removed_tabs: vec![entry("Window 1", "https://a/", "A")],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn moved_tabs() {
+        let old = session(&[&[("https://a/", "A")], &[("https://b/", "B")]]);
+        let new = session(&[&[("https://a/", "A"), ("https://b/", "B")], &[]]);
+        assert_eq!(
+            diff(&old, &new),
+            SessionDiff {
+                moved_tabs: vec![MovedTab {
+                    title: "B".to_owned(),
+                    url: "https://b/".to_owned(),
+                    from_group: "Window 2".to_owned(),
+                    to_group: "Window 1".to_owned(),
+                }],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn reordered_and_retitled_tabs_are_unchanged() {
+        let old = session(&[&[("https://a/", "A"), ("https://b/", "B")]]);
+        let new = session(&[&[("https://b/", "B"), ("https://a/", "New title")]]);
+        let changes = diff(&old, &new);
+        assert!(changes.is_empty(), "{changes:?}");
+        assert_eq!(changes.to_string(), "No differences found\n");
+    }
+
+    #[test]
+    fn windows_in_only_one_session() {
+        let one = session(&[&[("https://a/", "A")]]);
+        let two = session(&[&[("https://a/", "A")], &[("https://b/", "B")]]);
+        assert_eq!(
+            diff(&one, &two),
+            SessionDiff {
+                added_groups: vec!["Window 2".to_owned()],
+                added_tabs: vec![entry("Window 2", "https://b/", "B")],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            diff(&two, &one),
+            SessionDiff {
+                removed_groups: vec!["Window 2".to_owned()],
+                removed_tabs: vec![entry("Window 2", "https://b/", "B")],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            diff(&two, &one).to_string(),
+            "- Group \"Window 2\"\n- [Window 2] B (https://b/)\n"
+        );
+    }
+}
@@ -1,13 +1,22 @@
 //! Firefox sessionstore files contains a JSON Value that can be deserialized to a `FirefoxSessionStore` struct.
 
+pub mod diff;
 pub mod group_tab;
+pub mod link_export;
 mod serde_as_json_str;
 mod serde_as_str;
 pub mod session_info;
+pub mod stats;
 pub mod to_links;
 
 use serde::{Deserialize, Serialize};
 
+use std::collections::BTreeMap;
+
+pub use diff::{diff, SessionDiff};
+pub use session_info::{build_tab_tree, TabTreeNode};
+pub use stats::SessionStats;
+
 #[cfg(feature = "view")]
 pub use serde_unstructured;
 #[cfg(feature = "view")]
@@ -27,6 +36,68 @@ pub struct FirefoxSessionStore {
     pub global: FirefoxGlobal,
 }
 
+impl FirefoxSessionStore {
+    /// The number of open windows.
+    pub fn window_count(&self) -> usize {
+        self.windows.len()
+    }
+    /// The number of recently closed windows.
+    pub fn closed_window_count(&self) -> usize {
+        self._closed_windows.len()
+    }
+    /// The number of tabs in open windows.
+    pub fn tab_count(&self) -> usize {
+        self.windows.iter().map(|window| window.tabs.len()).sum()
+    }
+    /// The number of pinned tabs in open windows.
+    pub fn pinned_tab_count(&self) -> usize {
+        self.open_tabs()
+            .filter(|tab| tab.pinned.unwrap_or(false))
+            .count()
+    }
+    /// The number of tabs in open windows that use each container, keyed by
+    /// the container's `userContextId`. Tabs without a container aren't
+    /// counted.
+    pub fn container_tab_counts(&self) -> BTreeMap<i64, usize> {
+        let mut counts = BTreeMap::new();
+        for tab in self.open_tabs().filter(|tab| tab.user_context_id != 0) {
+            *counts.entry(tab.user_context_id).or_default() += 1;
+        }
+        counts
+    }
+    /// The version of the format that the session was written in, for example
+    /// `"sessionrestore 1"`. Returns `None` if the session has no version info.
+    ///
+    /// Firefox stores this as a list like `["sessionrestore", 1]` where the
+    /// first item is the name of the component that wrote the data and the
+    /// second is its format version. The format version has been `1` for a
+    /// long time so this can't be used to tell which Firefox release wrote
+    /// the session, but it does show if the data came from something other
+    /// than Firefox's session restore.
+    pub fn writer_version(&self) -> Option<String> {
+        if self.version.is_empty() {
+            return None;
+        }
+        let parts = self
+            .version
+            .iter()
+            .map(|part| match part {
+                FirefoxVersionInfo::Text(text) => text.clone(),
+                FirefoxVersionInfo::Number(number) => number.to_string(),
+            })
+            .collect::<Vec<_>>();
+        Some(parts.join(" "))
+    }
+    /// Counts of windows and tabs in this session.
+    pub fn stats(&self) -> SessionStats {
+        SessionStats::new(self)
+    }
+
+    fn open_tabs(&self) -> impl Iterator<Item = &FirefoxTab> + '_ {
+        self.windows.iter().flat_map(|window| &window.tabs)
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum FirefoxVersionInfo {
@@ -54,9 +125,14 @@ pub struct FirefoxWindow {
     pub tabs: Vec<FirefoxTab>,
     /// A 1-based index for the currently selected tab.
     pub selected: i64,
-    /// Info about recently closed tabs.
+    /// Info about recently closed tabs. Use [`FirefoxWindow::closed_tabs`] to
+    /// get the tab data regardless of the format it was stored in.
+    #[serde(default, rename = "_closedTabs")]
+    pub _closed_tabs: Vec<window_data::ClosedTab>,
+    /// Tab groups that were closed together with their tabs. Firefox version
+    /// 136 and later stores these.
     #[serde(default)]
-    pub _closed_tabs: Vec<FirefoxTab>,
+    pub closed_groups: Vec<window_data::ClosedGroup>,
     pub busy: Option<bool>,
     /// Extension data stored via the
     /// [`browser.sessions.setWindowValue`](https://developer.mozilla.org/docs/Mozilla/Add-ons/WebExtensions/API/sessions/setWindowValue)
@@ -74,6 +150,18 @@ pub struct FirefoxWindow {
     pub sidebar: SidebarInfo,
 }
 
+impl FirefoxWindow {
+    /// All recently closed tabs in this window, including the tabs of closed
+    /// tab groups. Closed tabs that were stored in an unknown format are
+    /// skipped.
+    pub fn closed_tabs(&self) -> impl Iterator<Item = &FirefoxTab> + '_ {
+        self._closed_tabs
+            .iter()
+            .chain(self.closed_groups.iter().flat_map(|group| &group.tabs))
+            .filter_map(window_data::ClosedTab::tab)
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 #[serde(untagged)]
@@ -123,6 +211,7 @@ pub mod window_data {
     //! Types for data that is stored inside [`FirefoxWindow`](super::FirefoxWindow).
 
     use crate::serde_as_json_str;
+    use crate::FirefoxTab;
     use serde::{Deserialize, Serialize};
 
     #[cfg(feature = "view")]
@@ -220,6 +309,60 @@ pub mod window_data {
         pub title: Option<String>,
         pub id: Option<i64>,
     }
+
+    /// A recently closed tab, see
+    /// [`FirefoxWindow::closed_tabs`](super::FirefoxWindow::closed_tabs).
+    #[derive(Deserialize, Serialize, Debug, Clone)]
+    #[serde(untagged)]
+    pub enum ClosedTab {
+        /// The tab's data together with info about when it was closed.
+        WithState(ClosedTabState),
+        /// Only the tab's data.
+        Tab(FirefoxTab),
+        /// Data in an unknown format. Kept so that the rest of the session
+        /// can still be parsed.
+        Unknown(serde_json::Value),
+    }
+    impl ClosedTab {
+        /// The closed tab's data if it was in a known format.
+        pub fn tab(&self) -> Option<&FirefoxTab> {
+            match self {
+                ClosedTab::WithState(closed) => Some(&closed.state),
+                ClosedTab::Tab(tab) => Some(tab),
+                ClosedTab::Unknown(_) => None,
+            }
+        }
+    }
+
+    #[derive(Deserialize, Serialize, Debug, Clone)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ClosedTabState {
+        pub state: FirefoxTab,
+        pub title: Option<String>,
+        pub image: Option<String>,
+        /// The tab's index in the window before it was closed.
+        pub pos: Option<i64>,
+        /// Unix timestamp in milliseconds.
+        pub closed_at: Option<i64>,
+        pub closed_id: Option<i64>,
+        pub source_window_id: Option<String>,
+        /// Set if the tab was closed as part of a tab group.
+        pub closed_in_tab_group_id: Option<String>,
+    }
+
+    /// A tab group that was closed together with its tabs.
+    #[derive(Deserialize, Serialize, Debug, Clone)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ClosedGroup {
+        pub id: String,
+        pub name: Option<String>,
+        pub color: Option<String>,
+        pub collapsed: Option<bool>,
+        /// Unix timestamp in milliseconds.
+        pub closed_at: Option<i64>,
+        #[serde(default)]
+        pub tabs: Vec<ClosedTab>,
+    }
 }
 
 pub mod tab_data {
@@ -350,3 +493,182 @@ pub mod tab_data {
         pub custom_color: Option<String>,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    /// A window from Firefox version 136 with closed tabs and a closed tab
+    /// group.
+    const CLOSED_TABS: &str = include_str!("./test_data/closed_tabs_window.json");
+
+    /// Contains every extension key that is modeled by [`window_data::ExtensionData`]
+    /// and [`tab_data::ExtensionData`].
+    const EXTENSION_KEYS: &str = include_str!("./test_data/extension_keys.json");
+
+    /// Re-serialize `data` and check that every key from `fixture` reappears
+    /// with the same value.
+    fn assert_round_trip(fixture: &Value, data: &impl Serialize) {
+        let serialized = serde_json::to_value(data).unwrap();
+        for (key, value) in fixture.as_object().unwrap() {
+            assert_eq!(
+                serialized.get(key),
+                Some(value),
+                "the key \"{}\" wasn't serialized with the same value",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn session_stats() {
+        let tab = |pinned: bool, container: i64| {
+            serde_json::json!({
+                "entries": [{ "url": "https://example.com/", "title": "Example" }],
+                "lastAccessed": 0,
+                "pinned": pinned,
+                "hidden": false,
+                "attributes": {},
+                "userContextId": container,
+                "index": 1,
+            })
+        };
+        let window = |tabs: Vec<Value>| {
+            serde_json::json!({
+                "tabs": tabs,
+                "selected": 1,
+                "width": 800,
+                "height": 600,
+                "screenX": 0,
+                "screenY": 0,
+                "sizemode": "normal",
+            })
+        };
+        let mut closed_tabs_window: Value = serde_json::from_str(CLOSED_TABS).unwrap();
+        closed_tabs_window["tabs"] = serde_json::json!([tab(false, 0)]);
+        let session: FirefoxSessionStore = serde_json::from_value(serde_json::json!({
+            "version": ["sessionrestore", 1],
+            "windows": [
+                window(vec![tab(true, 0), tab(false, 2), tab(false, 2)]),
+                closed_tabs_window,
+            ],
+            "_closedWindows": [window(vec![tab(false, 1), tab(false, 0)])],
+            "selectedWindow": 1,
+            "session": { "lastUpdate": 0, "startTime": 0, "recentCrashes": 0 },
+            "global": {},
+        }))
+        .unwrap();
+        assert_eq!(
+            session.stats(),
+            SessionStats {
+                window_count: 2,
+                closed_window_count: 1,
+                tab_count: 4,
+                pinned_tab_count: 1,
+                closed_tab_count: 4,
+                closed_window_tab_count: 2,
+                container_tab_counts: [(2, 2)].into_iter().collect(),
+                writer_version: Some("sessionrestore 1".to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn closed_tabs_in_all_formats() {
+        let window: FirefoxWindow = serde_json::from_str(CLOSED_TABS).unwrap();
+        assert_eq!(window._closed_tabs.len(), 3);
+        assert!(matches!(
+            window._closed_tabs[2],
+            window_data::ClosedTab::Unknown(_)
+        ));
+        assert_eq!(window.closed_groups.len(), 1);
+        assert_eq!(window.closed_groups[0].name.as_deref(), Some("Recipes"));
+
+        let urls: Vec<_> = window
+            .closed_tabs()
+            .map(|tab| tab.entries[0].url.as_str())
+            .collect();
+        assert_eq!(
+            urls,
+            [
+                "https://www.mozilla.org/",
+                "https://example.com/legacy",
+                "https://example.com/soup",
+                "https://example.com/bread",
+            ]
+        );
+    }
+
+    #[test]
+    fn window_extension_keys_round_trip() {
+        let fixture: Value = serde_json::from_str(EXTENSION_KEYS).unwrap();
+        let fixture = &fixture["window"];
+        let data: window_data::ExtensionData = serde_json::from_value(fixture.clone()).unwrap();
+
+        assert!(!data.no_data);
+        assert!(data.tabview_groups.is_some());
+        assert_eq!(
+            data.tabview_group
+                .as_ref()
+                .and_then(|group| group.title.as_deref()),
+            Some("Work")
+        );
+        assert!(data.tree_style_tab_web_extension_scroll_position.is_some());
+        assert_eq!(
+            data.tab_count_in_window_title_name.as_deref(),
+            Some("Research")
+        );
+        assert!(data.tab_count_in_window_title_is_restored.is_some());
+        assert_eq!(data.other_window_name.as_deref(), Some("Reading list"));
+        assert!(data.sidebery_groups.is_some());
+
+        assert_round_trip(fixture, &data);
+    }
+
+    #[test]
+    fn tab_extension_keys_round_trip() {
+        let fixture: Value = serde_json::from_str(EXTENSION_KEYS).unwrap();
+        let fixture = &fixture["tab"];
+        let data: tab_data::ExtensionData = serde_json::from_value(fixture.clone()).unwrap();
+
+        assert!(!data.no_data);
+        assert_eq!(data.tabview_tab.as_ref().map(|tab| tab.group_id), Some(1));
+        assert!(data.treestyletab_id.is_some());
+        assert!(data.treestyletab_subtree_collapsed.is_some());
+        assert!(data.treestyletab_insert_after.is_some());
+        assert!(data.treestyletab_insert_before.is_some());
+        assert!(data.treestyletab_parent.is_some());
+        assert_eq!(
+            data.tree_style_tab_web_extension_id
+                .as_ref()
+                .map(|id| id.id.as_str()),
+            Some("tab-1612345678-42")
+        );
+        assert!(data.tree_style_tab_web_extension_insert_before.is_some());
+        assert!(data.tree_style_tab_web_extension_insert_after.is_some());
+        assert_eq!(
+            data.tree_style_tabs_web_extension_subtree_collapsed,
+            Some(true)
+        );
+        assert_eq!(
+            data.tree_style_tabs_web_extension_ancestors.as_deref(),
+            Some(&["tab-1612345678-40".to_owned()][..])
+        );
+        assert_eq!(
+            data.tree_style_tabs_web_extension_children
+                .as_ref()
+                .map(Vec::len),
+            Some(2)
+        );
+        assert!(data.marked_for_removal.is_some());
+        assert_eq!(
+            data.sidebery_data
+                .as_ref()
+                .and_then(|data| data.custom_title.as_deref()),
+            Some("Notes")
+        );
+
+        assert_round_trip(fixture, &data);
+    }
+}
@@ -0,0 +1,170 @@
+//! A simple JSON format for exported tabs that keeps enough structure to
+//! rebuild a minimal sessionstore file from it, so that an edited export can be
+//! loaded by Firefox again.
+
+use crate::session_info::TabGroup;
+use crate::to_links::data_uri_mime_type;
+use serde::{Deserialize, Serialize};
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Windows and tabs exported from a session.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionExport {
+    pub windows: Vec<ExportedWindow>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportedWindow {
+    /// Windows are ordered by this index when a session is built. Only the
+    /// relative order matters so windows can be moved by editing it.
+    pub index: usize,
+    /// The name of the group the window was exported as. This is only
+    /// informational and isn't restored.
+    #[serde(default)]
+    pub name: String,
+    pub tabs: Vec<ExportedTab>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportedTab {
+    /// Tabs are ordered by this index inside their window when a session is
+    /// built. Only the relative order matters.
+    pub index: usize,
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+    /// `true` if this is the active tab of its window.
+    #[serde(default)]
+    pub selected: bool,
+    #[serde(default)]
+    pub pinned: bool,
+    /// The tab's `userContextId`, `0` for tabs that aren't in a container.
+    #[serde(default)]
+    pub container: i64,
+    /// The tab's favicon, see [`TabInfo::image`](crate::session_info::TabInfo::image).
+    /// Only the MIME type is exported for favicons that are `data:` URIs
+    /// unless the whole favicon was requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+}
+
+/// The serialized system principal that Firefox uses as the triggering
+/// principal for history entries it created itself. Firefox refuses to restore
+/// entries without a triggering principal.
+const SYSTEM_PRINCIPAL: &str = r#"{"3":{}}"#;
+
+impl SessionExport {
+    /// Export the tabs of some groups with each group as a window.
+    ///
+    /// Favicons that are `data:` URIs are exported as just their MIME type
+    /// unless `full_favicon` is `true`, since the image data can be large.
+    pub fn from_groups(groups: &[TabGroup<'_>], full_favicon: bool) -> Self {
+        let windows = groups
+            .iter()
+            .enumerate()
+            .map(|(index, group)| ExportedWindow {
+                index,
+                name: group.name().to_owned(),
+                tabs: group
+                    .tabs()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, tab)| ExportedTab {
+                        index,
+                        url: tab.url().to_owned(),
+                        title: tab.title().to_owned(),
+                        selected: tab.is_selected(),
+                        pinned: tab.is_pinned(),
+                        container: tab.data.user_context_id,
+                        favicon: tab
+                            .image()
+                            .map(|image| match data_uri_mime_type(image) {
+                                Some(mime_type) if !full_favicon => mime_type,
+                                _ => image,
+                            })
+                            .map(str::to_owned),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self { windows }
+    }
+
+    /// Build the JSON for a minimal sessionstore file with these windows and
+    /// tabs. The result can be deserialized as a
+    /// [`FirefoxSessionStore`](crate::FirefoxSessionStore).
+    ///
+    /// Windows without tabs are skipped. Pinned tabs are moved before other
+    /// tabs since Firefox always shows them first. Tabs with an empty URL are
+    /// restored as "about:blank".
+    pub fn to_session_json(&self) -> serde_json::Value {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis() as i64);
+
+        let mut windows = self
+            .windows
+            .iter()
+            .filter(|window| !window.tabs.is_empty())
+            .collect::<Vec<_>>();
+        windows.sort_by_key(|window| window.index);
+
+        let windows = windows
+            .into_iter()
+            .map(|window| {
+                let mut tabs = window.tabs.iter().collect::<Vec<_>>();
+                tabs.sort_by_key(|tab| (!tab.pinned, tab.index));
+
+                // 1-based index of the selected tab:
+                let selected = tabs.iter().position(|tab| tab.selected).unwrap_or(0) + 1;
+                let tabs = tabs
+                    .into_iter()
+                    .map(|tab| {
+                        let url = if tab.url.is_empty() {
+                            "about:blank"
+                        } else {
+                            &tab.url
+                        };
+                        serde_json::json!({
+                            "entries": [{
+                                "url": url,
+                                "title": tab.title,
+                                "triggeringPrincipal_base64": SYSTEM_PRINCIPAL,
+                            }],
+                            "lastAccessed": now,
+                            "pinned": tab.pinned,
+                            "hidden": false,
+                            "attributes": {},
+                            "userContextId": tab.container,
+                            "index": 1,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::json!({
+                    "tabs": tabs,
+                    "selected": selected,
+                    "_closedTabs": [],
+                    "width": 1280,
+                    "height": 720,
+                    "screenX": 0,
+                    "screenY": 0,
+                    "sizemode": "normal",
+                })
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::json!({
+            "version": ["sessionrestore", 1],
+            "windows": windows,
+            "_closedWindows": [],
+            "selectedWindow": 1,
+            "session": {
+                "lastUpdate": now,
+                "startTime": now,
+                "recentCrashes": 0,
+            },
+            "global": {},
+        })
+    }
+}
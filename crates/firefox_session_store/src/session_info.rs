@@ -5,15 +5,20 @@ use either::*;
 use super::group_tab::GroupTabInfo;
 use crate as session_store;
 
+use log::warn;
+use serde::Serialize;
+
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
-use std::iter;
+use std::{fmt, iter};
 
 #[derive(Debug, Clone)]
 pub struct TabGroup<'a> {
     name: Cow<'a, str>,
     tabs: Vec<TabInfo<'a>>,
     is_closed: bool,
+    window: Option<WindowInfo<'a>>,
 }
 impl<'a> TabGroup<'a> {
     pub fn new(name: impl Into<Cow<'a, str>>, tabs: Vec<TabInfo<'a>>, is_closed: bool) -> Self {
@@ -21,8 +26,14 @@ impl<'a> TabGroup<'a> {
             name: name.into(),
             tabs,
             is_closed,
+            window: None,
         }
     }
+    /// Remember the window that this group was created from.
+    pub fn with_window(mut self, window: WindowInfo<'a>) -> Self {
+        self.window = Some(window);
+        self
+    }
     pub fn name(&self) -> &str {
         self.name.as_ref()
     }
@@ -32,6 +43,58 @@ impl<'a> TabGroup<'a> {
     pub fn is_closed(&self) -> bool {
         self.is_closed
     }
+    /// The window that this group represents, if it was created from one.
+    pub fn window(&self) -> Option<WindowInfo<'a>> {
+        self.window
+    }
+    /// Only keep the tabs for which `f` returns `true`.
+    pub fn retain_tabs(&mut self, f: impl FnMut(&TabInfo<'a>) -> bool) {
+        self.tabs.retain(f);
+    }
+    /// Split this group into the tab groups of Firefox's old Panorama (also
+    /// called TabView) feature. The new groups are named after this group and
+    /// the Panorama group's title, or its id if the title isn't known. Tabs
+    /// that aren't in a Panorama group are kept in a group with this group's
+    /// name.
+    ///
+    /// The groups are ordered by their first tab. Returns this group unchanged
+    /// if none of its tabs are in a Panorama group.
+    pub fn split_by_tabview_groups(self) -> Vec<TabGroup<'a>> {
+        if self.tabs.iter().all(|tab| tab.tabview_group_id().is_none()) {
+            return vec![self];
+        }
+        let mut group_ids = Vec::<Option<i64>>::new();
+        let mut group_tabs = Vec::<Vec<TabInfo<'a>>>::new();
+        for tab in &self.tabs {
+            let group_id = tab.tabview_group_id();
+            match group_ids.iter().position(|&id| id == group_id) {
+                Some(index) => group_tabs[index].push(*tab),
+                None => {
+                    group_ids.push(group_id);
+                    group_tabs.push(vec![*tab]);
+                }
+            }
+        }
+        group_ids
+            .into_iter()
+            .zip(group_tabs)
+            .map(|(group_id, tabs)| {
+                let name = match group_id {
+                    Some(id) => match self.window.and_then(|w| w.tabview_group_title(id)) {
+                        Some(title) => Cow::Owned(format!("{} - {}", self.name, title)),
+                        None => Cow::Owned(format!("{} - Group {}", self.name, id)),
+                    },
+                    None => self.name.clone(),
+                };
+                TabGroup {
+                    name,
+                    tabs,
+                    is_closed: self.is_closed,
+                    window: self.window,
+                }
+            })
+            .collect()
+    }
 }
 
 fn sort_groups(mut groups: Vec<TabGroup<'_>>) -> Vec<TabGroup<'_>> {
@@ -39,38 +102,113 @@ fn sort_groups(mut groups: Vec<TabGroup<'_>>) -> Vec<TabGroup<'_>> {
     groups
 }
 
-/// Get tabs in groups for a given Firefox session.
-pub fn get_groups_from_session(
-    session_data: &session_store::FirefoxSessionStore,
-    include_open_windows: bool,
-    include_closed_windows: bool,
-    sort_names: bool,
-) -> impl Iterator<Item = TabGroup<'_>> {
-    let open_windows = session_data
+/// Append an index to group names that are used by more than one group.
+fn deduplicate_group_names<'a, 'b: 'a>(groups: impl IntoIterator<Item = &'a mut TabGroup<'b>>) {
+    let mut groups = groups.into_iter().collect::<Vec<_>>();
+    let mut name_counts = HashMap::<String, usize>::new();
+    for group in &groups {
+        *name_counts.entry(group.name().to_owned()).or_default() += 1;
+    }
+    let mut name_indexes = HashMap::<String, usize>::new();
+    for group in &mut groups {
+        if name_counts[group.name()] < 2 {
+            continue;
+        }
+        let index = name_indexes.entry(group.name().to_owned()).or_default();
+        *index += 1;
+        group.name = format!("{} ({})", group.name, index).into();
+    }
+}
+
+/// Determines how windows are named if they don't have a name provided by an
+/// extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowNaming {
+    /// Name windows after their index, for example "Window 1".
+    #[default]
+    Index,
+    /// Name windows after the title of their selected tab. Falls back to
+    /// naming the window after its index if the tab has no title. An index is
+    /// appended to windows that would otherwise have the same name.
+    ActiveTab,
+}
+
+/// Options for [`get_groups_from_session`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupOptions {
+    /// Include a group for each open window.
+    pub include_open_windows: bool,
+    /// Include a group for each recently closed window.
+    pub include_closed_windows: bool,
+    /// Sort the groups by name, groups with the same name keep their original
+    /// order. Open and closed windows are sorted separately.
+    pub sort_names: bool,
+    /// List closed windows before open windows.
+    pub closed_first: bool,
+    /// How windows without a name from an extension are named.
+    pub naming: WindowNaming,
+}
+impl Default for GroupOptions {
+    /// Only open windows in the same order as in the session.
+    fn default() -> Self {
+        Self {
+            include_open_windows: true,
+            include_closed_windows: false,
+            sort_names: false,
+            closed_first: false,
+            naming: WindowNaming::Index,
+        }
+    }
+}
+
+/// Get tabs in groups for a given Firefox session. Open windows come before
+/// closed windows unless [`GroupOptions::closed_first`] is `true`.
+///
+/// The groups are otherwise in the same order as the windows in the session
+/// unless [`GroupOptions::sort_names`] is `true`.
+pub fn get_groups_from_session<'a>(
+    session_data: &'a session_store::FirefoxSessionStore,
+    options: &GroupOptions,
+) -> impl Iterator<Item = TabGroup<'a>> {
+    let GroupOptions {
+        include_open_windows,
+        include_closed_windows,
+        sort_names,
+        closed_first,
+        naming,
+    } = *options;
+    let mut open_windows = session_data
         .windows
         .iter()
         .filter(move |_| include_open_windows)
         .enumerate()
         .map(|(index, window)| {
-            WindowInfo::new(window, false).as_group(format!("Window {}", index + 1))
-        });
-    let closed_windows = session_data
+            WindowInfo::new(window, false).as_named_group(naming, format!("Window {}", index + 1))
+        })
+        .collect::<Vec<_>>();
+    let mut closed_windows = session_data
         ._closed_windows
         .iter()
         .filter(move |_| include_closed_windows)
         .enumerate()
         .map(|(index, window)| {
-            WindowInfo::new(window, true).as_group(format!("Closed window {}", index + 1))
-        });
+            WindowInfo::new(window, true)
+                .as_named_group(naming, format!("Closed window {}", index + 1))
+        })
+        .collect::<Vec<_>>();
 
+    if naming == WindowNaming::ActiveTab {
+        deduplicate_group_names(open_windows.iter_mut().chain(&mut closed_windows));
+    }
     if sort_names {
-        Left(
-            sort_groups(open_windows.collect())
-                .into_iter()
-                .chain(sort_groups(closed_windows.collect())),
-        )
+        open_windows = sort_groups(open_windows);
+        closed_windows = sort_groups(closed_windows);
+    }
+
+    if closed_first {
+        Left(closed_windows.into_iter().chain(open_windows))
     } else {
-        Right(open_windows.chain(closed_windows))
+        Right(open_windows.into_iter().chain(closed_windows))
     }
 }
 
@@ -111,21 +249,232 @@ impl<'a> WindowInfo<'a> {
         }
     }
 
+    /// The title of a tab group from Firefox's old Panorama (also called
+    /// TabView) feature. Only the title of the group that is stored in the
+    /// window's `tabview-group` data is known.
+    pub fn tabview_group_title(&self, group_id: i64) -> Option<&'a str> {
+        let group = self.data.ext_data.tabview_group.as_ref()?;
+        if group.id != Some(group_id) {
+            return None;
+        }
+        group.title.as_deref().filter(|title| !title.is_empty())
+    }
+
+    /// The size and position of the window.
+    pub fn geometry(&self) -> WindowGeometry<'a> {
+        WindowGeometry {
+            width: self.data.width,
+            height: self.data.height,
+            screen_x: self.data.screen_x,
+            screen_y: self.data.screen_y,
+            sizemode: &self.data.sizemode,
+        }
+    }
+
+    /// The window's selected tab.
+    pub fn selected_tab(&self) -> Option<TabInfo<'a>> {
+        let index = usize::try_from(self.data.selected.checked_sub(1)?).ok()?;
+        Some(TabInfo::with_window(self.data.tabs.get(index)?, *self))
+    }
+
     pub fn as_group(&self, default_name: impl Into<Cow<'a, str>>) -> TabGroup<'a> {
         TabGroup::new(
             self.name().unwrap_or_else(|| default_name.into()),
             self.tabs_iter().collect(),
             self.is_closed,
         )
+        .with_window(*self)
+    }
+
+    /// Like [`WindowInfo::as_group`] but the default name can be determined
+    /// by the window's content.
+    pub fn as_named_group(
+        &self,
+        naming: WindowNaming,
+        default_name: impl Into<Cow<'a, str>>,
+    ) -> TabGroup<'a> {
+        let name = match naming {
+            WindowNaming::Index => None,
+            WindowNaming::ActiveTab => self
+                .selected_tab()
+                .map(|tab| tab.title())
+                .filter(|title| !title.is_empty()),
+        };
+        match name {
+            Some(name) => self.as_group(name),
+            None => self.as_group(default_name),
+        }
     }
 
     /// Iterate over the window's tabs.
     pub fn tabs_iter(&self) -> impl Iterator<Item = TabInfo<'a>> {
         let window = *self;
-        self.data.tabs.iter().map(move |tab| TabInfo {
-            data: tab,
-            window: Some(window),
-        })
+        self.data
+            .tabs
+            .iter()
+            .map(move |tab| TabInfo::with_window(tab, window))
+    }
+
+    /// Arrange the window's tabs in a tree using tree data from Tree Style Tab
+    /// like extensions. Returns the root tabs in the same order as they have in
+    /// the window and every tab's children are also kept in that order.
+    ///
+    /// Tabs without a parent in this window become roots. If the tree data has
+    /// a cycle then one tab in the cycle becomes a root and a warning is
+    /// logged, so every tab is included exactly once.
+    pub fn tab_tree(&self, tree_sources: &[TreeDataSource]) -> Vec<TabTreeNode<'a>> {
+        let tabs = self.tabs_iter().collect::<Vec<_>>();
+        let mut parents = tabs
+            .iter()
+            .map(|tab| {
+                let parent = tab.tst_parent(tree_sources)?.value;
+                tabs.iter()
+                    .position(|other| std::ptr::eq(other.data, parent.data))
+            })
+            .collect::<Vec<_>>();
+
+        // Break cycles by making a tab a root if it is its own ancestor:
+        for index in 0..tabs.len() {
+            let mut current = parents[index];
+            for _ in 0..tabs.len() {
+                let Some(parent) = current else { break };
+                if parent == index {
+                    warn!(
+                        r#"Tab tree data has a cycle, the tab with the title "{}" and the URL "{}" will be treated as a root tab"#,
+                        tabs[index].title(),
+                        tabs[index].url()
+                    );
+                    parents[index] = None;
+                    break;
+                }
+                current = parents[parent];
+            }
+        }
+
+        let mut children = vec![Vec::new(); tabs.len()];
+        let mut roots = Vec::new();
+        for (index, parent) in parents.iter().enumerate() {
+            match parent {
+                Some(parent) => children[*parent].push(index),
+                None => roots.push(index),
+            }
+        }
+
+        fn build<'a>(
+            index: usize,
+            tabs: &[TabInfo<'a>],
+            children: &[Vec<usize>],
+        ) -> TabTreeNode<'a> {
+            TabTreeNode {
+                tab: tabs[index],
+                children: children[index]
+                    .iter()
+                    .map(|&child| build(child, tabs, children))
+                    .collect(),
+            }
+        }
+        roots
+            .into_iter()
+            .map(|root| build(root, &tabs, &children))
+            .collect()
+    }
+}
+
+/// A tab together with the tabs that are nested under it in a tab tree, see
+/// [`WindowInfo::tab_tree`].
+#[derive(Clone, Debug)]
+pub struct TabTreeNode<'data> {
+    pub tab: TabInfo<'data>,
+    pub children: Vec<TabTreeNode<'data>>,
+}
+impl<'data> TabTreeNode<'data> {
+    /// The number of tabs nested under this tab, including indirect children.
+    pub fn descendant_count(&self) -> usize {
+        self.children
+            .iter()
+            .map(|child| 1 + child.descendant_count())
+            .sum()
+    }
+}
+
+/// Arrange the tabs of all open windows in trees, see [`WindowInfo::tab_tree`].
+/// The roots of every window are returned after each other, use
+/// [`TabInfo::window`] to find out which window a tab is part of.
+pub fn build_tab_tree<'a>(
+    session: &'a session_store::FirefoxSessionStore,
+    tree_sources: &[TreeDataSource],
+) -> Vec<TabTreeNode<'a>> {
+    session
+        .windows
+        .iter()
+        .flat_map(|window| WindowInfo::new(window, false).tab_tree(tree_sources))
+        .collect()
+}
+
+/// The size and position of a window, see [`WindowInfo::geometry`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct WindowGeometry<'a> {
+    pub width: i64,
+    pub height: i64,
+    pub screen_x: i64,
+    pub screen_y: i64,
+    /// For example "normal", "maximized" or "minimized".
+    pub sizemode: &'a str,
+}
+impl fmt::Display for WindowGeometry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} at ({}, {}), {}",
+            self.width, self.height, self.screen_x, self.screen_y, self.sizemode
+        )
+    }
+}
+
+/// Window data deserialized from a [`serde_json::Value`]. Use
+/// [`OwnedWindowData::info`] to get info about the window without needing to
+/// deserialize a whole [`session_store::FirefoxSessionStore`].
+#[derive(Clone, Debug)]
+pub struct OwnedWindowData {
+    pub data: session_store::FirefoxWindow,
+    pub is_closed: bool,
+}
+impl OwnedWindowData {
+    pub fn new(data: session_store::FirefoxWindow, is_closed: bool) -> Self {
+        Self { data, is_closed }
+    }
+    /// Deserialize a window from a JSON value, for example an item in a
+    /// session's `windows` or `_closedWindows` array.
+    pub fn from_value(value: &serde_json::Value, is_closed: bool) -> serde_json::Result<Self> {
+        Ok(Self::new(
+            serde::Deserialize::deserialize(value)?,
+            is_closed,
+        ))
+    }
+    pub fn info(&self) -> WindowInfo<'_> {
+        WindowInfo::new(&self.data, self.is_closed)
+    }
+}
+
+/// Tab data deserialized from a [`serde_json::Value`]. Use
+/// [`OwnedTabData::info`] to get info about the tab such as its title and URL.
+#[derive(Clone, Debug)]
+pub struct OwnedTabData {
+    pub data: session_store::FirefoxTab,
+}
+impl OwnedTabData {
+    pub fn new(data: session_store::FirefoxTab) -> Self {
+        Self { data }
+    }
+    /// Deserialize a tab from a JSON value, for example an item in a window's
+    /// `tabs` array.
+    pub fn from_value(value: &serde_json::Value) -> serde_json::Result<Self> {
+        Ok(Self::new(serde::Deserialize::deserialize(value)?))
+    }
+    /// Info about the tab. Note that the info won't have an associated window
+    /// so tree data can't be resolved, use [`OwnedWindowData`] for that.
+    pub fn info(&self) -> TabInfo<'_> {
+        TabInfo::new(&self.data)
     }
 }
 
@@ -142,6 +491,29 @@ impl<'data> TabInfo<'data> {
             window: None,
         }
     }
+    /// Create an info object for a tab that is part of the specified window.
+    pub fn with_window(
+        tab_data: &'data session_store::FirefoxTab,
+        window: WindowInfo<'data>,
+    ) -> Self {
+        Self {
+            data: tab_data,
+            window: Some(window),
+        }
+    }
+
+    /// `true` if this is the selected (active) tab of its window. Always
+    /// `false` for tabs that don't know what window they are part of.
+    pub fn is_selected(&self) -> bool {
+        self.window
+            .and_then(|window| window.selected_tab())
+            .is_some_and(|selected| std::ptr::eq(selected.data, self.data))
+    }
+
+    /// `true` if this tab is pinned to the start of its window's tab bar.
+    pub fn is_pinned(&self) -> bool {
+        self.data.pinned.unwrap_or(false)
+    }
 
     /// The index of the current history entry. The other entries represents the tabs history.
     pub fn current_entry_index(&self) -> Option<usize> {
@@ -158,6 +530,12 @@ impl<'data> TabInfo<'data> {
         self.data.entries.get(self.current_entry_index()?)
     }
 
+    /// The id of the tab group from Firefox's old Panorama (also called
+    /// TabView) feature that this tab belongs to.
+    pub fn tabview_group_id(&self) -> Option<i64> {
+        Some(self.data.ext_data.tabview_tab.as_ref()?.group_id)
+    }
+
     /// The title for this tab.
     pub fn title(&self) -> &'data str {
         self.current_entry()
@@ -171,6 +549,26 @@ impl<'data> TabInfo<'data> {
             .unwrap_or_default()
     }
 
+    /// Text that was typed into the tab's address bar but never navigated to,
+    /// for example a half typed URL. `None` if nothing was typed or if Firefox
+    /// started loading the typed text (indicated by `userTypedClear`) since
+    /// then the text is part of the tab's history instead.
+    pub fn typed_value(&self) -> Option<&'data str> {
+        if self.data.user_typed_clear.is_some_and(|clear| clear > 0) {
+            return None;
+        }
+        self.data
+            .user_typed_value
+            .as_deref()
+            .filter(|value| !value.trim().is_empty() && *value != self.url())
+    }
+
+    /// The tab's favicon, either a URL or a `data:` URI with the image data.
+    /// `None` if the tab doesn't have a favicon.
+    pub fn image(&self) -> Option<&'data str> {
+        self.data.image.as_deref().filter(|image| !image.is_empty())
+    }
+
     pub fn scroll(&self) -> Option<&'data str> {
         let scroll_info = self.data.scroll.as_ref()?;
 
@@ -211,6 +609,21 @@ impl<'data> TabInfo<'data> {
         .preform(tree_sources)
     }
 
+    /// `true` if Tree Style Tab has collapsed this tab's subtree so that its
+    /// descendants are hidden in the sidebar.
+    pub fn tst_subtree_collapsed(&self) -> bool {
+        let ext_data = &self.data.ext_data;
+        ext_data
+            .tree_style_tabs_web_extension_subtree_collapsed
+            .or_else(|| {
+                ext_data
+                    .treestyletab_subtree_collapsed
+                    .as_deref()
+                    .map(|collapsed| collapsed == "true")
+            })
+            .unwrap_or(false)
+    }
+
     pub fn tst_web_ext_id(&self) -> Option<&'data str> {
         self.data
             .ext_data
@@ -234,19 +647,49 @@ impl<'data> TabInfo<'data> {
         self.data.ext_data.treestyletab_parent.as_deref()
     }
 
+    /// Get this tab's parent tab using tree data. This is the same as the
+    /// first tab returned by [`TabInfo::tst_ancestor_tabs`].
+    pub fn tst_parent(
+        &self,
+        tree_sources: &[TreeDataSource],
+    ) -> Option<TreeDataOutput<TabInfo<'data>>> {
+        self.tst_ancestor_tabs(tree_sources).next()
+    }
+
     /// Get the ancestor tabs of this tab using Tree Style Tab session data. The first tab in the iterator will be this tab's parent tab.
+    ///
+    /// Parent tabs are searched for in the tab's associated window. If the tab
+    /// doesn't know its window then the iterator will be empty.
+    ///
+    /// If the tree data has a cycle (a tab that is its own ancestor) then the
+    /// iterator ends before the cycle repeats and a warning is logged.
     pub fn tst_ancestor_tabs<'iter>(
         &'iter self,
         mut tree_sources: &'iter [TreeDataSource],
-        window: WindowInfo<'data>,
     ) -> impl Iterator<Item = TreeDataOutput<TabInfo<'data>>> + 'iter {
         let mut current_tab = *self;
+        let window = self.window;
+        let mut visited = HashSet::new();
+        if let Some(id) = self.tst_id(tree_sources) {
+            visited.insert(id.value);
+        }
         iter::from_fn(move || {
+            let window = window?;
             if tree_sources.is_empty() {
                 return None;
             }
             let parent_id = current_tab.tst_parent_id(tree_sources)?;
 
+            if !visited.insert(parent_id.value) {
+                warn!(
+                    r#"Tab tree data has a cycle, the tab with the title "{}" and the URL "{}" is its own ancestor"#,
+                    self.title(),
+                    self.url()
+                );
+                tree_sources = &[];
+                return None;
+            }
+
             if tree_sources.len() > 1 {
                 // Only allow fallback when resolving first parent as to prevent infinite loops and other strange behaviors.
                 let ix = tree_sources
@@ -276,7 +719,7 @@ impl<'data> TabInfo<'data> {
 }
 
 /// An id for a tab used by Tree Style Tab like extensions.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TreeTabId<'a> {
     /// Used by Tree Style Tab (both legacy and WebExtension versions.)
     Text(&'a str),
@@ -375,3 +818,255 @@ impl TreeDataSource {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn legacy_tst_tab(id: &str, parent: &str) -> serde_json::Value {
+        json!({
+            "entries": [{ "url": format!("https://example.com/{id}"), "title": id }],
+            "lastAccessed": 0,
+            "hidden": false,
+            "attributes": {},
+            "extData": { "treestyletab-id": id, "treestyletab-parent": parent },
+            "userContextId": 0,
+            "index": 1,
+        })
+    }
+
+    fn window_with_tabs(tabs: Vec<serde_json::Value>) -> OwnedWindowData {
+        let window = json!({
+            "tabs": tabs,
+            "selected": 1,
+            "width": 800,
+            "height": 600,
+            "screenX": 0,
+            "screenY": 0,
+            "sizemode": "normal",
+        });
+        OwnedWindowData::from_value(&window, false).unwrap()
+    }
+
+    #[test]
+    fn typed_value_ignores_cleared_and_current_url() {
+        let tab = |typed: &str, clear: Option<i64>| {
+            let mut tab = legacy_tst_tab("a", "");
+            tab["userTypedValue"] = json!(typed);
+            tab["userTypedClear"] = json!(clear);
+            OwnedTabData::from_value(&tab).unwrap()
+        };
+        assert_eq!(
+            tab("https://exam", None).info().typed_value(),
+            Some("https://exam")
+        );
+        assert_eq!(
+            tab("https://exam", Some(0)).info().typed_value(),
+            Some("https://exam")
+        );
+        assert_eq!(tab("https://exam", Some(1)).info().typed_value(), None);
+        assert_eq!(
+            tab("https://example.com/a", None).info().typed_value(),
+            None
+        );
+        assert_eq!(tab("", None).info().typed_value(), None);
+    }
+
+    #[test]
+    fn name_windows_by_active_tab() {
+        let mut session: session_store::FirefoxSessionStore = serde_json::from_value(json!({
+            "version": ["sessionrestore", 1],
+            "windows": [],
+            "selectedWindow": 1,
+            "session": { "lastUpdate": 0, "startTime": 0, "recentCrashes": 0 },
+            "global": {},
+        }))
+        .unwrap();
+        session.windows = ["a", "b", "a", ""]
+            .into_iter()
+            .map(|title| window_with_tabs(vec![legacy_tst_tab(title, "")]).data)
+            .collect();
+        let names = get_groups_from_session(
+            &session,
+            &GroupOptions {
+                naming: WindowNaming::ActiveTab,
+                ..Default::default()
+            },
+        )
+        .map(|group| group.name().to_owned())
+        .collect::<Vec<_>>();
+        assert_eq!(names, ["a (1)", "b", "a (2)", "Window 4"]);
+    }
+
+    #[test]
+    fn only_the_windows_selected_tab_is_selected() {
+        let window = window_with_tabs(vec![legacy_tst_tab("a", ""), legacy_tst_tab("b", "")]);
+        let selected = window
+            .info()
+            .tabs_iter()
+            .map(|tab| tab.is_selected())
+            .collect::<Vec<_>>();
+        assert_eq!(selected, [true, false]);
+
+        let tab = OwnedTabData::from_value(&legacy_tst_tab("a", "")).unwrap();
+        assert!(!tab.info().is_selected());
+    }
+
+    #[test]
+    fn tst_ancestor_tabs_follows_parents() {
+        let window = window_with_tabs(vec![
+            legacy_tst_tab("a", ""),
+            legacy_tst_tab("b", "a"),
+            legacy_tst_tab("c", "b"),
+        ]);
+        let tabs = window.info().tabs_iter().collect::<Vec<_>>();
+        let ancestors = tabs[2]
+            .tst_ancestor_tabs(&[TreeDataSource::TstLegacy])
+            .map(|tab| tab.value.title())
+            .collect::<Vec<_>>();
+        assert_eq!(ancestors, ["b", "a"]);
+    }
+
+    #[test]
+    fn tst_ancestor_tabs_stops_at_self_cycle() {
+        let window = window_with_tabs(vec![legacy_tst_tab("a", "a")]);
+        let tab = window.info().tabs_iter().next().unwrap();
+        assert_eq!(
+            tab.tst_ancestor_tabs(&[TreeDataSource::TstLegacy]).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn tst_ancestor_tabs_stops_at_cycle() {
+        let window = window_with_tabs(vec![
+            legacy_tst_tab("a", "c"),
+            legacy_tst_tab("b", "a"),
+            legacy_tst_tab("c", "b"),
+        ]);
+        let tabs = window.info().tabs_iter().collect::<Vec<_>>();
+        let ancestors = tabs[2]
+            .tst_ancestor_tabs(&[TreeDataSource::TstLegacy])
+            .map(|tab| tab.value.title())
+            .collect::<Vec<_>>();
+        assert_eq!(ancestors, ["b", "a"]);
+    }
+
+    #[test]
+    fn tst_ancestor_tabs_without_window_is_empty() {
+        let tab = OwnedTabData::from_value(&legacy_tst_tab("b", "a")).unwrap();
+        assert_eq!(
+            tab.info()
+                .tst_ancestor_tabs(&[TreeDataSource::TstLegacy])
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn tab_tree_nests_children() {
+        let window = window_with_tabs(vec![
+            legacy_tst_tab("a", ""),
+            legacy_tst_tab("b", "a"),
+            legacy_tst_tab("c", "b"),
+            legacy_tst_tab("d", "a"),
+            legacy_tst_tab("e", "missing"),
+        ]);
+        fn format_tree(nodes: &[TabTreeNode<'_>]) -> String {
+            nodes
+                .iter()
+                .map(|node| {
+                    if node.children.is_empty() {
+                        node.tab.title().to_owned()
+                    } else {
+                        format!("{}({})", node.tab.title(), format_tree(&node.children))
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        let tree = window.info().tab_tree(&[TreeDataSource::TstLegacy]);
+        assert_eq!(format_tree(&tree), "a(b(c) d) e");
+        assert_eq!(tree[0].descendant_count(), 3);
+    }
+
+    #[test]
+    fn tab_tree_breaks_cycles() {
+        let window = window_with_tabs(vec![
+            legacy_tst_tab("a", "c"),
+            legacy_tst_tab("b", "a"),
+            legacy_tst_tab("c", "b"),
+            legacy_tst_tab("d", "d"),
+        ]);
+        let tree = window.info().tab_tree(&[TreeDataSource::TstLegacy]);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(
+            tree.iter()
+                .map(|node| 1 + node.descendant_count())
+                .sum::<usize>(),
+            4
+        );
+    }
+
+    #[test]
+    fn tabview_groups_split_windows() {
+        let tab = |title: &str, group_id: Option<i64>| {
+            let mut tab = legacy_tst_tab(title, "");
+            if let Some(group_id) = group_id {
+                tab["extData"]["tabview-tab"] = json!(format!(r#"{{"groupID":{group_id}}}"#));
+            }
+            tab
+        };
+        let mut window = window_with_tabs(vec![
+            tab("a", Some(1)),
+            tab("b", Some(2)),
+            tab("c", Some(1)),
+            tab("d", None),
+        ]);
+        window.data.ext_data = serde_json::from_value(
+            json!({ "tabview-group": r#"{"slot":1,"title":"Work","id":1}"# }),
+        )
+        .unwrap();
+
+        let groups = window
+            .info()
+            .as_group("Window 1")
+            .split_by_tabview_groups()
+            .into_iter()
+            .map(|group| {
+                let titles = group
+                    .tabs()
+                    .iter()
+                    .map(|tab| tab.title())
+                    .collect::<Vec<_>>();
+                (group.name().to_owned(), titles)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            [
+                ("Window 1 - Work".to_owned(), vec!["a", "c"]),
+                ("Window 1 - Group 2".to_owned(), vec!["b"]),
+                ("Window 1".to_owned(), vec!["d"]),
+            ]
+        );
+
+        let window = window_with_tabs(vec![tab("a", None)]);
+        let groups = window.info().as_group("Window 1").split_by_tabview_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name(), "Window 1");
+    }
+
+    #[test]
+    fn window_groups_know_their_geometry() {
+        let window = window_with_tabs(vec![legacy_tst_tab("a", "")]);
+        let group = window.info().as_group("Window");
+        let geometry = group.window().unwrap().geometry();
+        assert_eq!(
+            (geometry.width, geometry.height, geometry.sizemode),
+            (800, 600, "normal")
+        );
+        assert_eq!(geometry.to_string(), "800x600 at (0, 0), normal");
+    }
+}
@@ -0,0 +1,82 @@
+//! Counts of windows and tabs in a Firefox session, useful as a quick
+//! overview of a sessionstore file.
+
+use serde::Serialize;
+
+use crate::FirefoxSessionStore;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// High level info about a session, see [`FirefoxSessionStore::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SessionStats {
+    pub window_count: usize,
+    pub closed_window_count: usize,
+    /// Tabs in open windows.
+    pub tab_count: usize,
+    /// Pinned tabs in open windows.
+    pub pinned_tab_count: usize,
+    /// Recently closed tabs in open windows.
+    pub closed_tab_count: usize,
+    /// Tabs in closed windows.
+    pub closed_window_tab_count: usize,
+    /// The number of tabs in open windows that use each container, keyed by
+    /// the container's `userContextId`. Tabs without a container aren't
+    /// included.
+    pub container_tab_counts: BTreeMap<i64, usize>,
+    /// See [`FirefoxSessionStore::writer_version`].
+    pub writer_version: Option<String>,
+}
+impl SessionStats {
+    pub fn new(session: &FirefoxSessionStore) -> Self {
+        Self {
+            window_count: session.window_count(),
+            closed_window_count: session.closed_window_count(),
+            tab_count: session.tab_count(),
+            pinned_tab_count: session.pinned_tab_count(),
+            closed_tab_count: session
+                .windows
+                .iter()
+                .map(|window| window.closed_tabs().count())
+                .sum(),
+            closed_window_tab_count: session
+                ._closed_windows
+                .iter()
+                .map(|window| window.tabs.len())
+                .sum(),
+            container_tab_counts: session.container_tab_counts(),
+            writer_version: session.writer_version(),
+        }
+    }
+}
+impl fmt::Display for SessionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Windows: {}", self.window_count)?;
+        writeln!(f, "Closed windows: {}", self.closed_window_count)?;
+        writeln!(f, "Tabs: {}", self.tab_count)?;
+        writeln!(f, "Pinned tabs: {}", self.pinned_tab_count)?;
+        writeln!(f, "Closed tabs: {}", self.closed_tab_count)?;
+        writeln!(
+            f,
+            "Tabs in closed windows: {}",
+            self.closed_window_tab_count
+        )?;
+        if self.container_tab_counts.is_empty() {
+            writeln!(f, "Container tabs: 0")?;
+        } else {
+            writeln!(
+                f,
+                "Container tabs: {}",
+                self.container_tab_counts.values().sum::<usize>()
+            )?;
+            for (id, count) in &self.container_tab_counts {
+                writeln!(f, "  Container {}: {}", id, count)?;
+            }
+        }
+        if let Some(version) = &self.writer_version {
+            writeln!(f, "Format version: {}", version)?;
+        }
+        Ok(())
+    }
+}
@@ -111,9 +111,42 @@ pub mod simple_html {
 }
 
 pub mod simple_rtf {
-    use std::fmt;
+    use std::borrow::Cow;
+    use std::fmt::{self, Write as _};
     use std::io::{self, Write};
 
+    /// Escape text so that it can be written inside an RTF document. Characters
+    /// outside of ASCII are written as `\uN?` control words since RTF files
+    /// can't contain raw UTF-8. The `?` is used by readers that don't support
+    /// Unicode.
+    pub fn rtf_escaped_text(text: &str) -> Cow<'_, str> {
+        if text
+            .chars()
+            .all(|c| c.is_ascii() && !matches!(c, '\\' | '{' | '}'))
+        {
+            return Cow::Borrowed(text);
+        }
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '\\' | '{' | '}' => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                c if c.is_ascii() => escaped.push(c),
+                c => {
+                    // The number is a signed 16-bit value and characters
+                    // outside the Basic Multilingual Plane are written as
+                    // UTF-16 surrogate pairs:
+                    for unit in c.encode_utf16(&mut [0; 2]) {
+                        write!(escaped, "\\u{}?", *unit as i16).unwrap();
+                    }
+                }
+            }
+        }
+        escaped.into()
+    }
+
     pub struct RTFWriter<W: Write>(Option<W>);
     impl<W: Write> Write for RTFWriter<W> {
         fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
@@ -239,14 +272,37 @@ mod simple_typst {
     }
 }
 
-use super::session_info::{TabGroup, TreeDataSource};
+mod simple_dot {
+    use std::borrow::Cow;
+
+    /// Escape text so that it can be used inside a quoted Graphviz DOT
+    /// string.
+    pub fn dot_escaped_text(text: &str) -> Cow<'_, str> {
+        if !text.contains(['\\', '"', '\n', '\r']) {
+            return Cow::Borrowed(text);
+        }
+
+        // https://graphviz.org/doc/info/lang.html
+        text.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\r', "")
+            .replace('\n', "\\n")
+            .into()
+    }
+}
+
+use super::link_export::SessionExport;
+use super::session_info::{TabGroup, TabInfo, TreeDataSource};
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
 use either::*;
+use simple_dot::dot_escaped_text;
 use simple_html::{html_escaped_text, html_horizontal_line, HTMLWriter};
-use simple_rtf::{rtf_horizontal_line, RTFWriter};
+use simple_rtf::{rtf_escaped_text, rtf_horizontal_line, RTFWriter};
 use simple_typst::typst_escaped_text;
 use std::{
     borrow::Cow,
     io::{self, Write},
+    ptr,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -259,6 +315,21 @@ pub enum LinkFormat {
     HTML,
     Markdown,
     Typst,
+    /// Write each tab using [`ToLinksOptions::template`].
+    Template,
+    /// A Graphviz DOT graph with an edge from each tab to its children in a
+    /// tab tree.
+    Dot,
+    /// Only the tabs' URLs, one per line.
+    UrlList,
+    /// A [`SessionExport`] that can be turned back into a session.
+    Json,
+    /// An SVG image of the tab trees with each tab's title indented by its
+    /// depth and lines connecting it to its parent tab.
+    Svg,
+    /// The same data as [`LinkFormat::Json`] but written as YAML. Requires the
+    /// `yaml` feature, otherwise writing links fails.
+    Yaml,
 }
 impl LinkFormat {
     #[must_use]
@@ -292,17 +363,137 @@ impl LinkFormat {
     pub fn is_typst(&self) -> bool {
         matches!(self, Self::Typst)
     }
+    #[must_use]
+    pub fn is_template(self) -> bool {
+        self == LinkFormat::Template
+    }
+    #[must_use]
+    pub fn is_dot(self) -> bool {
+        self == LinkFormat::Dot
+    }
+    #[must_use]
+    pub fn is_url_list(self) -> bool {
+        self == LinkFormat::UrlList
+    }
+    #[must_use]
+    pub fn is_json(self) -> bool {
+        self == LinkFormat::Json
+    }
+    #[must_use]
+    pub fn is_svg(self) -> bool {
+        self == LinkFormat::Svg
+    }
+    #[must_use]
+    pub fn is_yaml(self) -> bool {
+        self == LinkFormat::Yaml
+    }
 
     pub fn line_break(self) -> &'static str {
         match self {
             LinkFormat::TXT | LinkFormat::Markdown => "\n",
             LinkFormat::RTF { .. } => concat!(r#"\line"#, "\n"),
             LinkFormat::HTML => concat!("<br />", "\n"),
-            LinkFormat::Typst => "\n",
+            LinkFormat::Typst
+            | LinkFormat::Template
+            | LinkFormat::Dot
+            | LinkFormat::UrlList
+            | LinkFormat::Json
+            | LinkFormat::Svg
+            | LinkFormat::Yaml => "\n",
+        }
+    }
+
+    /// The text that is added once for each level of a tab tree, see
+    /// [`ToLinksOptions::indent_unit`].
+    pub fn default_indent_unit(self) -> &'static str {
+        match self {
+            LinkFormat::Markdown => "  ",
+            LinkFormat::HTML => "|&nbsp;&nbsp;&nbsp;&nbsp;",
+            LinkFormat::RTF { .. } => "|  ",
+            LinkFormat::TXT => "|    ",
+            LinkFormat::Typst
+            | LinkFormat::Template
+            | LinkFormat::Dot
+            | LinkFormat::UrlList
+            | LinkFormat::Json
+            | LinkFormat::Svg
+            | LinkFormat::Yaml => "  ",
+        }
+    }
+
+    /// The text that is written right before a tab that has a parent in a tab
+    /// tree, see [`ToLinksOptions::tree_connector`].
+    pub fn default_tree_connector(self) -> &'static str {
+        match self {
+            LinkFormat::Markdown => "",
+            LinkFormat::RTF { .. } | LinkFormat::HTML => "|---",
+            LinkFormat::TXT => "|--- ",
+            LinkFormat::Typst => "- ",
+            LinkFormat::Template
+            | LinkFormat::Dot
+            | LinkFormat::UrlList
+            | LinkFormat::Json
+            | LinkFormat::Svg
+            | LinkFormat::Yaml => "",
         }
     }
 }
 
+/// Colors used by the HTML format, see [`ToLinksOptions::html_theme`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HtmlTheme {
+    /// Dark text on a light background.
+    Light,
+    /// Light text on a dark background.
+    Dark,
+    /// Follow the reader's system preference via the `prefers-color-scheme`
+    /// media query.
+    Auto,
+}
+impl HtmlTheme {
+    const LIGHT_CSS: &'static str = "body { background-color: #ffffff; color: #1b1b1b; }\n\
+        a { color: #0b57d0; }\n\
+        a:visited { color: #6f32a8; }\n";
+    const DARK_CSS: &'static str = "body { background-color: #1c1b22; color: #e8e6e3; }\n\
+        a { color: #8ab4f8; }\n\
+        a:visited { color: #c58af9; }\n";
+
+    /// Write CSS rules that apply this theme.
+    pub fn write_css<W: Write>(self, writer: &mut W) -> io::Result<()> {
+        match self {
+            HtmlTheme::Light => {
+                writeln!(writer, ":root {{ color-scheme: light; }}")?;
+                write!(writer, "{}", Self::LIGHT_CSS)
+            }
+            HtmlTheme::Dark => {
+                writeln!(writer, ":root {{ color-scheme: dark; }}")?;
+                write!(writer, "{}", Self::DARK_CSS)
+            }
+            HtmlTheme::Auto => {
+                writeln!(writer, ":root {{ color-scheme: light dark; }}")?;
+                write!(writer, "{}", Self::LIGHT_CSS)?;
+                writeln!(writer, "@media (prefers-color-scheme: dark) {{")?;
+                write!(writer, "{}", Self::DARK_CSS)?;
+                writeln!(writer, "}}")
+            }
+        }
+    }
+}
+
+/// Title used for tabs without any history entries when
+/// [`ToLinksOptions::include_unloaded_tabs`] is enabled.
+pub const UNLOADED_TAB_TITLE: &str = "Unloaded tab";
+
+/// The URL and title to write for a tab. Tabs without any history entries
+/// use their typed value as URL and [`UNLOADED_TAB_TITLE`] as title.
+fn tab_url_and_title<'data>(tab: &TabInfo<'data>) -> (&'data str, &'data str) {
+    if tab.data.entries.is_empty() {
+        (tab.typed_value().unwrap_or_default(), UNLOADED_TAB_TITLE)
+    } else {
+        (tab.url(), tab.title())
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct ToLinksOptions<'a> {
     pub format: LinkFormat,
@@ -315,12 +506,104 @@ pub struct ToLinksOptions<'a> {
     pub indent_all_links: bool,
     pub custom_page_break: Cow<'a, str>,
     pub tree_sources: Cow<'a, [TreeDataSource]>,
+    /// Write the date each tab was last accessed after its link. Only
+    /// supported for the Markdown and HTML formats.
+    pub last_accessed_timestamps: bool,
+    /// Use a shortened form of each tab's URL as the link's text instead of
+    /// the tab's title. The value is the maximum number of characters of the
+    /// shortened URL, see [`shorten_url`]. Links still point to the full URL.
+    pub shorten_urls: Option<usize>,
+    /// Text used to indent tabs once for each ancestor they have in a tab
+    /// tree. The text is written as is, so it must already be escaped for the
+    /// output format. Uses [`LinkFormat::default_indent_unit`] if `None`.
+    pub indent_unit: Option<Cow<'a, str>>,
+    /// Text written between the indentation and a tab that has a parent in a
+    /// tab tree. The text is written as is, so it must already be escaped for
+    /// the output format. Uses [`LinkFormat::default_tree_connector`] if
+    /// `None`.
+    pub tree_connector: Option<Cow<'a, str>>,
+    /// The maximum number of levels that tabs in a tab tree are indented.
+    /// Tabs that are nested deeper are indented as if they were at this level.
+    pub tree_max_depth: Option<usize>,
+    /// Write an extra annotated link after tabs that have text typed into
+    /// their address bar which was never navigated to, see
+    /// [`TabInfo::typed_value`].
+    pub typed_values: bool,
+    /// Include tabs that have no history entries instead of skipping them.
+    /// Such tabs are usually unloaded tabs, they are written using their typed
+    /// value if they have one and otherwise as a link titled
+    /// [`UNLOADED_TAB_TITLE`] without any URL.
+    pub include_unloaded_tabs: bool,
+    /// Colors for the [`LinkFormat::HTML`] format. Uses the browser's default
+    /// styling if `None`.
+    pub html_theme: Option<HtmlTheme>,
+    /// Wrap each group's links in a `<details>` element so that groups can be
+    /// collapsed. Only supported for the [`LinkFormat::HTML`] format.
+    pub collapsible_groups: bool,
+    /// The text that is written for each tab when using the
+    /// [`LinkFormat::Template`] format. See [`write_template`] for info about
+    /// the supported placeholders.
+    pub template: Cow<'a, str>,
+    /// Write the whole favicon for the `{favicon}` template placeholder and
+    /// the [`LinkFormat::Json`] and [`LinkFormat::Yaml`] formats even if it is
+    /// a `data:` URI. Otherwise only the MIME type of such favicons is written
+    /// since the image data can be quite large.
+    pub full_favicon: bool,
+    /// Emphasize each window's selected tab, see [`TabInfo::is_selected`].
+    /// The link is bold for most formats and prefixed with `*` for
+    /// [`LinkFormat::TXT`]. Not supported for [`LinkFormat::Template`].
+    pub mark_selected: bool,
+    /// Write consecutive tabs in a group that have the same URL and tree
+    /// depth as a single link with the number of merged tabs (like `×3`)
+    /// added to its title. Tabs that aren't adjacent are never merged.
+    pub fold_adjacent_duplicates: bool,
+    /// The maximum number of characters of a tab's title that is written. Longer
+    /// titles are truncated and end with an ellipsis (`…`). Links still point
+    /// to the full URL.
+    pub max_title_length: Option<usize>,
+    /// Skip the descendants of tabs whose subtree is collapsed in Tree Style
+    /// Tab, see [`TabInfo::tst_subtree_collapsed`]. The number of skipped tabs
+    /// is written after the collapsed tab's title, like `(+3 hidden)`. Only
+    /// used by formats that show tab trees.
+    pub collapse_subtrees: bool,
+    /// The number of tabs there were before the groups were truncated. If
+    /// this is set then a notice about how many of the tabs were written is
+    /// added at the end of the output. No notice is written for
+    /// [`LinkFormat::UrlList`] since every line of that format must be a URL
+    /// or for [`LinkFormat::Json`] and [`LinkFormat::Yaml`].
+    pub truncated_from: Option<usize>,
 }
 impl ToLinksOptions<'_> {
     #[allow(clippy::cognitive_complexity)]
     pub fn write_links<W: Write>(&self, groups: &[TabGroup<'_>], writer: &mut W) -> io::Result<()> {
         const HTML_GROUP_TAG: &str = "p";
 
+        // Formats that aren't written as a list of links below:
+        match self.format {
+            LinkFormat::Template => return self.write_template_links(groups, writer),
+            LinkFormat::Dot => return self.write_dot_links(groups, writer),
+            LinkFormat::UrlList => return self.write_url_list(groups, writer),
+            LinkFormat::Svg => return self.write_svg_links(groups, writer),
+            LinkFormat::Json => {
+                return serde_json::to_writer_pretty(
+                    &mut *writer,
+                    &SessionExport::from_groups(groups, self.full_favicon),
+                )
+                .map_err(io::Error::from);
+            }
+            LinkFormat::Yaml => {
+                return write_yaml(
+                    &SessionExport::from_groups(groups, self.full_favicon),
+                    writer,
+                );
+            }
+            LinkFormat::TXT
+            | LinkFormat::Markdown
+            | LinkFormat::RTF { .. }
+            | LinkFormat::HTML
+            | LinkFormat::Typst => {}
+        }
+
         // -------------------------------------
         //            Format header
         // -------------------------------------
@@ -338,6 +621,11 @@ impl ToLinksOptions<'_> {
                     writeln!(writer, "{}", " {page-break-after: always}")?;
                     writeln!(writer, "{}", "</STYLE>")?;
                 }
+                if let Some(theme) = self.html_theme {
+                    writeln!(writer, "<style>")?;
+                    theme.write_css(&mut writer)?;
+                    writeln!(writer, "</style>")?;
+                }
 
                 Right(Right(writer.start_body()?))
             }
@@ -347,6 +635,7 @@ impl ToLinksOptions<'_> {
                 writeln!(writer, "\n")?;
                 Left(writer)
             }
+            _ => unreachable!("handled above"),
         };
 
         // -------------------------------------
@@ -402,7 +691,7 @@ impl ToLinksOptions<'_> {
                     writer!("");
 
                     for group in groups {
-                        writer!("{}", group.name());
+                        writer!("{}", self.rtf_escaped(group.name()));
                     }
 
                     writer!("");
@@ -416,6 +705,7 @@ impl ToLinksOptions<'_> {
                     writer!("");
                     writer!("");
                 }
+                _ => unreachable!("handled above"),
             }
 
             // Page break:
@@ -456,28 +746,39 @@ impl ToLinksOptions<'_> {
         //                Links
         // -------------------------------------
 
-        let tree_source = self
-            .tree_sources
-            .iter()
-            .find(|s| {
-                s.has_any_data(
-                    groups
-                        .iter()
-                        .flat_map(|group| group.tabs().iter())
-                        .map(|tab_info| tab_info.data),
-                )
-            })
-            .map(|source| std::array::from_ref(source) as &[_])
-            .unwrap_or(&[]);
+        let indent_unit = self
+            .indent_unit
+            .as_deref()
+            .unwrap_or(self.format.default_indent_unit());
+        let tree_connector = self
+            .tree_connector
+            .as_deref()
+            .unwrap_or(self.format.default_tree_connector());
+
+        let tree_source = self.tree_source(groups);
 
         for (group_index, group) in groups.iter().enumerate() {
             match self.format {
                 LinkFormat::TXT | LinkFormat::RTF { .. } => {
-                    writer!("{}", group.name());
+                    writer!("{}", self.rtf_escaped(group.name()));
                     if self.format.is_rtf() {
                         writer!("");
                     }
                 }
+                LinkFormat::HTML if self.collapsible_groups => {
+                    writeln!(
+                        writer,
+                        r#"<a name="group{}"></a><details open><summary>{} ({} {})</summary>"#,
+                        group_index + 1,
+                        html_escaped_text(group.name()),
+                        group.tabs().len(),
+                        if group.tabs().len() == 1 {
+                            "tab"
+                        } else {
+                            "tabs"
+                        }
+                    )?;
+                }
                 LinkFormat::HTML => {
                     writer!(
                         r#"<a name="group{}"></a><h2>{}</h2>"#,
@@ -491,25 +792,50 @@ impl ToLinksOptions<'_> {
                 LinkFormat::Typst => {
                     writer!("= #\"{}\"\n", typst_escaped_text(group.name()));
                 }
+                _ => unreachable!("handled above"),
             }
 
-            for tab in group.tabs() {
-                if tab.data.entries.is_empty() {
-                    // Can have 0 entries! Why?
+            let mut tabs = group.tabs().iter().peekable();
+            while let Some(tab) = tabs.next() {
+                // Can have 0 entries! Why?
+                let unloaded = tab.data.entries.is_empty();
+                if !self.includes_tab(tab) {
                     continue;
                 }
-                let url = tab.url();
-                let mut title = tab.title();
+                let (url, mut title) = tab_url_and_title(tab);
+                let tree_depth = self.tree_depth(tab, tree_source);
+
+                let hidden = self.hidden_descendants(tab, tabs.clone(), tree_source);
+                if hidden > 0 {
+                    tabs.nth(hidden - 1);
+                }
+
+                let mut repeats = 1;
+                if self.fold_adjacent_duplicates {
+                    while let Some(next) = tabs.peek() {
+                        if next.data.entries.is_empty() != unloaded
+                            || tab_url_and_title(next).0 != url
+                            || self.tree_depth(next, tree_source) != tree_depth
+                        {
+                            break;
+                        }
+                        repeats += 1;
+                        tabs.next();
+                    }
+                }
+
                 if title.is_empty() {
                     title = "No title";
                 }
+                let mut title = self.link_text(url, title);
+                if repeats > 1 {
+                    title = format!("{} ×{}", title, repeats).into();
+                }
+                if hidden > 0 {
+                    title = format!("{} (+{} hidden)", title, hidden).into();
+                }
 
-                let mut number_of_tree_style_tab_parents = tab
-                    .tst_ancestor_tabs(
-                        tree_source,
-                        tab.window.expect("tab should have an associated window"),
-                    )
-                    .count();
+                let mut number_of_tree_style_tab_parents = tree_depth;
                 if self.format == LinkFormat::Typst {
                     // Typst: items not in lists can have greater indentation
                     // than list items, so always put all links in a list item.
@@ -525,6 +851,7 @@ impl ToLinksOptions<'_> {
                         LinkFormat::TXT => "    ",
                         LinkFormat::Markdown => "  ",
                         LinkFormat::Typst => "",
+                        _ => unreachable!("handled above"),
                     };
                 }
 
@@ -533,22 +860,10 @@ impl ToLinksOptions<'_> {
                 for index in 0..number_of_tree_style_tab_parents {
                     if index + 1 == number_of_tree_style_tab_parents {
                         // Last indentation:
-                        let extra = match self.format {
-                            LinkFormat::Markdown => "",
-                            LinkFormat::RTF { .. } | LinkFormat::HTML => "|---",
-                            LinkFormat::TXT => "|--- ",
-                            LinkFormat::Typst => "- ",
-                        };
-                        tab_tree_indention_main = tab_tree_indention.clone() + extra;
+                        tab_tree_indention_main = tab_tree_indention.clone() + tree_connector;
                     }
 
-                    tab_tree_indention += match self.format {
-                        LinkFormat::Markdown => "  ",
-                        LinkFormat::HTML => "|&nbsp;&nbsp;&nbsp;&nbsp;",
-                        LinkFormat::RTF { .. } => "|  ",
-                        LinkFormat::TXT => "|    ",
-                        LinkFormat::Typst => "  ",
-                    };
+                    tab_tree_indention += indent_unit;
                 }
 
                 let mut scroll = tab.scroll().unwrap_or_default().to_owned();
@@ -556,7 +871,30 @@ impl ToLinksOptions<'_> {
                     scroll = format!(" (scroll: {})", scroll);
                 }
 
-                if url == "about:newtab" {
+                let last_accessed = if self.last_accessed_timestamps {
+                    last_accessed_time(tab.data.last_accessed)
+                } else {
+                    None
+                };
+                let last_accessed = match (self.format, last_accessed) {
+                    (LinkFormat::Markdown, Some(time)) => {
+                        format!(" *({})*", time.format("%Y-%m-%d"))
+                    }
+                    (LinkFormat::HTML, Some(time)) => format!(
+                        r#" <time datetime="{}">{}</time>"#,
+                        time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                        time.format("%Y-%m-%d")
+                    ),
+                    _ => String::new(),
+                };
+
+                let typed_value = if self.typed_values && !unloaded {
+                    tab.typed_value()
+                } else {
+                    None
+                };
+
+                if url == "about:newtab" && typed_value.is_none() {
                     // Treat empty new tabs as separators.
 
                     match self.format {
@@ -585,62 +923,109 @@ impl ToLinksOptions<'_> {
 
                             // OR horizontal line:
                             // writer!("{}#v(0.5em - 1pt)#line(length: 100%)", tab_tree_indention_main);
-                            writer!("{}#line(start: (0%, 0.5em - 1pt), length: 100%)", tab_tree_indention_main);
-                        }
-                    }
-                } else {
-                    match self.format {
-                        LinkFormat::HTML => {
                             writer!(
-                                r#"{}<a href="{}">{}</a>{}"#,
-                                tab_tree_indention_main,
-                                html_escaped_text(url),
-                                html_escaped_text(title),
-                                scroll
-                            );
-                        }
-                        LinkFormat::RTF { .. } => {
-                            writer!(
-                                "{}{}{}{}{}{}{}",
-                                tab_tree_indention_main,
-                                r#"{\field{\*\fldinst HYPERLINK ""#,
-                                url,
-                                r#""}{\fldrslt "#,
-                                title,
-                                "}}",
-                                scroll
+                                "{}#line(start: (0%, 0.5em - 1pt), length: 100%)",
+                                tab_tree_indention_main
                             );
                         }
-                        LinkFormat::TXT => {
-                            writer!("{}", tab_tree_indention);
-                            writer!("{}{}{}", tab_tree_indention_main, title, scroll);
-                            writer!("{}{}", tab_tree_indention, url);
-                        }
-                        LinkFormat::Markdown => {
-                            writer!(
-                                "{}- [{}]({}){}",
-                                tab_tree_indention_main,
-                                // TODO: escape markdown link TITLE:
-                                title,
-                                // TODO: escape markdown URL:
-                                url,
-                                scroll
-                            );
-                        }
-                        LinkFormat::Typst => {
-                            // https://typst.app/docs/reference/model/link/
-                            writer!(
-                                "{}#link(\"{}\", \"{}\"){}\n",
-                                tab_tree_indention_main,
-                                typst_escaped_text(url),
-                                typst_escaped_text(title),
-                                scroll
-                            );
+                        _ => unreachable!("handled above"),
+                    }
+                } else {
+                    let typed_title;
+                    let selected = self.mark_selected && tab.is_selected();
+                    let mut links = vec![(
+                        url,
+                        &*title,
+                        last_accessed.as_str(),
+                        scroll.as_str(),
+                        selected,
+                    )];
+                    if let Some(typed_value) = typed_value {
+                        typed_title = format!("Typed: {}", typed_value);
+                        links.push((typed_value, &typed_title, "", "", false));
+                    }
+                    for (url, title, last_accessed, scroll, selected) in links {
+                        match self.format {
+                            LinkFormat::HTML => {
+                                let (start, end) = if selected {
+                                    ("<strong>", "</strong>")
+                                } else {
+                                    ("", "")
+                                };
+                                writer!(
+                                    r#"{}{}<a href="{}">{}</a>{}{}{}"#,
+                                    tab_tree_indention_main,
+                                    start,
+                                    html_escaped_text(url),
+                                    html_escaped_text(title),
+                                    end,
+                                    last_accessed,
+                                    scroll
+                                );
+                            }
+                            LinkFormat::RTF { .. } => {
+                                let (start, end) = if selected { (r"{\b ", "}") } else { ("", "") };
+                                writer!(
+                                    "{}{}{}{}{}{}{}{}{}",
+                                    tab_tree_indention_main,
+                                    start,
+                                    r#"{\field{\*\fldinst HYPERLINK ""#,
+                                    rtf_escaped_text(url),
+                                    r#""}{\fldrslt "#,
+                                    rtf_escaped_text(title),
+                                    "}}",
+                                    end,
+                                    scroll
+                                );
+                            }
+                            LinkFormat::TXT => {
+                                let marker = if selected { "* " } else { "" };
+                                writer!("{}", tab_tree_indention);
+                                writer!("{}{}{}{}", tab_tree_indention_main, marker, title, scroll);
+                                writer!("{}{}", tab_tree_indention, url);
+                            }
+                            LinkFormat::Markdown => {
+                                let emphasis = if selected { "**" } else { "" };
+                                writer!(
+                                    "{}- [{}{}{}]({}){}{}",
+                                    tab_tree_indention_main,
+                                    emphasis,
+                                    // TODO: escape markdown link TITLE:
+                                    title,
+                                    emphasis,
+                                    // TODO: escape markdown URL:
+                                    url,
+                                    last_accessed,
+                                    scroll
+                                );
+                            }
+                            LinkFormat::Typst => {
+                                let (start, end) = if selected {
+                                    ("#strong[", "]")
+                                } else {
+                                    ("", "")
+                                };
+                                // https://typst.app/docs/reference/model/link/
+                                writer!(
+                                    "{}{}#link(\"{}\", \"{}\"){}{}\n",
+                                    tab_tree_indention_main,
+                                    start,
+                                    typst_escaped_text(url),
+                                    typst_escaped_text(title),
+                                    end,
+                                    scroll
+                                );
+                            }
+                            _ => unreachable!("handled above"),
                         }
                     }
                 }
             } // end of tab for loop
 
+            if self.format.is_html() && self.collapsible_groups {
+                writeln!(writer, "</details>")?;
+            }
+
             let skip_page_break =
                 self.skip_page_break_after_last_group && group_index + 1 == groups.len();
 
@@ -682,6 +1067,28 @@ impl ToLinksOptions<'_> {
             }
         }
 
+        // -------------------------------------
+        //          Truncation notice
+        // -------------------------------------
+
+        if let Some(notice) = self.truncation_notice(groups) {
+            match self.format {
+                LinkFormat::HTML => {
+                    writer!("<p><em>{}</em></p>", html_escaped_text(&notice));
+                }
+                LinkFormat::Markdown => {
+                    writer!("*{}*", notice);
+                }
+                LinkFormat::Typst => {
+                    writer!("#emph(\"{}\")", typst_escaped_text(&notice));
+                }
+                LinkFormat::TXT | LinkFormat::RTF { .. } => {
+                    writer!("{}", self.rtf_escaped(&notice));
+                }
+                _ => unreachable!("handled above"),
+            }
+        }
+
         // -------------------------------------
         //             Format footer
         // -------------------------------------
@@ -695,4 +1102,1082 @@ impl ToLinksOptions<'_> {
 
         Ok(())
     }
+
+    /// The number of tabs at the start of `following` that are descendants of
+    /// `tab` and should be skipped since `tab`'s subtree is collapsed, see
+    /// [`ToLinksOptions::collapse_subtrees`].
+    fn hidden_descendants<'a, 'data: 'a>(
+        &self,
+        tab: &TabInfo<'data>,
+        following: impl Iterator<Item = &'a TabInfo<'data>>,
+        tree_source: &[TreeDataSource],
+    ) -> usize {
+        if !self.collapse_subtrees || !tab.tst_subtree_collapsed() {
+            return 0;
+        }
+        following
+            .take_while(|other| {
+                other
+                    .tst_ancestor_tabs(tree_source)
+                    .any(|ancestor| ptr::eq(ancestor.value.data, tab.data))
+            })
+            .count()
+    }
+
+    /// The text of a tab's link, see [`ToLinksOptions::shorten_urls`] and
+    /// [`ToLinksOptions::max_title_length`].
+    fn link_text<'t>(&self, url: &'t str, title: &'t str) -> Cow<'t, str> {
+        match (self.shorten_urls, self.max_title_length) {
+            (Some(max_len), _) => shorten_url(url, max_len),
+            (None, Some(max_len)) => truncate_with_ellipsis(title, max_len, 0),
+            (None, None) => Cow::Borrowed(title),
+        }
+    }
+
+    /// Escape text if the output format is RTF, other formats get the text
+    /// unchanged.
+    fn rtf_escaped<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        if self.format.is_rtf() {
+            rtf_escaped_text(text)
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    /// `false` for unloaded tabs that are skipped, see
+    /// [`ToLinksOptions::include_unloaded_tabs`].
+    pub fn includes_tab(&self, tab: &TabInfo<'_>) -> bool {
+        !tab.data.entries.is_empty() || self.include_unloaded_tabs
+    }
+
+    /// A notice about how many tabs were written if the groups were truncated,
+    /// see [`ToLinksOptions::truncated_from`].
+    fn truncation_notice(&self, groups: &[TabGroup<'_>]) -> Option<String> {
+        let total = self.truncated_from?;
+        let shown = groups
+            .iter()
+            .flat_map(|group| group.tabs())
+            .filter(|tab| self.includes_tab(tab))
+            .count();
+        Some(format!("(truncated: showing {shown} of {total} tabs)"))
+    }
+
+    /// The first tree data source that has data for any of the tabs.
+    fn tree_source(&self, groups: &[TabGroup<'_>]) -> &[TreeDataSource] {
+        self.tree_sources
+            .iter()
+            .find(|s| {
+                s.has_any_data(
+                    groups
+                        .iter()
+                        .flat_map(|group| group.tabs().iter())
+                        .map(|tab_info| tab_info.data),
+                )
+            })
+            .map(|source| std::array::from_ref(source) as &[_])
+            .unwrap_or(&[])
+    }
+
+    /// The number of levels a tab should be indented when visualizing tab
+    /// trees.
+    fn tree_depth(&self, tab: &TabInfo<'_>, tree_source: &[TreeDataSource]) -> usize {
+        let depth = tab.tst_ancestor_tabs(tree_source).count();
+        match self.tree_max_depth {
+            Some(max_depth) => depth.min(max_depth),
+            None => depth,
+        }
+    }
+
+    /// Write links using [`ToLinksOptions::template`], see [`write_template`].
+    fn write_template_links<W: Write>(
+        &self,
+        groups: &[TabGroup<'_>],
+        writer: &mut W,
+    ) -> io::Result<()> {
+        let tree_source = self.tree_source(groups);
+        for group in groups {
+            for tab in group.tabs() {
+                if !self.includes_tab(tab) {
+                    continue;
+                }
+                let (url, title) = tab_url_and_title(tab);
+                let title = self.link_text(url, title);
+                let depth = self.tree_depth(tab, tree_source).to_string();
+                let last_accessed = last_accessed_time(tab.data.last_accessed)
+                    .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true))
+                    .unwrap_or_default();
+                let typed_value = tab.typed_value().unwrap_or_default();
+                let favicon = match tab.image() {
+                    Some(image) if !self.full_favicon => data_uri_mime_type(image).unwrap_or(image),
+                    image => image.unwrap_or_default(),
+                };
+
+                write_template(writer, &self.template, |placeholder| match placeholder {
+                    "title" => Some(&title),
+                    "url" => Some(url),
+                    "group" => Some(group.name()),
+                    "depth" => Some(&depth),
+                    "last_accessed" => Some(&last_accessed),
+                    "typed_value" => Some(typed_value),
+                    "favicon" => Some(favicon),
+                    _ => None,
+                })?;
+            }
+        }
+        if let Some(notice) = self.truncation_notice(groups) {
+            writeln!(writer, "{}", notice)?;
+        }
+        Ok(())
+    }
+
+    /// Write each tab's URL on its own line. Empty new tabs are skipped.
+    fn write_url_list<W: Write>(&self, groups: &[TabGroup<'_>], writer: &mut W) -> io::Result<()> {
+        for tab in groups.iter().flat_map(|group| group.tabs()) {
+            if !self.includes_tab(tab) {
+                continue;
+            }
+            let (url, _) = tab_url_and_title(tab);
+            if url.is_empty() || url == "about:newtab" {
+                continue;
+            }
+            writeln!(writer, "{}", url)?;
+        }
+        Ok(())
+    }
+
+    /// Write a Graphviz DOT graph with one node per tab. Each group has a root
+    /// node with edges to the group's tabs that don't have a parent tab in the
+    /// same group.
+    fn write_dot_links<W: Write>(&self, groups: &[TabGroup<'_>], writer: &mut W) -> io::Result<()> {
+        let tree_source = self.tree_source(groups);
+
+        writeln!(writer, "digraph tabs {{")?;
+        writeln!(writer, "    rankdir=LR;")?;
+        writeln!(writer, "    node [shape=box];")?;
+        for (group_index, group) in groups.iter().enumerate() {
+            let tabs = group.tabs();
+            writeln!(writer)?;
+            writeln!(
+                writer,
+                r#"    group{} [label="{}", style=bold];"#,
+                group_index + 1,
+                dot_escaped_text(group.name())
+            )?;
+            let mut tab_iter = tabs.iter().enumerate();
+            while let Some((tab_index, tab)) = tab_iter.next() {
+                if !self.includes_tab(tab) {
+                    continue;
+                }
+                let (url, mut title) = tab_url_and_title(tab);
+                if title.is_empty() {
+                    title = "No title";
+                }
+                let mut title = self.link_text(url, title);
+                let hidden =
+                    self.hidden_descendants(tab, tab_iter.clone().map(|(_, tab)| tab), tree_source);
+                if hidden > 0 {
+                    tab_iter.nth(hidden - 1);
+                    title = format!("{} (+{} hidden)", title, hidden).into();
+                }
+                let selected = self.mark_selected && tab.is_selected();
+                writeln!(
+                    writer,
+                    r#"    tab{}_{} [label="{}", URL="{}"{}];"#,
+                    group_index + 1,
+                    tab_index + 1,
+                    dot_escaped_text(&title),
+                    dot_escaped_text(url),
+                    if selected { ", style=bold" } else { "" }
+                )?;
+
+                let parent_index = tab.tst_parent(tree_source).and_then(|parent| {
+                    tabs.iter()
+                        .position(|other| ptr::eq(other.data, parent.value.data))
+                        .filter(|&index| self.includes_tab(&tabs[index]))
+                });
+                match parent_index {
+                    Some(parent_index) => writeln!(
+                        writer,
+                        "    tab{0}_{1} -> tab{0}_{2};",
+                        group_index + 1,
+                        parent_index + 1,
+                        tab_index + 1
+                    )?,
+                    None => writeln!(
+                        writer,
+                        "    group{0} -> tab{0}_{1};",
+                        group_index + 1,
+                        tab_index + 1
+                    )?,
+                }
+            }
+        }
+        if let Some(notice) = self.truncation_notice(groups) {
+            writeln!(writer)?;
+            writeln!(writer, "    // {}", notice)?;
+        }
+        writeln!(writer, "}}")
+    }
+
+    /// Write an SVG image with one line of text per tab. Tabs are indented by
+    /// their depth in a tab tree and connected to their parent tab, or to their
+    /// group's name if they don't have a parent in the same group.
+    fn write_svg_links<W: Write>(&self, groups: &[TabGroup<'_>], writer: &mut W) -> io::Result<()> {
+        const ROW_HEIGHT: usize = 20;
+        const INDENT: usize = 20;
+        const MARGIN: usize = 10;
+        /// Rough width of a character, only used to choose the image width.
+        const CHAR_WIDTH: usize = 8;
+
+        struct Row<'a> {
+            x: usize,
+            y: usize,
+            text: Cow<'a, str>,
+            url: Option<&'a str>,
+            parent: Option<usize>,
+            selected: bool,
+        }
+
+        let tree_source = self.tree_source(groups);
+        let mut rows = Vec::<Row<'_>>::new();
+        let mut y = MARGIN;
+        for group in groups {
+            y += ROW_HEIGHT;
+            let group_row = rows.len();
+            rows.push(Row {
+                x: MARGIN,
+                y,
+                text: Cow::Borrowed(group.name()),
+                url: None,
+                parent: None,
+                selected: false,
+            });
+
+            let tabs = group.tabs();
+            // The row that each tab was written to:
+            let mut tab_rows = vec![None; tabs.len()];
+            let mut tab_iter = tabs.iter().enumerate();
+            while let Some((tab_index, tab)) = tab_iter.next() {
+                if !self.includes_tab(tab) {
+                    continue;
+                }
+                let (url, mut title) = tab_url_and_title(tab);
+                if title.is_empty() {
+                    title = "No title";
+                }
+                let mut text = self.link_text(url, title);
+                let hidden =
+                    self.hidden_descendants(tab, tab_iter.clone().map(|(_, tab)| tab), tree_source);
+                if hidden > 0 {
+                    tab_iter.nth(hidden - 1);
+                    text = format!("{} (+{} hidden)", text, hidden).into();
+                }
+                let parent = tab
+                    .tst_parent(tree_source)
+                    .and_then(|parent| {
+                        tabs.iter()
+                            .position(|other| ptr::eq(other.data, parent.value.data))
+                    })
+                    .and_then(|index| tab_rows[index])
+                    .unwrap_or(group_row);
+
+                y += ROW_HEIGHT;
+                tab_rows[tab_index] = Some(rows.len());
+                rows.push(Row {
+                    x: MARGIN + (self.tree_depth(tab, tree_source) + 1) * INDENT,
+                    y,
+                    text,
+                    url: Some(url),
+                    parent: Some(parent),
+                    selected: self.mark_selected && tab.is_selected(),
+                });
+            }
+            // Empty space between groups:
+            y += ROW_HEIGHT;
+        }
+        let notice = self.truncation_notice(groups);
+        if notice.is_some() {
+            y += ROW_HEIGHT;
+        }
+
+        let width = rows
+            .iter()
+            .map(|row| row.x + row.text.chars().count() * CHAR_WIDTH)
+            .chain(
+                notice
+                    .iter()
+                    .map(|notice| MARGIN + notice.chars().count() * CHAR_WIDTH),
+            )
+            .max()
+            .unwrap_or(0)
+            + MARGIN;
+        let height = y + MARGIN;
+
+        writeln!(
+            writer,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}" font-family="sans-serif" font-size="14">"#,
+            width, height
+        )?;
+        writeln!(writer, r#"  <g stroke="gray" fill="none">"#)?;
+        for row in &rows {
+            let Some(parent) = row.parent.map(|index| &rows[index]) else {
+                continue;
+            };
+            // Go down from below the parent's text and then right to the
+            // middle of this row's text:
+            let line_x = parent.x + INDENT / 2;
+            let line_y = row.y - 5;
+            writeln!(
+                writer,
+                r#"    <line x1="{0}" y1="{1}" x2="{0}" y2="{2}" />"#,
+                line_x,
+                parent.y + 4,
+                line_y
+            )?;
+            writeln!(
+                writer,
+                r#"    <line x1="{}" y1="{2}" x2="{}" y2="{2}" />"#,
+                line_x,
+                row.x.saturating_sub(4).max(line_x),
+                line_y
+            )?;
+        }
+        writeln!(writer, "  </g>")?;
+        for row in &rows {
+            let text = html_escaped_text(&row.text);
+            match row.url {
+                Some(url) => writeln!(
+                    writer,
+                    r#"  <a href="{}"><text x="{}" y="{}"{}>{}</text></a>"#,
+                    html_escaped_text(url),
+                    row.x,
+                    row.y,
+                    if row.selected {
+                        r#" font-weight="bold""#
+                    } else {
+                        ""
+                    },
+                    text
+                )?,
+                None => writeln!(
+                    writer,
+                    r#"  <text x="{}" y="{}" font-weight="bold">{}</text>"#,
+                    row.x, row.y, text
+                )?,
+            }
+        }
+        if let Some(notice) = notice {
+            writeln!(
+                writer,
+                r#"  <text x="{}" y="{}" font-style="italic">{}</text>"#,
+                MARGIN,
+                y,
+                html_escaped_text(&notice)
+            )?;
+        }
+        writeln!(writer, "</svg>")
+    }
+}
+
+/// The MIME type of a `data:` URI, for example `image/png` for
+/// `data:image/png;base64,iVBOR...`. `None` if the text isn't a `data:` URI.
+pub(crate) fn data_uri_mime_type(uri: &str) -> Option<&str> {
+    let data = uri.strip_prefix("data:")?;
+    let end = data.find([';', ',']).unwrap_or(data.len());
+    Some(&data[..end])
+}
+
+/// Write a template where placeholders such as `{title}` are replaced with
+/// the text returned by `lookup`. Placeholders that `lookup` doesn't know
+/// about are written as is and `{{` and `}}` can be used to write `{` and `}`.
+///
+/// The replaced text isn't escaped in any way.
+///
+/// The placeholders used when writing links are `{title}`, `{url}`,
+/// `{group}`, `{depth}` (the number of ancestors a tab has in a tab tree),
+/// `{last_accessed}` (an ISO-8601 timestamp or empty if unknown),
+/// `{typed_value}` (see [`TabInfo::typed_value`], empty if there is none) and
+/// `{favicon}` (see [`TabInfo::image`] and [`ToLinksOptions::full_favicon`],
+/// empty if there is none).
+pub fn write_template<'a, W: Write>(
+    writer: &mut W,
+    template: &str,
+    mut lookup: impl FnMut(&str) -> Option<&'a str>,
+) -> io::Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        writer.write_all(&rest.as_bytes()[..start])?;
+        rest = &rest[start..];
+
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            writer.write_all(&rest.as_bytes()[..1])?;
+            rest = &rest[2..];
+            continue;
+        }
+        let replaced = rest
+            .strip_prefix('{')
+            .and_then(|placeholder| placeholder.split_once('}'))
+            .and_then(|(name, after)| Some((lookup(name)?, after)));
+        match replaced {
+            Some((value, after)) => {
+                writer.write_all(value.as_bytes())?;
+                rest = after;
+            }
+            None => {
+                writer.write_all(&rest.as_bytes()[..1])?;
+                rest = &rest[1..];
+            }
+        }
+    }
+    writer.write_all(rest.as_bytes())
+}
+
+/// Write an export as YAML, see [`LinkFormat::Yaml`].
+#[cfg(feature = "yaml")]
+fn write_yaml<W: Write>(export: &SessionExport, writer: &mut W) -> io::Result<()> {
+    serde_yaml::to_writer(writer, export).map_err(io::Error::other)
+}
+#[cfg(not(feature = "yaml"))]
+fn write_yaml<W: Write>(_export: &SessionExport, _writer: &mut W) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "YAML output requires the \"yaml\" feature",
+    ))
+}
+
+/// Shorten a URL so that it can be used as the text of a link. The scheme is
+/// removed and if the rest of the URL is longer than `max_len` characters then
+/// it is truncated and an ellipsis (`…`) is appended. The host is always kept
+/// in full even if it is longer than `max_len`.
+pub fn shorten_url(url: &str, max_len: usize) -> Cow<'_, str> {
+    let Some((_scheme, rest)) = url.split_once("://") else {
+        // URLs like "about:newtab" or "data:..." don't have a host:
+        return truncate_with_ellipsis(url, max_len, 0);
+    };
+    let short = match rest.split_once('/') {
+        // Skip trailing slash of the root path:
+        Some((host, "")) => host,
+        _ => rest,
+    };
+    let host_len = short.find(['/', '?', '#']).unwrap_or(short.len());
+    let host_chars = short[..host_len].chars().count();
+    truncate_with_ellipsis(short, max_len, host_chars)
+}
+
+/// Keep at most `max_len` characters (including the ellipsis) of `text` but
+/// never less than `min_chars` characters.
+fn truncate_with_ellipsis(text: &str, max_len: usize, min_chars: usize) -> Cow<'_, str> {
+    if text.chars().count() <= max_len {
+        return Cow::Borrowed(text);
+    }
+    if max_len == 0 && min_chars == 0 {
+        // Not even the ellipsis fits:
+        return Cow::Borrowed("");
+    }
+    let keep = max_len.saturating_sub(1).max(min_chars);
+    match text.char_indices().nth(keep) {
+        Some((end, _)) => Cow::Owned(format!("{}…", &text[..end])),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Convert a tab's `last_accessed` field (milliseconds since the Unix epoch)
+/// to a date time. Returns `None` if the tab has no info about when it was last
+/// accessed.
+pub fn last_accessed_time(last_accessed: i64) -> Option<DateTime<Utc>> {
+    if last_accessed <= 0 {
+        return None;
+    }
+    let seconds = last_accessed.div_euclid(1000);
+    let nanoseconds = (last_accessed.rem_euclid(1000) * 1_000_000) as u32;
+    Utc.timestamp_opt(seconds, nanoseconds).single()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_info::{OwnedTabData, OwnedWindowData};
+
+    #[test]
+    fn last_accessed_time_keeps_milliseconds() {
+        let time = last_accessed_time(1_714_566_896_789).unwrap();
+        assert_eq!(
+            time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "2024-05-01T12:34:56.789Z"
+        );
+        assert_eq!(time.format("%Y-%m-%d").to_string(), "2024-05-01");
+    }
+
+    #[test]
+    fn shorten_url_keeps_short_urls() {
+        assert_eq!(
+            shorten_url("https://www.wikipedia.org/", 40),
+            "www.wikipedia.org"
+        );
+        assert_eq!(
+            shorten_url("https://example.com/a/b?c=d", 40),
+            "example.com/a/b?c=d"
+        );
+        assert_eq!(shorten_url("about:newtab", 40), "about:newtab");
+    }
+
+    #[test]
+    fn shorten_url_truncates_queries() {
+        let url = "https://www.google.com/search?q=firefox+session&client=firefox-b-d&sxsrf=ALeKk01&ei=abc&start=10";
+        let short = shorten_url(url, 30);
+        assert_eq!(short, "www.google.com/search?q=firef…");
+        assert_eq!(short.chars().count(), 30);
+    }
+
+    #[test]
+    fn shorten_url_keeps_full_host() {
+        assert_eq!(
+            shorten_url("https://a-very-long-host-name.example.com/path?query=1", 10),
+            "a-very-long-host-name.example.com…"
+        );
+        assert_eq!(
+            shorten_url("https://a-very-long-host-name.example.com/", 10),
+            "a-very-long-host-name.example.com"
+        );
+    }
+
+    #[test]
+    fn shorten_url_handles_multibyte_characters() {
+        assert_eq!(
+            shorten_url("https://example.com/söök?q=ååååå", 20),
+            "example.com/söök?q=…"
+        );
+    }
+
+    fn render_template(template: &str) -> String {
+        let mut output = Vec::new();
+        write_template(&mut output, template, |placeholder| match placeholder {
+            "title" => Some("Example {url}"),
+            "url" => Some("https://example.com"),
+            _ => None,
+        })
+        .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn template_replaces_placeholders() {
+        assert_eq!(
+            render_template("- {title} <{url}>\n"),
+            "- Example {url} <https://example.com>\n"
+        );
+    }
+
+    #[test]
+    fn template_keeps_unknown_placeholders() {
+        assert_eq!(
+            render_template("{{title}} {unknown} {url"),
+            "{title} {unknown} {url"
+        );
+        assert_eq!(render_template("}}{}"), "}{}");
+    }
+
+    /// JSON for a loaded tab with a single history entry.
+    fn tab(url: &str, title: &str) -> serde_json::Value {
+        serde_json::json!({
+            "entries": [{ "url": url, "title": title }],
+            "lastAccessed": 0,
+            "hidden": false,
+            "attributes": {},
+            "userContextId": 0,
+            "index": 1,
+        })
+    }
+
+    /// JSON for a tab with the legacy Tree Style Tab data that
+    /// [`TreeDataSource::TstLegacy`] reads.
+    fn tree_tab(id: &str, parent: &str) -> serde_json::Value {
+        let mut tab = tab(&format!("https://example.com/{id}"), id);
+        tab["extData"] = serde_json::json!({
+            "treestyletab-id": id,
+            "treestyletab-parent": parent,
+        });
+        tab
+    }
+
+    /// A window with some tabs where the first tab is selected.
+    fn window(tabs: Vec<serde_json::Value>) -> OwnedWindowData {
+        OwnedWindowData::from_value(
+            &serde_json::json!({
+                "tabs": tabs,
+                "selected": 1,
+                "width": 800,
+                "height": 600,
+                "screenX": 0,
+                "screenY": 0,
+                "sizemode": "normal",
+            }),
+            false,
+        )
+        .unwrap()
+    }
+
+    /// Write links for some windows that are named "Window 1", "Window 2"
+    /// and so on.
+    fn render(windows: &[OwnedWindowData], options: &ToLinksOptions<'_>) -> String {
+        let groups = windows
+            .iter()
+            .enumerate()
+            .map(|(index, window)| window.info().as_group(format!("Window {}", index + 1)))
+            .collect::<Vec<_>>();
+        let mut output = Vec::new();
+        options.write_links(&groups, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    /// Options for writing links in a format.
+    fn format(format: LinkFormat) -> ToLinksOptions<'static> {
+        ToLinksOptions {
+            format,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unloaded_tabs_use_typed_value() {
+        let tab = |entries: serde_json::Value, typed: &str| {
+            let mut tab = tab("", "");
+            tab["entries"] = entries;
+            tab["userTypedValue"] = typed.into();
+            OwnedTabData::from_value(&tab).unwrap()
+        };
+        let unloaded = tab(serde_json::json!([]), "https://example.com/");
+        assert_eq!(
+            tab_url_and_title(&unloaded.info()),
+            ("https://example.com/", UNLOADED_TAB_TITLE)
+        );
+        let unloaded = tab(serde_json::json!([]), "");
+        assert_eq!(
+            tab_url_and_title(&unloaded.info()),
+            ("", UNLOADED_TAB_TITLE)
+        );
+        let loaded = tab(
+            serde_json::json!([{ "url": "https://example.org/", "title": "Example" }]),
+            "https://example.com/",
+        );
+        assert_eq!(
+            tab_url_and_title(&loaded.info()),
+            ("https://example.org/", "Example")
+        );
+    }
+
+    #[test]
+    fn rtf_escapes_unicode_and_control_characters() {
+        assert_eq!(rtf_escaped_text("Plain title"), "Plain title");
+        assert_eq!(
+            rtf_escaped_text("日本語のタイトル"),
+            r"\u26085?\u26412?\u-30050?\u12398?\u12479?\u12452?\u12488?\u12523?"
+        );
+        assert_eq!(rtf_escaped_text("a\\b {c}"), r"a\\b \{c\}");
+        assert_eq!(rtf_escaped_text("é😀"), r"\u233?\u-10179?\u-8704?");
+    }
+
+    #[test]
+    fn rtf_escapes_hyperlink_urls() {
+        let windows = [window(vec![tab(
+            "moz-extension://id/page.html#{\"a\":1}",
+            "Page",
+        )])];
+        let output = render(
+            &windows,
+            &format(LinkFormat::RTF {
+                picture_horizontal_line: false,
+            }),
+        );
+        assert!(output.contains(
+            r#"{\field{\*\fldinst HYPERLINK "moz-extension://id/page.html#\{"a":1\}"}{\fldrslt Page}}"#
+        ));
+        // Braces must stay balanced for the document to open:
+        assert_eq!(output.matches(r"\{").count(), output.matches(r"\}").count());
+        let unescaped = output.replace(r"\{", "").replace(r"\}", "");
+        assert_eq!(
+            unescaped.matches('{').count(),
+            unescaped.matches('}').count()
+        );
+    }
+
+    #[test]
+    fn template_favicon_shortens_data_uris() {
+        let tab = |image: &str| {
+            let mut tab = tab("https://example.com/", "Example");
+            tab["image"] = image.into();
+            tab
+        };
+        let windows = [window(vec![
+            tab("data:image/png;base64,iVBORw0KGgo="),
+            tab("https://example.com/favicon.ico"),
+            tab(""),
+        ])];
+        let template = |full_favicon| {
+            render(
+                &windows,
+                &ToLinksOptions {
+                    template: "[{favicon}]\n".into(),
+                    full_favicon,
+                    ..format(LinkFormat::Template)
+                },
+            )
+        };
+        assert_eq!(
+            template(false),
+            "[image/png]\n[https://example.com/favicon.ico]\n[]\n"
+        );
+        assert_eq!(
+            template(true),
+            "[data:image/png;base64,iVBORw0KGgo=]\n[https://example.com/favicon.ico]\n[]\n"
+        );
+
+        // The JSON export shortens favicons the same way:
+        let favicons = |full_favicon| {
+            let output = render(
+                &windows,
+                &ToLinksOptions {
+                    full_favicon,
+                    ..format(LinkFormat::Json)
+                },
+            );
+            let export: SessionExport = serde_json::from_str(&output).unwrap();
+            export.windows[0]
+                .tabs
+                .iter()
+                .map(|tab| tab.favicon.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            favicons(false),
+            [
+                Some("image/png".to_owned()),
+                Some("https://example.com/favicon.ico".to_owned()),
+                None
+            ]
+        );
+        assert_eq!(
+            favicons(true)[0].as_deref(),
+            Some("data:image/png;base64,iVBORw0KGgo=")
+        );
+    }
+
+    #[test]
+    fn mark_selected_emphasizes_active_tab() {
+        let mut windows = [window(vec![
+            tab("https://example.com/a", "a"),
+            tab("https://example.com/b", "b"),
+        ])];
+        windows[0].data.selected = 2;
+        let marked = |format_| {
+            render(
+                &windows,
+                &ToLinksOptions {
+                    mark_selected: true,
+                    table_of_contents: false,
+                    ..format(format_)
+                },
+            )
+        };
+        let markdown = marked(LinkFormat::Markdown);
+        assert!(markdown.contains("- [a](https://example.com/a)"));
+        assert!(markdown.contains("- [**b**](https://example.com/b)"));
+        let txt = marked(LinkFormat::TXT);
+        assert!(txt.contains("\na\n"));
+        assert!(txt.contains("\n* b\n"));
+        let dot = marked(LinkFormat::Dot);
+        assert!(dot.contains(r#"tab1_1 [label="a", URL="https://example.com/a"];"#));
+        assert!(dot.contains(r#"tab1_2 [label="b", URL="https://example.com/b", style=bold];"#));
+        let svg = marked(LinkFormat::Svg);
+        assert!(svg.contains(r#"<a href="https://example.com/a"><text x="30" y="50">a</text></a>"#));
+        assert!(svg.contains(r#"<text x="30" y="70" font-weight="bold">b</text>"#));
+    }
+
+    #[test]
+    fn dot_format_links_children_to_parents() {
+        let tab = |id: &str, parent: &str| {
+            let mut tab = tree_tab(id, parent);
+            tab["entries"][0]["title"] = format!("Tab \"{id}\"").into();
+            tab
+        };
+        let windows = [window(vec![tab("a", ""), tab("b", "a"), tab("c", "")])];
+        let output = render(
+            &windows,
+            &ToLinksOptions {
+                tree_sources: vec![TreeDataSource::TstLegacy].into(),
+                ..format(LinkFormat::Dot)
+            },
+        );
+        assert!(output.starts_with("digraph tabs {\n"));
+        assert!(output.ends_with("}\n"));
+        assert!(output.contains(r#"group1 [label="Window 1", style=bold];"#));
+        assert!(output.contains(r#"tab1_1 [label="Tab \"a\"", URL="https://example.com/a"];"#));
+        assert!(output.contains("group1 -> tab1_1;"));
+        assert!(output.contains("tab1_1 -> tab1_2;"));
+        assert!(output.contains("group1 -> tab1_3;"));
+        assert!(!output.contains("group1 -> tab1_2;"));
+    }
+
+    #[test]
+    fn truncation_notice_is_written_last() {
+        let windows = [window(vec![tab("https://example.com/", "Example")])];
+        let truncated = |format_| {
+            render(
+                &windows,
+                &ToLinksOptions {
+                    template: "{url}\n".into(),
+                    truncated_from: Some(5000),
+                    ..format(format_)
+                },
+            )
+        };
+        assert!(
+            truncated(LinkFormat::Markdown).ends_with("*(truncated: showing 1 of 5000 tabs)*\n")
+        );
+        assert_eq!(
+            truncated(LinkFormat::Template),
+            "https://example.com/\n(truncated: showing 1 of 5000 tabs)\n"
+        );
+        assert!(
+            truncated(LinkFormat::Dot).ends_with("    // (truncated: showing 1 of 5000 tabs)\n}\n")
+        );
+    }
+
+    #[test]
+    fn truncation_notice_only_counts_written_tabs() {
+        let mut unloaded = tab("", "");
+        unloaded["entries"] = serde_json::json!([]);
+        let windows = [window(vec![
+            tab("https://example.com/", "Example"),
+            unloaded,
+        ])];
+        let truncated = |include_unloaded_tabs| {
+            render(
+                &windows,
+                &ToLinksOptions {
+                    template: "{url}\n".into(),
+                    truncated_from: Some(5000),
+                    include_unloaded_tabs,
+                    ..format(LinkFormat::Template)
+                },
+            )
+        };
+        assert!(truncated(false).ends_with("(truncated: showing 1 of 5000 tabs)\n"));
+        assert!(truncated(true).ends_with("(truncated: showing 2 of 5000 tabs)\n"));
+    }
+
+    #[test]
+    fn url_list_only_writes_urls() {
+        let windows = [
+            window(vec![
+                tab("https://example.com/a", "Title"),
+                tab("about:newtab", "Title"),
+            ]),
+            window(vec![tab("https://example.com/b", "Title")]),
+        ];
+        let output = render(
+            &windows,
+            &ToLinksOptions {
+                table_of_contents: true,
+                truncated_from: Some(10),
+                ..format(LinkFormat::UrlList)
+            },
+        );
+        assert_eq!(output, "https://example.com/a\nhttps://example.com/b\n");
+    }
+
+    #[test]
+    fn last_accessed_time_ignores_missing_values() {
+        assert_eq!(last_accessed_time(0), None);
+        assert_eq!(last_accessed_time(-1), None);
+    }
+
+    #[test]
+    fn json_export_can_be_turned_into_a_session() {
+        let mut pinned = tab("https://example.com/b", "https://example.com/b");
+        pinned["pinned"] = true.into();
+        pinned["userContextId"] = 2.into();
+        let windows = [
+            window(vec![
+                tab("https://example.com/a", "https://example.com/a"),
+                pinned,
+            ]),
+            window(vec![tab("https://example.com/c", "https://example.com/c")]),
+        ];
+        let output = render(&windows, &format(LinkFormat::Json));
+
+        let mut export: SessionExport = serde_json::from_str(&output).unwrap();
+        assert_eq!(export.windows.len(), 2);
+        assert_eq!(export.windows[0].tabs[1].container, 2);
+        // Move the second window first and select its tab:
+        export.windows[1].index = 0;
+        export.windows[0].index = 1;
+        export.windows[1].tabs[0].selected = true;
+
+        let session: crate::FirefoxSessionStore =
+            serde_json::from_value(export.to_session_json()).unwrap();
+        let urls = session
+            .windows
+            .iter()
+            .map(|window| {
+                window
+                    .tabs
+                    .iter()
+                    .map(|tab| tab.entries[0].url.as_str())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            [
+                vec!["https://example.com/c"],
+                // Pinned tabs are moved first:
+                vec!["https://example.com/b", "https://example.com/a"],
+            ]
+        );
+        assert_eq!(session.windows[0].selected, 1);
+        assert_eq!(session.windows[1].tabs[0].pinned, Some(true));
+        assert_eq!(session.windows[1].tabs[0].user_context_id, 2);
+    }
+
+    #[test]
+    fn fold_adjacent_duplicates_merges_consecutive_tabs() {
+        let tab = |title: &str| tab(&format!("https://example.com/{title}"), title);
+        let windows = [window(vec![
+            tab("a"),
+            tab("a"),
+            tab("a"),
+            tab("b"),
+            tab("a"),
+        ])];
+        let folded = |fold_adjacent_duplicates| {
+            render(
+                &windows,
+                &ToLinksOptions {
+                    fold_adjacent_duplicates,
+                    ..format(LinkFormat::Markdown)
+                },
+            )
+        };
+        let output = folded(true);
+        let links = output
+            .lines()
+            .filter(|line| line.starts_with("- ["))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            [
+                "- [a ×3](https://example.com/a)",
+                "- [b](https://example.com/b)",
+                "- [a](https://example.com/a)",
+            ]
+        );
+        assert_eq!(
+            folded(false)
+                .matches("- [a](https://example.com/a)")
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn svg_format_indents_children_below_parents() {
+        let tab = |id: &str, parent: &str| {
+            let mut tab = tree_tab(id, parent);
+            tab["entries"][0]["title"] = format!("Tab <{id}>").into();
+            tab
+        };
+        let windows = [window(vec![tab("a", ""), tab("b", "a"), tab("c", "")])];
+        let output = render(
+            &windows,
+            &ToLinksOptions {
+                tree_sources: vec![TreeDataSource::TstLegacy].into(),
+                ..format(LinkFormat::Svg)
+            },
+        );
+        assert!(output.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg""#));
+        assert!(output.ends_with("</svg>\n"));
+        assert!(output.contains(r#"<text x="10" y="30" font-weight="bold">Window 1</text>"#));
+        assert!(output.contains(
+            r#"<a href="https://example.com/a"><text x="30" y="50">Tab &lt;a&gt;</text></a>"#
+        ));
+        assert!(output.contains(
+            r#"<a href="https://example.com/b"><text x="50" y="70">Tab &lt;b&gt;</text></a>"#
+        ));
+        assert!(output.contains(
+            r#"<a href="https://example.com/c"><text x="30" y="90">Tab &lt;c&gt;</text></a>"#
+        ));
+        // "b" is connected to "a" and "c" to the window's name:
+        assert!(output.contains(r#"<line x1="40" y1="54" x2="40" y2="65" />"#));
+        assert!(output.contains(r#"<line x1="20" y1="34" x2="20" y2="85" />"#));
+    }
+
+    #[test]
+    fn max_title_length_truncates_at_char_boundaries() {
+        let windows = [window(vec![tab("https://example.com/", "Ärger über Öl")])];
+        let truncated = |max_title_length| {
+            render(
+                &windows,
+                &ToLinksOptions {
+                    max_title_length,
+                    ..format(LinkFormat::Markdown)
+                },
+            )
+        };
+        // The 6th character is the multibyte "ü" which must not be split:
+        assert!(truncated(Some(7)).contains("- [Ärger …](https://example.com/)"));
+        assert!(truncated(Some(6)).contains("- [Ärger…](https://example.com/)"));
+        assert!(truncated(Some(1)).contains("- […](https://example.com/)"));
+        assert!(truncated(Some(0)).contains("- [](https://example.com/)"));
+        assert!(truncated(Some(13)).contains("- [Ärger über Öl](https://example.com/)"));
+        assert!(truncated(None).contains("- [Ärger über Öl](https://example.com/)"));
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn yaml_export_matches_json_export() {
+        let mut tab = tab("https://example.com/", "Example: \"quoted\"");
+        tab["userContextId"] = 1.into();
+        let windows = [window(vec![tab])];
+        let yaml: SessionExport =
+            serde_yaml::from_str(&render(&windows, &format(LinkFormat::Yaml))).unwrap();
+        let json: SessionExport =
+            serde_json::from_str(&render(&windows, &format(LinkFormat::Json))).unwrap();
+        assert_eq!(yaml, json);
+        assert_eq!(yaml.windows[0].tabs[0].title, "Example: \"quoted\"");
+    }
+
+    #[test]
+    fn collapse_subtrees_hides_descendants() {
+        let tab = |id: &str, parent: &str, collapsed: bool| {
+            let mut tab = tree_tab(id, parent);
+            tab["extData"]["treestyletab-subtree-collapsed"] = collapsed.to_string().into();
+            tab
+        };
+        let windows = [window(vec![
+            tab("a", "", true),
+            tab("b", "a", false),
+            tab("c", "b", false),
+            tab("d", "", false),
+        ])];
+        let collapse = |collapse_subtrees| {
+            render(
+                &windows,
+                &ToLinksOptions {
+                    tree_sources: vec![TreeDataSource::TstLegacy].into(),
+                    collapse_subtrees,
+                    ..format(LinkFormat::Markdown)
+                },
+            )
+        };
+        let collapsed = collapse(true);
+        assert!(collapsed.contains("- [a (+2 hidden)](https://example.com/a)"));
+        assert!(!collapsed.contains("https://example.com/b"));
+        assert!(!collapsed.contains("https://example.com/c"));
+        assert!(collapsed.contains("- [d](https://example.com/d)"));
+
+        let expanded = collapse(false);
+        assert!(expanded.contains("- [a](https://example.com/a)"));
+        assert!(expanded.contains("https://example.com/c"));
+    }
 }
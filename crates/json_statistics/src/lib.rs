@@ -1,13 +1,20 @@
 use either::Either;
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
 use serde_json::{Map, Number, Value};
 use std::borrow::Cow;
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
 
 pub mod print;
 pub mod type_script;
 
+#[cfg(feature = "with_num_format")]
+pub use num_format;
+
 use print::{StandardStatisticsFormatter, StatisticsFormatter};
 
 macro_rules! define_ref_enum {
@@ -71,6 +78,124 @@ impl<'a> fmt::Display for JSONStatisticsRef<'a> {
     }
 }
 
+/// Options that affect what data is gathered when collecting statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollectOptions {
+    /// Track how many distinct strings were encountered, see
+    /// [`JSONStringStatistics::distinct_hashes`].
+    pub distinct_strings: bool,
+    /// Remember the largest string and number, see [`LargestValue`].
+    pub largest_values: bool,
+    /// Remember the size of every encountered value instead of only their
+    /// count and total size, see [`Sizes::values`]. Enabled by default.
+    ///
+    /// Disabling this uses much less memory for large JSON data but the
+    /// histogram of string sizes can then not be printed. Array lengths and
+    /// object property counts are always kept since the size of arrays and
+    /// objects depends on them.
+    pub collect_sizes: bool,
+}
+impl Default for CollectOptions {
+    fn default() -> Self {
+        Self {
+            distinct_strings: false,
+            largest_values: false,
+            collect_sizes: true,
+        }
+    }
+}
+
+/// The sizes in characters of encountered values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sizes {
+    count: usize,
+    total: u64,
+    values: Option<Vec<u64>>,
+}
+impl Default for Sizes {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            total: 0,
+            values: Some(Vec::new()),
+        }
+    }
+}
+impl Sizes {
+    /// Add the size of another value. The size is only remembered if
+    /// [`CollectOptions::collect_sizes`] is enabled.
+    pub fn push(&mut self, size: u64, options: &CollectOptions) {
+        self.count += 1;
+        self.total += size;
+        if options.collect_sizes {
+            if let Some(values) = &mut self.values {
+                values.push(size);
+            }
+        } else {
+            self.values = None;
+        }
+    }
+    /// The number of sizes that were added.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+    /// The sum of all sizes that were added.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+    /// Every size that was added or `None` if some sizes weren't remembered
+    /// because [`CollectOptions::collect_sizes`] was disabled.
+    pub fn values(&self) -> Option<&[u64]> {
+        self.values.as_deref()
+    }
+    pub fn merge(&mut self, other: &Self) {
+        self.count += other.count;
+        self.total += other.total;
+        match (&mut self.values, &other.values) {
+            (Some(values), Some(other)) => values.extend_from_slice(other),
+            (values, _) => *values = None,
+        }
+    }
+}
+
+/// A preview of the largest value that was encountered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LargestValue {
+    /// The start of the value's text, ends with `…` if it was truncated.
+    pub preview: String,
+    /// The size of the value in bytes.
+    pub size: usize,
+}
+impl LargestValue {
+    /// Max number of characters kept from a value's text.
+    pub const MAX_PREVIEW_CHARS: usize = 200;
+
+    pub fn new(text: &str) -> Self {
+        let preview = match text.char_indices().nth(Self::MAX_PREVIEW_CHARS) {
+            Some((end, _)) => format!("{}…", &text[..end]),
+            None => text.to_owned(),
+        };
+        Self {
+            preview,
+            size: text.len(),
+        }
+    }
+    /// Keep whichever value is larger, preferring the current one if they
+    /// have the same size.
+    fn keep_largest(current: &mut Option<Self>, other: Cow<Option<Self>>) {
+        let other_size = match &*other {
+            Some(other) => other.size,
+            None => return,
+        };
+        if current
+            .as_ref()
+            .is_none_or(|current| current.size < other_size)
+        {
+            *current = other.into_owned();
+        }
+    }
+}
+
 pub trait Statistics: Clone + fmt::Display {
     /// The total size in number of characters for the data that the statistics were gathered for.
     fn size(&self) -> u64;
@@ -152,13 +277,16 @@ define_union_struct! {
 }
 impl JSONValueStatistics {
     pub fn add_value(&mut self, value: &Value) {
+        self.add_value_with_options(value, &CollectOptions::default())
+    }
+    pub fn add_value_with_options(&mut self, value: &Value, options: &CollectOptions) {
         match value {
             Value::Null => self.nulls.add_null(),
             Value::Bool(v) => self.booleans.add_bool(*v),
-            Value::Number(v) => self.numbers.add_number(v),
-            Value::String(v) => self.strings.add_string(v),
-            Value::Array(v) => self.arrays.add_array(v),
-            Value::Object(v) => self.objects.add_object(v),
+            Value::Number(v) => self.numbers.add_number_with_options(v, options),
+            Value::String(v) => self.strings.add_string_with_options(v, options),
+            Value::Array(v) => self.arrays.add_array_with_options(v, options),
+            Value::Object(v) => self.objects.add_object_with_options(v, options),
         }
     }
     pub fn with_formatter<'f>(
@@ -235,24 +363,43 @@ impl fmt::Display for JSONBooleanStatistics {
 #[derive(Default, Debug, Clone)]
 pub struct JSONNumberStatistics {
     /// The sizes in characters of the encountered values.
-    pub sizes: Vec<usize>,
+    pub sizes: Sizes,
+    /// The number with the most characters. Only tracked if
+    /// [`CollectOptions::largest_values`] was enabled.
+    pub largest: Option<LargestValue>,
 }
 impl JSONNumberStatistics {
     pub fn add_number(&mut self, value: &Number) {
+        self.add_number_with_options(value, &CollectOptions::default())
+    }
+    pub fn add_number_with_options(&mut self, value: &Number, options: &CollectOptions) {
         // TODO: better precision.
-        self.sizes.push(ToString::to_string(value).len())
+        let text = ToString::to_string(value);
+        self.sizes.push(text.len() as u64, options);
+        if options.largest_values {
+            LargestValue::keep_largest(
+                &mut self.largest,
+                Cow::Owned(Some(LargestValue::new(&text))),
+            );
+        }
     }
 }
 impl Statistics for JSONNumberStatistics {
     fn size(&self) -> u64 {
-        let value: usize = self.sizes.iter().sum();
-        value as u64
+        self.sizes.total()
     }
     fn count(&self) -> usize {
-        self.sizes.len()
+        self.sizes.count()
     }
     fn merge(&mut self, data: Cow<Self>) {
-        self.sizes.extend_from_slice(&data.sizes);
+        self.sizes.merge(&data.sizes);
+        LargestValue::keep_largest(
+            &mut self.largest,
+            match data {
+                Cow::Borrowed(v) => Cow::Borrowed(&v.largest),
+                Cow::Owned(v) => Cow::Owned(v.largest),
+            },
+        );
     }
 }
 impl fmt::Display for JSONNumberStatistics {
@@ -264,24 +411,67 @@ impl fmt::Display for JSONNumberStatistics {
 #[derive(Default, Debug, Clone)]
 pub struct JSONStringStatistics {
     /// The sizes in characters of the encountered values.
-    pub sizes: Vec<usize>,
+    pub sizes: Sizes,
+    /// Hashes of the encountered values. Only tracked if
+    /// [`CollectOptions::distinct_strings`] was enabled.
+    ///
+    /// Only 8 bytes are stored per distinct string instead of the string
+    /// itself, so memory usage stays bounded for sessions with many large
+    /// strings. The tradeoff is that hash collisions could make the distinct
+    /// count slightly too low, which is unlikely to matter for statistics.
+    pub distinct_hashes: Option<HashSet<u64>>,
+    /// The longest string. Only tracked if [`CollectOptions::largest_values`]
+    /// was enabled.
+    pub largest: Option<LargestValue>,
 }
 impl JSONStringStatistics {
     pub fn add_string(&mut self, value: &str) {
-        self.sizes.push(value.len());
+        self.add_string_with_options(value, &CollectOptions::default())
+    }
+    pub fn add_string_with_options(&mut self, value: &str, options: &CollectOptions) {
+        self.sizes.push(value.len() as u64, options);
+        if options.distinct_strings {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            self.distinct_hashes
+                .get_or_insert_with(HashSet::new)
+                .insert(hasher.finish());
+        }
+        if options.largest_values
+            && self
+                .largest
+                .as_ref()
+                .is_none_or(|largest| largest.size < value.len())
+        {
+            self.largest = Some(LargestValue::new(value));
+        }
+    }
+    /// The number of distinct strings that were encountered or `None` if that
+    /// wasn't tracked.
+    pub fn distinct_count(&self) -> Option<usize> {
+        self.distinct_hashes.as_ref().map(HashSet::len)
     }
 }
 impl Statistics for JSONStringStatistics {
     fn size(&self) -> u64 {
-        let mut value: usize = self.sizes.iter().sum();
-        value += 2 * self.sizes.len(); // start and end quotes.
-        value as u64
+        // Add start and end quotes.
+        self.sizes.total() + 2 * self.sizes.count() as u64
     }
     fn count(&self) -> usize {
-        self.sizes.len()
+        self.sizes.count()
     }
     fn merge(&mut self, data: Cow<Self>) {
-        self.sizes.extend_from_slice(&data.sizes);
+        self.sizes.merge(&data.sizes);
+        LargestValue::keep_largest(&mut self.largest, Cow::Borrowed(&data.largest));
+        let hashes = match data {
+            Cow::Borrowed(v) => Either::Left(v.distinct_hashes.iter().flatten().copied()),
+            Cow::Owned(v) => Either::Right(v.distinct_hashes.into_iter().flatten()),
+        };
+        for hash in hashes {
+            self.distinct_hashes
+                .get_or_insert_with(HashSet::new)
+                .insert(hash);
+        }
     }
 }
 impl fmt::Display for JSONStringStatistics {
@@ -295,7 +485,7 @@ pub struct JSONArrayStatistics {
     /// The lengths of arrays.
     pub lengths: Vec<usize>,
     /// The sizes in characters of the encountered arrays.
-    pub sizes: Vec<u64>,
+    pub sizes: Sizes,
     /// Info about the values that occurred in this array.
     pub values: Option<Box<JSONValueStatistics>>,
 }
@@ -307,19 +497,32 @@ impl JSONArrayStatistics {
         self.values.as_mut().unwrap()
     }
     pub fn add_array(&mut self, array: &[Value]) {
+        self.add_array_with_options(array, &CollectOptions::default())
+    }
+    pub fn add_array_with_options(&mut self, array: &[Value], options: &CollectOptions) {
         let mut stats = JSONValueStatistics::default();
         for value in array {
-            stats.add_value(value);
+            stats.add_value_with_options(value, options);
         }
-        self.sizes.push(stats.size());
-        self.lengths.push(array.len());
+        self.add_array_statistics(array.len(), stats, options);
+    }
+    /// Add an array with `len` items whose values have already been collected
+    /// into `stats`.
+    fn add_array_statistics(
+        &mut self,
+        len: usize,
+        stats: JSONValueStatistics,
+        options: &CollectOptions,
+    ) {
+        self.sizes.push(stats.size(), options);
+        self.lengths.push(len);
         self.get_values().merge(Cow::Owned(stats));
     }
 }
 impl Statistics for JSONArrayStatistics {
     fn size(&self) -> u64 {
         // Only content of the array not separators or [] at start and end of array.
-        let inner_size: u64 = self.sizes.iter().sum();
+        let inner_size = self.sizes.total();
 
         let separators: usize = self
             .lengths
@@ -331,11 +534,11 @@ impl Statistics for JSONArrayStatistics {
         inner_size + (separators as u64)
     }
     fn count(&self) -> usize {
-        self.sizes.len()
+        self.sizes.count()
     }
     fn merge(&mut self, data: Cow<Self>) {
         self.lengths.extend_from_slice(&data.lengths);
-        self.sizes.extend_from_slice(&data.sizes);
+        self.sizes.merge(&data.sizes);
         if data.values.is_some() {
             self.get_values().merge(match data {
                 Cow::Borrowed(v) => Cow::Borrowed(v.values.as_ref().unwrap()),
@@ -358,17 +561,24 @@ pub struct JSONObjectStatistics {
     ///
     /// This includes property keys but not their surrounding quotes and any characters used to describe values
     /// but not separators between values and between keys and values like (`:` and `,`).
-    pub sizes: Vec<u64>,
+    pub sizes: Sizes,
     /// Info about the properties that existed for this object.
     /// The key is the properties' names.
     pub properties: BTreeMap<String, JSONObjectPropertyStatistics>,
 }
 impl JSONObjectStatistics {
     pub fn add_object(&mut self, object: &Map<String, Value>) {
+        self.add_object_with_options(object, &CollectOptions::default())
+    }
+    pub fn add_object_with_options(
+        &mut self,
+        object: &Map<String, Value>,
+        options: &CollectOptions,
+    ) {
         let mut size = 0;
         for (key, value) in object.iter() {
             let mut data = JSONObjectPropertyStatistics::default();
-            data.add_value(value);
+            data.add_value_with_options(value, options);
 
             size += key.len() as u64;
             size += data.size();
@@ -376,8 +586,15 @@ impl JSONObjectStatistics {
             self.add_property(Cow::Borrowed(key), Cow::Owned(data));
         }
 
-        self.properties_count.push(object.len());
-        self.sizes.push(size);
+        self.add_object_size(object.len(), size, options);
+    }
+
+    /// Record an object with `count` properties whose keys and values had a
+    /// combined `size`. The properties themselves should be added with
+    /// [`Self::add_property`].
+    fn add_object_size(&mut self, count: usize, size: u64, options: &CollectOptions) {
+        self.properties_count.push(count);
+        self.sizes.push(size, options);
     }
 
     /// Add more data about a property. This will not update `sizes` or `properties_count`.
@@ -393,7 +610,7 @@ impl JSONObjectStatistics {
 }
 impl Statistics for JSONObjectStatistics {
     fn size(&self) -> u64 {
-        let inner_size: u64 = self.sizes.iter().sum();
+        let inner_size = self.sizes.total();
         // Data layout: {"KEY":VALUE,"KEY":VALUE}
         // KEY and VALUE text is included in `inner_size`.
         let quotes_and_separators: usize = self
@@ -413,10 +630,10 @@ impl Statistics for JSONObjectStatistics {
         inner_size + (quotes_and_separators as u64)
     }
     fn count(&self) -> usize {
-        self.sizes.len()
+        self.sizes.count()
     }
     fn merge(&mut self, data: Cow<Self>) {
-        self.sizes.extend_from_slice(&data.sizes);
+        self.sizes.merge(&data.sizes);
         self.properties_count
             .extend_from_slice(&data.properties_count);
         let properties = match data {
@@ -443,27 +660,34 @@ impl fmt::Display for JSONObjectStatistics {
 #[derive(Default, Debug, Clone)]
 pub struct JSONObjectPropertyStatistics {
     /// The sizes in characters of the encountered properties that has this properties name.
-    pub sizes: Vec<u64>,
+    pub sizes: Sizes,
     /// Info about the values that properties with this name had.
     pub value_info: JSONValueStatistics,
 }
 impl JSONObjectPropertyStatistics {
     pub fn add_value(&mut self, value: &Value) {
+        self.add_value_with_options(value, &CollectOptions::default())
+    }
+    pub fn add_value_with_options(&mut self, value: &Value, options: &CollectOptions) {
         let mut stats = JSONValueStatistics::default();
-        stats.add_value(value);
-        self.sizes.push(stats.size());
+        stats.add_value_with_options(value, options);
+        self.add_value_statistics(stats, options);
+    }
+    /// Add a single value that has already been collected into `stats`.
+    fn add_value_statistics(&mut self, stats: JSONValueStatistics, options: &CollectOptions) {
+        self.sizes.push(stats.size(), options);
         self.value_info.merge(Cow::Owned(stats));
     }
 }
 impl Statistics for JSONObjectPropertyStatistics {
     fn size(&self) -> u64 {
-        self.sizes.iter().sum()
+        self.sizes.total()
     }
     fn count(&self) -> usize {
-        self.sizes.len()
+        self.sizes.count()
     }
     fn merge(&mut self, data: Cow<Self>) {
-        self.sizes.extend_from_slice(&data.sizes);
+        self.sizes.merge(&data.sizes);
         self.value_info.merge(match data {
             Cow::Borrowed(v) => Cow::Borrowed(&v.value_info),
             Cow::Owned(v) => Cow::Owned(v.value_info),
@@ -477,12 +701,141 @@ impl fmt::Display for JSONObjectPropertyStatistics {
 }
 
 pub fn collect_statistics(json_value: &Value) -> JSONValueStatistics {
+    collect_statistics_with_options(json_value, &CollectOptions::default())
+}
+
+pub fn collect_statistics_with_options(
+    json_value: &Value,
+    options: &CollectOptions,
+) -> JSONValueStatistics {
     let mut stats = JSONValueStatistics::default();
-    stats.add_value(json_value);
+    stats.add_value_with_options(json_value, options);
 
     stats
 }
 
+/// Collect statistics for a single JSON value while it is being deserialized.
+///
+/// Unlike [`collect_statistics_with_options`] this never needs to hold the
+/// whole JSON document in memory, only the statistics about it. Use it with
+/// [`serde::de::DeserializeSeed::deserialize`] or see
+/// [`collect_statistics_from_reader`].
+///
+/// Note that if an object has duplicate keys then each of them will be
+/// counted, while a parsed [`Value`] would only keep the last one.
+#[derive(Debug, Clone, Copy)]
+pub struct StatisticsSeed<'a> {
+    pub options: &'a CollectOptions,
+}
+impl<'de> DeserializeSeed<'de> for StatisticsSeed<'_> {
+    type Value = JSONValueStatistics;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+impl<'de> Visitor<'de> for StatisticsSeed<'_> {
+    type Value = JSONValueStatistics;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any valid JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        let mut stats = JSONValueStatistics::default();
+        stats.nulls.add_null();
+        Ok(stats)
+    }
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.visit_unit()
+    }
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        let mut stats = JSONValueStatistics::default();
+        stats.booleans.add_bool(value);
+        Ok(stats)
+    }
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        let mut stats = JSONValueStatistics::default();
+        stats
+            .numbers
+            .add_number_with_options(&Number::from(value), self.options);
+        Ok(stats)
+    }
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        let mut stats = JSONValueStatistics::default();
+        stats
+            .numbers
+            .add_number_with_options(&Number::from(value), self.options);
+        Ok(stats)
+    }
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        // Non-finite numbers can't be represented in JSON and are treated as
+        // null by serde_json:
+        let Some(number) = Number::from_f64(value) else {
+            return self.visit_unit();
+        };
+        let mut stats = JSONValueStatistics::default();
+        stats.numbers.add_number_with_options(&number, self.options);
+        Ok(stats)
+    }
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        let mut stats = JSONValueStatistics::default();
+        stats.strings.add_string_with_options(value, self.options);
+        Ok(stats)
+    }
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut values = JSONValueStatistics::default();
+        let mut len = 0;
+        while let Some(item) = seq.next_element_seed(self)? {
+            values.merge(Cow::Owned(item));
+            len += 1;
+        }
+
+        let mut stats = JSONValueStatistics::default();
+        stats.arrays.add_array_statistics(len, values, self.options);
+        Ok(stats)
+    }
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut stats = JSONValueStatistics::default();
+        let objects = &mut stats.objects;
+        let mut count = 0;
+        let mut size = 0;
+        while let Some(key) = map.next_key::<String>()? {
+            let mut data = JSONObjectPropertyStatistics::default();
+            data.add_value_statistics(map.next_value_seed(self)?, self.options);
+
+            size += key.len() as u64;
+            size += data.size();
+            count += 1;
+
+            objects.add_property(Cow::Owned(key), Cow::Owned(data));
+        }
+        objects.add_object_size(count, size, self.options);
+        Ok(stats)
+    }
+}
+
+/// Collect statistics about JSON data as it is read, without first parsing it
+/// into a [`Value`]. The reader should be buffered.
+pub fn collect_statistics_from_reader<R: io::Read>(
+    reader: R,
+    options: &CollectOptions,
+) -> serde_json::Result<JSONValueStatistics> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let stats = StatisticsSeed { options }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(stats)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -491,4 +844,248 @@ mod tests {
     fn statistics_start_at_zero_size() {
         assert_eq!(Statistics::size(&super::JSONValueStatistics::default()), 0)
     }
+
+    #[test]
+    fn empty_arrays_and_objects_can_be_printed() {
+        let stats = collect_statistics(&serde_json::json!([[], {}, [{}], { "a": [] }]));
+        assert!(!stats.to_string().is_empty());
+
+        // Statistics that never had any values added:
+        assert!(!JSONArrayStatistics::default().to_string().is_empty());
+        assert!(!JSONObjectStatistics::default().to_string().is_empty());
+
+        let mut info = print::StatisticsInfoValues::new();
+        info.get_from_stats(&(&JSONArrayStatistics::default()).into());
+        assert_eq!(info.average_length, None);
+    }
+
+    #[test]
+    fn flat_paths_sorted_by_size() {
+        let stats = collect_statistics(&serde_json::json!({
+            "windows": [{ "tabs": [{ "url": "https://example.com/long/url" }, { "url": "a" }] }],
+            "version": 1,
+        }));
+        let text = stats
+            .with_formatter(print::FlatStatisticsFormatter::new())
+            .to_string();
+        let paths: Vec<_> = text
+            .lines()
+            .map(|line| line.split(" -> ").next().unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "(root)",
+                "windows",
+                "windows[]",
+                "windows[].tabs",
+                "windows[].tabs[]",
+                "windows[].tabs[].url",
+                "version",
+            ]
+        );
+        assert!(text.lines().last().unwrap().ends_with("-> 1 B"));
+    }
+
+    #[test]
+    fn largest_values() {
+        let long = "x".repeat(LargestValue::MAX_PREVIEW_CHARS + 10);
+        let value = serde_json::json!(["short", long, "medium value", 1, 123.5, -20]);
+
+        let stats = collect_statistics(&value);
+        let values = stats.arrays.values.as_ref().unwrap();
+        assert_eq!(values.strings.largest, None);
+
+        let stats = collect_statistics_with_options(
+            &value,
+            &CollectOptions {
+                largest_values: true,
+                ..Default::default()
+            },
+        );
+        let values = stats.arrays.values.as_ref().unwrap();
+        let largest = values.strings.largest.as_ref().unwrap();
+        assert_eq!(largest.size, long.len());
+        assert_eq!(
+            largest.preview,
+            format!("{}…", &long[..LargestValue::MAX_PREVIEW_CHARS])
+        );
+        assert_eq!(
+            values.numbers.largest,
+            Some(LargestValue {
+                preview: "123.5".to_owned(),
+                size: 5
+            })
+        );
+    }
+
+    #[test]
+    fn distinct_strings() {
+        let value = serde_json::json!({
+            "a": ["x", "y", "x"],
+            "b": { "c": "y", "d": "z" },
+        });
+
+        let stats = collect_statistics(&value);
+        assert_eq!(stats.strings.distinct_count(), None);
+
+        let stats = collect_statistics_with_options(
+            &value,
+            &CollectOptions {
+                distinct_strings: true,
+                ..Default::default()
+            },
+        );
+        let property = |name: &str| &stats.objects.properties[name].value_info;
+        assert_eq!(
+            property("a")
+                .arrays
+                .values
+                .as_ref()
+                .unwrap()
+                .strings
+                .distinct_count(),
+            Some(2)
+        );
+        assert_eq!(
+            property("b").objects.properties["c"]
+                .value_info
+                .strings
+                .distinct_count(),
+            Some(1)
+        );
+
+        let mut merged = property("a")
+            .arrays
+            .values
+            .as_ref()
+            .unwrap()
+            .strings
+            .clone();
+        merged.merge(Cow::Borrowed(
+            &property("b").objects.properties["d"].value_info.strings,
+        ));
+        assert_eq!(merged.count(), 4);
+        assert_eq!(merged.distinct_count(), Some(3));
+    }
+
+    #[test]
+    fn totals_without_collecting_sizes() {
+        let value = serde_json::json!({
+            "a": ["x", "yy", 10],
+            "b": { "c": "zzz", "d": null },
+            "e": [],
+        });
+        let all = collect_statistics(&value);
+        let totals = collect_statistics_with_options(
+            &value,
+            &CollectOptions {
+                collect_sizes: false,
+                ..Default::default()
+            },
+        );
+        assert_eq!(totals.size(), all.size());
+        assert_eq!(totals.size(), value.to_string().len() as u64);
+        assert_eq!(totals.count(), all.count());
+
+        let strings = &all.objects.properties["a"].value_info.arrays;
+        let strings = &strings.values.as_ref().unwrap().strings.sizes;
+        assert_eq!(strings.values(), Some(&[1, 2][..]));
+        let strings = &totals.objects.properties["a"].value_info.arrays;
+        let strings = &strings.values.as_ref().unwrap().strings.sizes;
+        assert_eq!(strings.values(), None);
+        assert_eq!((strings.count(), strings.total()), (2, 3));
+    }
+
+    #[test]
+    fn compare_paths_sorted_by_change() {
+        let old = collect_statistics(&serde_json::json!({
+            "tabs": [{ "url": "a" }],
+            "removed": "xxxx",
+            "same": 1,
+        }));
+        let new = collect_statistics(&serde_json::json!({
+            "tabs": [{ "url": "a" }, { "url": "https://example.com/" }],
+            "same": 1,
+        }));
+        let paths = print::compare_paths(&old, &new);
+        let changes = paths
+            .iter()
+            .map(|path| (path.path.as_str(), path.size_change()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            [
+                ("tabs", 31),
+                ("tabs[]", 30),
+                ("tabs[].url", 22),
+                ("", 14),
+                ("removed", -6),
+                ("same", 0),
+            ]
+        );
+        let removed = paths.iter().find(|path| path.path == "removed").unwrap();
+        assert_eq!((removed.old_count, removed.new_count), (1, 0));
+
+        let table = print::ComparisonTable {
+            paths: &paths,
+            ..Default::default()
+        }
+        .to_string();
+        assert!(table.lines().next().unwrap().starts_with("path"));
+        assert!(table.contains("removed"));
+        assert!(!table.contains("same"));
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        use print::Histogram;
+
+        assert_eq!(Histogram::bucket_range(0), 0..=15);
+        assert_eq!(Histogram::bucket_range(1), 16..=31);
+        assert_eq!(Histogram::bucket_range(2), 32..=63);
+        assert_eq!(Histogram::bucket_range(60), 1 << 63..=u64::MAX);
+
+        let histogram = Histogram::from_values([0, 15, 16, 40, 63, 64, u64::MAX]);
+        assert_eq!(histogram.buckets[..4], [2, 1, 2, 1]);
+        assert_eq!(histogram.buckets.len(), 61);
+        assert_eq!(histogram.buckets[60], 1);
+    }
+
+    #[test]
+    fn streamed_statistics_match_parsed_value() {
+        let text = r#"{
+            "windows": [{ "tabs": [{ "url": "https://example.com", "pinned": true }, {}], "x": -1.5 }],
+            "empty": [],
+            "nothing": null,
+            "version": [1, 18446744073709551615, 2.0e3]
+        }"#;
+        let options = CollectOptions {
+            distinct_strings: true,
+            largest_values: true,
+            ..Default::default()
+        };
+
+        let parsed =
+            collect_statistics_with_options(&serde_json::from_str(text).unwrap(), &options);
+        let streamed = collect_statistics_from_reader(text.as_bytes(), &options).unwrap();
+
+        assert_eq!(streamed.size(), parsed.size());
+        assert_eq!(streamed.count(), parsed.count());
+        for (streamed, parsed) in [
+            (streamed.to_string(), parsed.to_string()),
+            (
+                streamed
+                    .with_formatter(print::FlatStatisticsFormatter::new())
+                    .to_string(),
+                parsed
+                    .with_formatter(print::FlatStatisticsFormatter::new())
+                    .to_string(),
+            ),
+        ] {
+            assert_eq!(streamed, parsed);
+        }
+
+        assert!(collect_statistics_from_reader(&b"[1, 2] 3"[..], &options).is_err());
+    }
 }
@@ -1,6 +1,8 @@
-use super::{DynStatistics, JSONStatisticsRef};
+use super::{DynStatistics, JSONStatisticsRef, JSONValueStatistics, LargestValue, Statistics};
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::ops::RangeInclusive;
 
 pub trait StatisticsFormatter {
     /// Format a statistics entry.
@@ -28,7 +30,7 @@ impl FMTNumber {
         locale: num_format::Locale,
     ) -> fmt::Result {
         if let FMTNumber::Double(v) = self {
-            if v < 100_f64 || v > (std::u64::MAX as f64) {
+            if v < 100_f64 || v > (u64::MAX as f64) {
                 return fmt::Display::fmt(&self, f);
             } else {
                 self = FMTNumber::UInt64(v as u64);
@@ -82,6 +84,55 @@ pub enum ExtraFMTInfo {
     TrueCount,
     /// Number of booleans that were `false`.
     FalseCount,
+    /// Number of distinct strings, only available if that was tracked while
+    /// collecting statistics.
+    DistinctCount,
+    /// The largest string or number, only available if that was tracked while
+    /// collecting statistics.
+    Largest,
+    /// Print a histogram of the sizes of strings or the lengths of arrays on
+    /// separate lines after the other info. String sizes are only available
+    /// if [`CollectOptions::collect_sizes`](crate::CollectOptions) was
+    /// enabled.
+    Histogram,
+}
+
+/// Counts of values grouped into buckets that double in size: `0-15`, `16-31`,
+/// `32-63` and so on.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Histogram {
+    /// The number of values in each bucket, see [`Histogram::bucket_range`].
+    pub buckets: Vec<usize>,
+}
+impl Histogram {
+    /// Values in the first bucket are smaller than `1 << FIRST_BUCKET_BITS`.
+    const FIRST_BUCKET_BITS: u32 = 4;
+
+    pub fn from_values(values: impl IntoIterator<Item = u64>) -> Self {
+        let mut buckets = Vec::new();
+        for value in values {
+            let index = Self::bucket_index(value);
+            if buckets.len() <= index {
+                buckets.resize(index + 1, 0);
+            }
+            buckets[index] += 1;
+        }
+        Self { buckets }
+    }
+    fn bucket_index(value: u64) -> usize {
+        let bits = u64::BITS - value.leading_zeros();
+        bits.saturating_sub(Self::FIRST_BUCKET_BITS) as usize
+    }
+    /// The values that are counted by the bucket at `index`.
+    pub fn bucket_range(index: usize) -> RangeInclusive<u64> {
+        let end = |index: usize| {
+            1_u64
+                .checked_shl(Self::FIRST_BUCKET_BITS + index as u32)
+                .map_or(u64::MAX, |v| v - 1)
+        };
+        let start = if index == 0 { 0 } else { end(index - 1) + 1 };
+        start..=end(index)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -148,6 +199,8 @@ pub struct StatisticsInfoValues<'a> {
     pub size: Option<u64>,
     pub true_count: Option<u64>,
     pub average_length: Option<u64>,
+    pub distinct_count: Option<u64>,
+    pub largest: Option<LargestValue>,
     pub all_elements_have_same_size: bool,
 }
 impl<'a> StatisticsInfoValues<'a> {
@@ -173,6 +226,14 @@ impl<'a> StatisticsInfoValues<'a> {
             AverageLength => self.average_length.map(FMTNumber::UInt64).map(Into::into),
             TrueCount => self.true_count.map(FMTNumber::UInt64).map(Into::into),
             FalseCount => self.false_count().map(FMTNumber::UInt64).map(Into::into),
+            DistinctCount => self.distinct_count.map(FMTNumber::UInt64).map(Into::into),
+            Largest => self
+                .largest
+                .as_ref()
+                .map(|largest| Cow::from(format!("{:?} ({} bytes)", largest.preview, largest.size)))
+                .map(Into::into),
+            // Not a single value, handled by the formatter:
+            Histogram => None,
         }
     }
 
@@ -195,23 +256,22 @@ impl<'a> StatisticsInfoValues<'a> {
                 self.set_name("bool");
                 self.true_count = Some(stats.true_count as u64);
             }
-            JSONNumber(_) => {
+            JSONNumber(stats) => {
                 self.set_name("number");
+                self.largest = stats.largest.clone();
             }
-            JSONString(_) => {
+            JSONString(stats) => {
                 self.set_name("string");
+                self.distinct_count = stats.distinct_count().map(|count| count as u64);
+                self.largest = stats.largest.clone();
             }
             JSONArray(stats) => {
                 self.set_name("array");
-                self.average_length =
-                    Some((stats.lengths.iter().sum::<usize>() / stats.lengths.len()) as u64);
+                self.average_length = average(&stats.lengths);
             }
             JSONObject(stats) => {
                 self.set_name("object");
-                self.average_length = Some(
-                    (stats.properties_count.iter().sum::<usize>() / stats.properties_count.len())
-                        as u64,
-                );
+                self.average_length = average(&stats.properties_count);
             }
             JSONObjectProperty(_) => (),
         }
@@ -229,6 +289,15 @@ impl<'a> StatisticsInfoValues<'a> {
     }
 }
 
+/// The rounded down average of some values or `None` if there are no values.
+fn average(values: &[usize]) -> Option<u64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some((values.iter().sum::<usize>() / values.len()) as u64)
+    }
+}
+
 pub struct StatisticsInfoTypeNames {
     pub is_object: bool,
 }
@@ -248,6 +317,9 @@ impl StatisticsInfoTypeNames {
             ExtraFMTInfo::Size => "size".into(),
             ExtraFMTInfo::TrueCount => "true".into(),
             ExtraFMTInfo::FalseCount => "false".into(),
+            ExtraFMTInfo::DistinctCount => "distinct".into(),
+            ExtraFMTInfo::Largest => "largest".into(),
+            ExtraFMTInfo::Histogram => "histogram".into(),
         }
     }
 }
@@ -286,6 +358,8 @@ impl<'a> StandardStatisticsFormatter<'a> {
                 ExtraFMTInfo::AverageLength,
                 ExtraFMTInfo::TrueCount,
                 ExtraFMTInfo::FalseCount,
+                ExtraFMTInfo::DistinctCount,
+                ExtraFMTInfo::Largest,
             ])),
             format_options,
         }
@@ -307,6 +381,13 @@ impl<'a> StandardStatisticsFormatter<'a> {
             format_options: self.format_options.clone(),
         }
     }
+    /// Print another type of info if it isn't already printed.
+    pub fn add_info_to_print(&mut self, info: ExtraFMTInfo) {
+        let infos = self.infos_to_print.get_or_insert_with(Default::default);
+        if !infos.contains(&info) {
+            infos.to_mut().push(info);
+        }
+    }
     /// Set the text that should be written before and after info entries.
     pub fn set_surround_info(
         &mut self,
@@ -329,6 +410,40 @@ impl<'a> StandardStatisticsFormatter<'a> {
     fn write_property_name(&self, f: &mut fmt::Formatter, name: &str) -> fmt::Result {
         write!(f, "\"{}\": ", name)
     }
+    /// Write each bucket of a histogram on its own line.
+    fn write_histogram(&self, f: &mut fmt::Formatter, histogram: &Histogram) -> fmt::Result {
+        const MAX_BAR_WIDTH: usize = 20;
+        let max_count = histogram.buckets.iter().copied().max().unwrap_or(0);
+        let first = histogram.buckets.iter().position(|&count| count > 0);
+        let label = |index: usize| {
+            let range = Histogram::bucket_range(index);
+            format!("{}-{}", range.start(), range.end())
+        };
+        let label_width = label(histogram.buckets.len().saturating_sub(1)).len();
+        for (index, &count) in histogram
+            .buckets
+            .iter()
+            .enumerate()
+            .skip(first.unwrap_or(0))
+        {
+            let bar_width = if count == 0 {
+                0
+            } else {
+                (count * MAX_BAR_WIDTH / max_count).max(1)
+            };
+            self.write_new_line(f)?;
+            write!(
+                f,
+                "{:<width$} |{} ",
+                label(index),
+                "#".repeat(bar_width),
+                width = label_width,
+            )?;
+            let count = FMTInfoValue::from(FMTNumber::UInt64(count as u64));
+            write!(f, "{}", self.format_options.format_with(&count))?;
+        }
+        Ok(())
+    }
 }
 impl StatisticsFormatter for StandardStatisticsFormatter<'_> {
     fn format_entry(&mut self, f: &mut fmt::Formatter, stats: JSONStatisticsRef) -> fmt::Result {
@@ -353,6 +468,24 @@ impl StatisticsFormatter for StandardStatisticsFormatter<'_> {
                     }
                 }
             }
+
+            if infos_to_print.contains(&ExtraFMTInfo::Histogram) {
+                let histogram = match stats {
+                    JSONStatisticsRef::JSONString(stats) => stats
+                        .sizes
+                        .values()
+                        .map(|sizes| Histogram::from_values(sizes.iter().copied())),
+                    JSONStatisticsRef::JSONArray(stats) => Some(Histogram::from_values(
+                        stats.lengths.iter().map(|&length| length as u64),
+                    )),
+                    _ => None,
+                };
+                if let Some(histogram) = histogram {
+                    let mut nested_options = self.as_borrowed();
+                    nested_options.indents += 1;
+                    nested_options.write_histogram(f, &histogram)?;
+                }
+            }
         }
 
         use JSONStatisticsRef::*;
@@ -406,3 +539,227 @@ impl StatisticsFormatter for StandardStatisticsFormatter<'_> {
         Ok(())
     }
 }
+
+/// Lists the total size of every path in the JSON data, largest first. Paths
+/// look like `windows[].tabs[].entries[].url` where `[]` means any item in an
+/// array.
+#[derive(Default, Debug, Clone)]
+pub struct FlatStatisticsFormatter {
+    /// Only list this many of the largest paths.
+    pub max_entries: Option<usize>,
+}
+impl FlatStatisticsFormatter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collect the counts and sizes of a value and all values nested inside
+    /// it.
+    fn collect_value_paths(
+        path: String,
+        stats: &JSONValueStatistics,
+        out: &mut Vec<(String, usize, u64)>,
+    ) {
+        if stats.count() == 0 {
+            return;
+        }
+        for (key, property) in stats.objects.properties.iter() {
+            let nested_path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            Self::collect_value_paths(nested_path, &property.value_info, out);
+        }
+        if let Some(values) = &stats.arrays.values {
+            Self::collect_value_paths(format!("{}[]", path), values, out);
+        }
+        out.push((path, stats.count(), stats.size()));
+    }
+
+    /// Format a size in bytes using the largest fitting unit.
+    fn write_size(f: &mut fmt::Formatter, size: u64) -> fmt::Result {
+        const UNITS: &[&str] = &["KB", "MB", "GB", "TB"];
+        if size < 1024 {
+            return write!(f, "{} B", size);
+        }
+        let mut value = size as f64;
+        let mut unit = "B";
+        for next_unit in UNITS {
+            if value < 1024.0 {
+                break;
+            }
+            value /= 1024.0;
+            unit = next_unit;
+        }
+        write!(f, "{:.1} {}", value, unit)
+    }
+}
+impl StatisticsFormatter for FlatStatisticsFormatter {
+    fn format_entry(&mut self, f: &mut fmt::Formatter, stats: JSONStatisticsRef) -> fmt::Result {
+        let mut paths = Vec::new();
+        use JSONStatisticsRef::*;
+        match stats {
+            JSONValue(stats) => Self::collect_value_paths(String::new(), stats, &mut paths),
+            JSONObjectProperty(stats) => {
+                Self::collect_value_paths(String::new(), &stats.value_info, &mut paths)
+            }
+            JSONObject(stats) => {
+                let value = JSONValueStatistics {
+                    objects: stats.clone(),
+                    ..Default::default()
+                };
+                Self::collect_value_paths(String::new(), &value, &mut paths)
+            }
+            JSONArray(stats) => {
+                let value = JSONValueStatistics {
+                    arrays: stats.clone(),
+                    ..Default::default()
+                };
+                Self::collect_value_paths(String::new(), &value, &mut paths)
+            }
+            JSONNull(_) | JSONBoolean(_) | JSONNumber(_) | JSONString(_) => {
+                paths.push((String::new(), stats.boxed_count(), stats.boxed_size()))
+            }
+        }
+        paths.sort_by(|(a_path, _, a_size), (b_path, _, b_size)| {
+            b_size.cmp(a_size).then_with(|| a_path.cmp(b_path))
+        });
+
+        for (path, _, size) in paths.iter().take(self.max_entries.unwrap_or(usize::MAX)) {
+            write!(f, "{} -> ", if path.is_empty() { "(root)" } else { path })?;
+            Self::write_size(f, *size)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The count and total size of a path in two different sets of statistics,
+/// see [`compare_paths`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PathComparison {
+    /// A path like the ones listed by [`FlatStatisticsFormatter`]. Empty for
+    /// the root value.
+    pub path: String,
+    pub old_count: usize,
+    pub new_count: usize,
+    pub old_size: u64,
+    pub new_size: u64,
+}
+impl PathComparison {
+    /// How much larger the path's values are in the new statistics. Negative
+    /// if they got smaller.
+    pub fn size_change(&self) -> i128 {
+        i128::from(self.new_size) - i128::from(self.old_size)
+    }
+    /// `true` if both the count and size of the path are the same.
+    pub fn is_unchanged(&self) -> bool {
+        self.old_count == self.new_count && self.old_size == self.new_size
+    }
+}
+
+/// Compare the count and size of every path in two sets of statistics. Paths
+/// that only exist in one of them have a count and size of zero in the other.
+///
+/// The paths are sorted by how much their size changed, largest change first.
+pub fn compare_paths(old: &JSONValueStatistics, new: &JSONValueStatistics) -> Vec<PathComparison> {
+    let mut old_paths = Vec::new();
+    FlatStatisticsFormatter::collect_value_paths(String::new(), old, &mut old_paths);
+    let mut new_paths = Vec::new();
+    FlatStatisticsFormatter::collect_value_paths(String::new(), new, &mut new_paths);
+
+    let mut paths = BTreeMap::<String, PathComparison>::new();
+    for (path, count, size) in old_paths {
+        let entry = paths.entry(path).or_default();
+        entry.old_count = count;
+        entry.old_size = size;
+    }
+    for (path, count, size) in new_paths {
+        let entry = paths.entry(path).or_default();
+        entry.new_count = count;
+        entry.new_size = size;
+    }
+
+    let mut paths = paths
+        .into_iter()
+        .map(|(path, comparison)| PathComparison { path, ..comparison })
+        .collect::<Vec<_>>();
+    paths.sort_by(|a, b| {
+        b.size_change()
+            .unsigned_abs()
+            .cmp(&a.size_change().unsigned_abs())
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    paths
+}
+
+/// Writes the result of [`compare_paths`] as a table with one path per line.
+#[derive(Debug, Clone, Default)]
+pub struct ComparisonTable<'a> {
+    pub paths: &'a [PathComparison],
+    /// Only list this many paths.
+    pub max_entries: Option<usize>,
+    /// Also list paths whose count and size didn't change.
+    pub include_unchanged: bool,
+}
+impl fmt::Display for ComparisonTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        struct Size(u64);
+        impl fmt::Display for Size {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                FlatStatisticsFormatter::write_size(f, self.0)
+            }
+        }
+
+        let rows = self
+            .paths
+            .iter()
+            .filter(|path| self.include_unchanged || !path.is_unchanged())
+            .take(self.max_entries.unwrap_or(usize::MAX))
+            .map(|path| {
+                let change = path.size_change();
+                [
+                    if path.path.is_empty() {
+                        "(root)".to_owned()
+                    } else {
+                        path.path.clone()
+                    },
+                    Size(path.old_size).to_string(),
+                    Size(path.new_size).to_string(),
+                    format!(
+                        "{}{}",
+                        if change < 0 { "-" } else { "+" },
+                        Size(u64::try_from(change.unsigned_abs()).unwrap_or(u64::MAX))
+                    ),
+                    path.old_count.to_string(),
+                    path.new_count.to_string(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let header = [
+            "path",
+            "old size",
+            "new size",
+            "change",
+            "old count",
+            "new count",
+        ]
+        .map(str::to_owned);
+        let mut widths = [0; 6];
+        for row in std::iter::once(&header).chain(&rows) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        for row in std::iter::once(&header).chain(&rows) {
+            write!(f, "{:<width$}", row[0], width = widths[0])?;
+            for (cell, width) in row.iter().zip(widths).skip(1) {
+                write!(f, "  {:>width$}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}